@@ -0,0 +1,1309 @@
+//! The logic of cargo-bounds that doesnt need the command line: which versions of a bound to
+//! test, searching for the edges of a bound and writing the found bound as a requirement.
+//!
+//! Nothing in here runs cargo or prints anything. Checking a version is left to the caller, and
+//! what happens during a search is reported through [`Progress`].
+//!
+//! Everything works on a single requirement and the versions the caller fetched. Reading a
+//! manifest, pinning versions and running the checks are left to the binary.
+
+use std::{
+    fmt::{self, Display},
+    str::FromStr,
+    time::Duration,
+};
+
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+
+/// The outcome of checking one version.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum TestResult {
+    #[serde(rename = "fail")]
+    Fail,
+    #[serde(rename = "pass")]
+    Sucess,
+    /// The check ran against a different version than the one pinned.
+    #[serde(rename = "indeterminate")]
+    Indeterminate,
+}
+
+impl TestResult {
+    /// Count an indeterminate result as a failure, for searches that need a yes or no.
+    pub fn or_fail(self) -> TestResult {
+        match self {
+            TestResult::Indeterminate => TestResult::Fail,
+            result => result,
+        }
+    }
+}
+
+/// How densely the versions in a bound are tested.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Granularity {
+    /// One version per breaking series, which for `0.x` is one per minor.
+    Major,
+    /// One version per minor.
+    Minor,
+    /// Every version.
+    Patch,
+}
+
+impl Display for Granularity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Granularity::Major => write!(f, "major"),
+            Granularity::Minor => write!(f, "minor"),
+            Granularity::Patch => write!(f, "patch"),
+        }
+    }
+}
+
+impl FromStr for Granularity {
+    type Err = anyhow::Error;
+
+    fn from_str(granularity: &str) -> Result<Self> {
+        match granularity {
+            "major" => Ok(Granularity::Major),
+            "minor" => Ok(Granularity::Minor),
+            "patch" => Ok(Granularity::Patch),
+            _ => Err(anyhow!(
+                "unknown granularity {granularity}, expected major, minor or patch"
+            )),
+        }
+    }
+}
+
+impl Granularity {
+    /// Whether `a` and `b` are in the same series, so only one of them needs testing.
+    pub fn same_series(self, a: &semver::Version, b: &semver::Version) -> bool {
+        match self {
+            Granularity::Major if a.major != 0 => a.major == b.major,
+            Granularity::Major | Granularity::Minor => a.major == b.major && a.minor == b.minor,
+            Granularity::Patch => a == b,
+        }
+    }
+}
+
+/// The first version of each breaking series in the sorted `versions`, see [`Granularity::Major`].
+pub fn breaking_series(versions: &[semver::Version]) -> Vec<&semver::Version> {
+    let mut series = Vec::<&semver::Version>::new();
+    for version in versions {
+        if series
+            .last()
+            .is_none_or(|first| !Granularity::Major.same_series(first, version))
+        {
+            series.push(version);
+        }
+    }
+    series
+}
+
+/// Which version of each series gets tested.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Sample {
+    /// The first release of the series.
+    #[default]
+    First,
+    /// The last release of the series, which is what the resolver picks.
+    Last,
+    /// Both the first and the last release.
+    Both,
+}
+
+impl Display for Sample {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Sample::First => write!(f, "first"),
+            Sample::Last => write!(f, "last"),
+            Sample::Both => write!(f, "both"),
+        }
+    }
+}
+
+impl FromStr for Sample {
+    type Err = anyhow::Error;
+
+    fn from_str(sample: &str) -> Result<Self> {
+        match sample {
+            "first" => Ok(Sample::First),
+            "last" => Ok(Sample::Last),
+            "both" => Ok(Sample::Both),
+            _ => Err(anyhow!(
+                "unknown sample {sample}, expected first, last or both"
+            )),
+        }
+    }
+}
+
+/// Pick the versions to test out of a sorted list, the newest version is always included.
+pub fn select_versions(
+    versions: &[semver::Version],
+    granularity: Granularity,
+    sample: Sample,
+) -> Vec<semver::Version> {
+    let mut selected = Vec::new();
+    for series in versions.chunk_by(|a, b| granularity.same_series(a, b)) {
+        let (first, last) = (&series[0], &series[series.len() - 1]);
+        if sample != Sample::Last {
+            selected.push(first.clone());
+        }
+        if sample != Sample::First && (first != last || sample == Sample::Last) {
+            selected.push(last.clone());
+        }
+    }
+    if let Some(newest) = versions.last()
+        && selected.last() != Some(newest)
+    {
+        selected.push(newest.clone());
+    }
+    selected
+}
+
+/// Keep at most `max` of `versions`, spread evenly and always keeping the oldest and newest.
+///
+/// With a `max` of 1 only the newest is kept, as there is no room for both ends.
+pub fn thin_out<T: Clone>(versions: &mut Vec<T>, max: usize) {
+    if versions.len() <= max {
+        return;
+    }
+    if max < 2 {
+        let newest = versions.pop();
+        versions.clear();
+        versions.extend(newest.filter(|_| max == 1));
+        return;
+    }
+    let last = versions.len() - 1;
+    *versions = (0..max)
+        .map(|index| versions[index * last / (max - 1)].clone())
+        .collect();
+}
+
+/// Keep the versions of at most `per_major` minors of each major out of a sorted list, spread
+/// evenly and always keeping the first and last minor.
+///
+/// The `0.x` minors are breaking series of their own, so they are all kept.
+pub fn spread_minors(versions: &[semver::Version], per_major: usize) -> Vec<semver::Version> {
+    let mut kept = Vec::new();
+    for major in versions.chunk_by(|a, b| a.major == b.major) {
+        let mut minors = major
+            .iter()
+            .map(|version| version.minor)
+            .collect::<Vec<_>>();
+        minors.dedup();
+        if major[0].major != 0 {
+            thin_out(&mut minors, per_major);
+        }
+        kept.extend(
+            major
+                .iter()
+                .filter(|version| minors.contains(&version.minor))
+                .cloned(),
+        );
+    }
+    kept
+}
+
+/// Pick the finest granularity whose untested versions are expected to fit in the budget.
+pub fn refine_granularity(
+    versions: &[semver::Version],
+    tested: &[semver::Version],
+    per_check: Duration,
+    budget: Duration,
+    sample: Sample,
+) -> Granularity {
+    for granularity in [Granularity::Patch, Granularity::Minor] {
+        let remaining = select_versions(versions, granularity, sample)
+            .iter()
+            .filter(|version| !tested.contains(version))
+            .count();
+        if per_check * remaining as u32 <= budget {
+            return granularity;
+        }
+    }
+    Granularity::Major
+}
+
+/// Get the version of a requirement of the form `=x.y.z`.
+pub fn exact_pin(bound: &semver::VersionReq) -> Option<semver::Version> {
+    let [comparator] = bound.comparators.as_slice() else {
+        return None;
+    };
+    if comparator.op != semver::Op::Exact {
+        return None;
+    }
+    Some(semver::Version {
+        major: comparator.major,
+        minor: comparator.minor?,
+        patch: comparator.patch?,
+        pre: comparator.pre.clone(),
+        build: semver::BuildMetadata::EMPTY,
+    })
+}
+
+/// A requirement without the failing versions of the one it replaces, see [`tighten_requirement`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tightened {
+    pub requirement: String,
+    /// The oldest version the requirement allows.
+    pub min: semver::Version,
+    /// The newest version the requirement allows.
+    pub max: semver::Version,
+    /// Failing versions between passing ones, which had to be left out with the passing
+    /// versions below them.
+    pub holes: Vec<semver::Version>,
+}
+
+/// Tighten the requirement `old` so it allows none of the `failing` versions.
+///
+/// Failures at the old end raise the lower bound and failures at the new end lower the upper
+/// one, just past the failing versions, so untested versions next to them stay allowed.
+/// Cargo has no `!=`, so failures in the middle cant be left out on their own: the range is
+/// then cut down to the passing versions around the newest one. `versions` are the published
+/// versions, sorted. Returns `None` when no failing version is allowed or none passed.
+pub fn tighten_requirement(
+    old: &str,
+    passing: &[semver::Version],
+    failing: &[semver::Version],
+    versions: &[semver::Version],
+) -> Option<Tightened> {
+    let bound = semver::VersionReq::parse(old).ok()?;
+    let mut tested = passing
+        .iter()
+        .map(|version| (version, true))
+        .chain(failing.iter().map(|version| (version, false)))
+        .filter(|(version, _)| bound.matches(version))
+        .collect::<Vec<_>>();
+    tested.sort();
+    if tested.iter().all(|(_, passed)| *passed) {
+        return None;
+    }
+    let oldest_pass = tested.iter().position(|(_, passed)| *passed)?;
+    let newest_pass = tested.iter().rposition(|(_, passed)| *passed)?;
+    let below = tested[..newest_pass]
+        .iter()
+        .rposition(|(_, passed)| !passed);
+    let above = tested[newest_pass..]
+        .iter()
+        .find(|(_, passed)| !passed)
+        .map(|(version, _)| *version);
+    let holes = match below {
+        Some(below) if below > oldest_pass => tested[oldest_pass..=below]
+            .iter()
+            .filter(|(_, passed)| !passed)
+            .map(|(version, _)| (*version).clone())
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    let allowed = versions
+        .iter()
+        .filter(|version| bound.matches(version))
+        .filter(|version| below.is_none_or(|below| *version > tested[below].0))
+        .filter(|version| above.is_none_or(|above| *version < above))
+        .collect::<Vec<_>>();
+    let (min, max) = (*allowed.first()?, *allowed.last()?);
+    let requirement = restyle_requirement(old, min, max, versions);
+    (requirement != old.trim()).then(|| Tightened {
+        requirement,
+        min: min.clone(),
+        max: max.clone(),
+        holes,
+    })
+}
+
+/// The newest of `versions` below `bound` and the oldest above it, for a bound they dont match.
+///
+/// A version is below when a comparator rejects it for being too old, like `2.2.9` for `^2.3`,
+/// and above otherwise.
+pub fn closest_outside<'a>(
+    bound: &semver::VersionReq,
+    versions: &'a [semver::Version],
+) -> (Option<&'a semver::Version>, Option<&'a semver::Version>) {
+    use semver::Op;
+
+    let too_old = |version: &semver::Version| {
+        bound.comparators.iter().any(|comparator| {
+            let lowest = semver::Version {
+                major: comparator.major,
+                minor: comparator.minor.unwrap_or(0),
+                patch: comparator.patch.unwrap_or(0),
+                pre: comparator.pre.clone(),
+                build: semver::BuildMetadata::EMPTY,
+            };
+            !matches!(comparator.op, Op::Less | Op::LessEq)
+                && !comparator.matches(version)
+                && *version <= lowest
+        })
+    };
+    let outside = || versions.iter().filter(|version| !bound.matches(version));
+    (
+        outside().filter(|version| too_old(version)).max(),
+        outside().filter(|version| !too_old(version)).min(),
+    )
+}
+
+/// Write the bound `>=min, <=max` in the style of the requirement `old` it replaces.
+///
+/// Requirements count as the same when they allow the same published `versions`. `old` is kept
+/// as is when it already allows just the found ones, otherwise its operators and precision are
+/// reused where possible, so a tilde stays a tilde and a `<2.0.0-0` upper bound keeps its form.
+/// Falls back to [`suggest_requirement`].
+pub fn restyle_requirement(
+    old: &str,
+    min: &semver::Version,
+    max: &semver::Version,
+    versions: &[semver::Version],
+) -> String {
+    use semver::Op;
+
+    let allows_found = |candidate: &String| allows_same(candidate, min, max, versions);
+
+    let mut candidates = Vec::new();
+    if let Ok(old_req) = semver::VersionReq::parse(old) {
+        // `*` always allows the found versions, but replacing it is the point.
+        if !old_req.comparators.is_empty() {
+            candidates.push(old.trim().to_owned());
+        }
+        match old_req.comparators.as_slice() {
+            // `1.*` means the same as `1`.
+            [single] if single.op == Op::Wildcard => {
+                let styled = comparator_like(single, Op::Caret, min).to_string();
+                candidates.push(styled.trim_start_matches('^').to_owned());
+            }
+            [single] if matches!(single.op, Op::Caret | Op::Tilde | Op::Exact) => {
+                let styled = comparator_like(single, single.op, min).to_string();
+                // `1.2` and `^1.2` mean the same, keep whichever was written.
+                match styled.strip_prefix('^') {
+                    Some(bare) if !old.trim_start().starts_with('^') => {
+                        candidates.push(bare.to_owned())
+                    }
+                    _ => candidates.push(styled),
+                }
+            }
+            comparators => {
+                let separator = if old.contains(", ") { ", " } else { "," };
+                let lower = comparators
+                    .iter()
+                    .find(|comparator| matches!(comparator.op, Op::Greater | Op::GreaterEq));
+                let upper = comparators
+                    .iter()
+                    .find(|comparator| matches!(comparator.op, Op::Less | Op::LessEq));
+                let mut lowers = Vec::new();
+                if let Some(lower) = lower {
+                    lowers.push(lower.to_string());
+                    lowers.push(comparator_like(lower, Op::GreaterEq, min).to_string());
+                }
+                let mut uppers = Vec::new();
+                if let Some(upper) = upper {
+                    uppers.push(upper.to_string());
+                    if upper.op == Op::Less {
+                        let next = next_release(upper, max);
+                        uppers.push(comparator_like(upper, Op::Less, &next).to_string());
+                    } else {
+                        uppers.push(comparator_like(upper, Op::LessEq, max).to_string());
+                    }
+                }
+                for lower in &lowers {
+                    for upper in &uppers {
+                        candidates.push(format!("{lower}{separator}{upper}"));
+                    }
+                }
+            }
+        }
+    }
+    candidates
+        .into_iter()
+        .find(allows_found)
+        .unwrap_or_else(|| suggest_requirement(min, max, versions))
+}
+
+/// The requirement people would write for `>=min, <=max`, allowing the same published `versions`.
+///
+/// A range that reaches the newest release of its breaking series doesnt need an upper bound on
+/// patches: within one series it's a caret requirement like `0.5.2`, across several it's
+/// `>=0.5.2, <0.9`. Otherwise the upper bound is the next minor, or `<=max` as a last resort.
+pub fn suggest_requirement(
+    min: &semver::Version,
+    max: &semver::Version,
+    versions: &[semver::Version],
+) -> String {
+    use semver::Op;
+
+    // The part of the version that a breaking release bumps, `0.x` versions break on the minor.
+    let series = |version: &semver::Version| match (version.major, version.minor) {
+        (0, 0) => (0, 0, version.patch),
+        (0, minor) => (0, minor, 0),
+        (major, _) => (major, 0, 0),
+    };
+    let newest_of = |same: &dyn Fn(&semver::Version) -> bool| {
+        versions.iter().filter(|version| same(version)).max() == Some(max)
+    };
+    let bare = semver::Comparator {
+        op: Op::Caret,
+        major: 0,
+        minor: None,
+        patch: None,
+        pre: semver::Prerelease::EMPTY,
+    };
+    let lower = comparator_like(&bare, Op::GreaterEq, min);
+
+    let mut candidates = Vec::new();
+    if newest_of(&|version| series(version) == series(max)) {
+        if series(min) == series(max) {
+            let caret = comparator_like(&bare, Op::Caret, min).to_string();
+            candidates.push(caret.trim_start_matches('^').to_owned());
+        }
+        candidates.push(format!("{lower}, <{}", next_series(max)));
+    }
+    if newest_of(&|version| (version.major, version.minor) == (max.major, max.minor)) {
+        candidates.push(format!("{lower}, <{}.{}", max.major, max.minor + 1));
+    }
+    candidates
+        .into_iter()
+        .find(|candidate| allows_same(candidate, min, max, versions))
+        .unwrap_or_else(|| format!(">={min}, <={max}"))
+}
+
+/// The first release of the breaking series after the one of `max`, like `2` for `1.4.2` and
+/// `0.5` for `0.4.1`.
+fn next_series(max: &semver::Version) -> String {
+    match (max.major, max.minor) {
+        (0, 0) => format!("0.0.{}", max.patch + 1),
+        (0, minor) => format!("0.{}", minor + 1),
+        (major, _) => format!("{}", major + 1),
+    }
+}
+
+/// Widen the requirement `old` so it allows everything up to `max` too, keeping its lower bound
+/// as written.
+///
+/// `^1.2` and `~1.2` start at `>=1.2`. The upper bound keeps the form of the old one where it
+/// can, otherwise it is the next breaking series or minor, or `<=max` as a last resort.
+pub fn raise_upper(old: &str, max: &semver::Version, versions: &[semver::Version]) -> String {
+    use semver::Op;
+
+    let Ok(old_req) = semver::VersionReq::parse(old) else {
+        return format!("<={max}");
+    };
+    let separator = if old.contains(',') && !old.contains(", ") {
+        ","
+    } else {
+        ", "
+    };
+    let lower = old_req
+        .comparators
+        .iter()
+        .filter_map(|comparator| match comparator.op {
+            Op::Greater | Op::GreaterEq => Some(comparator.to_string()),
+            Op::Caret | Op::Tilde | Op::Exact | Op::Wildcard => Some(
+                semver::Comparator {
+                    op: Op::GreaterEq,
+                    ..comparator.clone()
+                }
+                .to_string(),
+            ),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join(separator);
+    let lower_req = semver::VersionReq::parse(&lower).unwrap_or(semver::VersionReq::STAR);
+
+    let mut uppers = Vec::new();
+    for upper in &old_req.comparators {
+        match upper.op {
+            Op::Less => {
+                uppers.push(comparator_like(upper, Op::Less, &next_release(upper, max)).to_string())
+            }
+            Op::LessEq => uppers.push(comparator_like(upper, Op::LessEq, max).to_string()),
+            _ => {}
+        }
+    }
+    uppers.push(format!("<{}", next_series(max)));
+    uppers.push(format!("<{}.{}", max.major, max.minor + 1));
+    uppers.push(format!("<={max}"));
+
+    uppers
+        .into_iter()
+        .map(|upper| match lower.is_empty() {
+            true => upper,
+            false => format!("{lower}{separator}{upper}"),
+        })
+        .find(|candidate| {
+            semver::VersionReq::parse(candidate).is_ok_and(|requirement| {
+                versions.iter().all(|version| {
+                    requirement.matches(version) == (lower_req.matches(version) && version <= max)
+                })
+            })
+        })
+        .unwrap_or_else(|| format!("{lower}{separator}<={max}"))
+}
+
+/// Whether `requirement` allows the same published `versions` as `>=min, <=max`.
+fn allows_same(
+    requirement: &str,
+    min: &semver::Version,
+    max: &semver::Version,
+    versions: &[semver::Version],
+) -> bool {
+    semver::VersionReq::parse(requirement).is_ok_and(|requirement| {
+        versions
+            .iter()
+            .all(|version| requirement.matches(version) == (min <= version && version <= max))
+    })
+}
+
+/// A comparator for `version` with the operator `op`, written as precisely as `like`.
+fn comparator_like(
+    like: &semver::Comparator,
+    op: semver::Op,
+    version: &semver::Version,
+) -> semver::Comparator {
+    let patch = like.patch.is_some() || version.patch != 0 || !version.pre.is_empty();
+    let minor = like.minor.is_some() || version.minor != 0 || patch;
+    semver::Comparator {
+        op,
+        major: version.major,
+        minor: minor.then_some(version.minor),
+        patch: patch.then_some(version.patch),
+        pre: if patch {
+            version.pre.clone()
+        } else {
+            semver::Prerelease::EMPTY
+        },
+    }
+}
+
+/// The exclusive upper bound just past `max`, bumping the same part `upper` bumps.
+///
+/// `<2.0.0-0` bumps the major version, so for a max of `3.1.4` this is `4.0.0-0`.
+fn next_release(upper: &semver::Comparator, max: &semver::Version) -> semver::Version {
+    let minor = upper.minor.unwrap_or(0);
+    let patch = upper.patch.unwrap_or(0);
+    let (major, minor, patch) = if minor == 0 && patch == 0 {
+        (max.major + 1, 0, 0)
+    } else if patch == 0 {
+        (max.major, max.minor + 1, 0)
+    } else {
+        (max.major, max.minor, max.patch + 1)
+    };
+    semver::Version {
+        major,
+        minor,
+        patch,
+        pre: upper.pre.clone(),
+        build: semver::BuildMetadata::EMPTY,
+    }
+}
+
+/// Which edge of a bound a search is looking for.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Edge {
+    /// The oldest version that still works.
+    Min,
+    /// The newest version that still works.
+    Max,
+}
+
+/// Hears about what a search does, so the caller can show it.
+///
+/// Every method does nothing by default.
+pub trait Progress {
+    /// A search for `edge` starts at `from`, the end of the current bound.
+    fn searching(&mut self, _edge: Edge, _from: &semver::Version) {}
+
+    /// The search is down to the `remaining` versions from `low` to `high`.
+    fn narrowed(&mut self, _low: &semver::Version, _high: &semver::Version, _remaining: usize) {}
+
+    /// A version was checked, indeterminate results are already counted as failures.
+    fn checked(&mut self, _version: &semver::Version, _result: TestResult) {}
+
+    /// The results contradict each other, so every version is checked one by one instead.
+    fn not_monotonic(&mut self) {}
+
+    /// The search for `edge` found `version`.
+    fn found(&mut self, _edge: Edge, _version: &semver::Version) {}
+}
+
+impl Progress for () {}
+
+/// The bound [`minimize_bound`] found.
+#[derive(Clone, Debug)]
+pub struct SuggestedBound {
+    /// The oldest version that works.
+    pub min: semver::Version,
+    /// The newest version that works.
+    pub max: semver::Version,
+    /// `>=min, <=max` written in the style of the old requirement, see [`restyle_requirement`].
+    pub requirement: String,
+}
+
+/// Search how far the requirement `old` can be widened in both directions.
+///
+/// `versions` are the published versions to consider, sorted, and `check` checks one of them.
+/// Returns `None` when none of them matches `old`.
+pub fn minimize_bound(
+    old: &str,
+    versions: &[semver::Version],
+    mut check: impl FnMut(&semver::Version) -> Result<TestResult>,
+    progress: &mut impl Progress,
+) -> Result<Option<SuggestedBound>> {
+    let bound = semver::VersionReq::parse(old)?;
+    let Some(min_index) = versions.iter().position(|version| bound.matches(version)) else {
+        return Ok(None);
+    };
+    let max_index = versions
+        .iter()
+        .rposition(|version| bound.matches(version))
+        .unwrap_or(min_index);
+
+    progress.searching(Edge::Min, &versions[min_index]);
+    let min = find_boundary(
+        &versions[..=min_index],
+        TestResult::Sucess,
+        &mut check,
+        progress,
+    )?;
+    progress.found(Edge::Min, &min);
+    progress.searching(Edge::Max, &versions[max_index]);
+    let max = find_boundary(
+        &versions[max_index..],
+        TestResult::Fail,
+        &mut check,
+        progress,
+    )?;
+    progress.found(Edge::Max, &max);
+
+    let requirement = restyle_requirement(old, &min, &max, versions);
+    Ok(Some(SuggestedBound {
+        min,
+        max,
+        requirement,
+    }))
+}
+
+/// Search how far the upper end of the requirement `old` can be raised, without touching its
+/// lower end.
+///
+/// `versions` are the published versions to consider, sorted, and `check` checks one of them.
+/// The search starts at the newest version `old` allows. Returns `None` when none of them
+/// matches `old`, and the `min` of the result is the oldest version it allows, which isnt
+/// checked.
+pub fn raise_bound(
+    old: &str,
+    versions: &[semver::Version],
+    mut check: impl FnMut(&semver::Version) -> Result<TestResult>,
+    progress: &mut impl Progress,
+) -> Result<Option<SuggestedBound>> {
+    let bound = semver::VersionReq::parse(old)?;
+    let Some(min_index) = versions.iter().position(|version| bound.matches(version)) else {
+        return Ok(None);
+    };
+    let max_index = versions
+        .iter()
+        .rposition(|version| bound.matches(version))
+        .unwrap_or(min_index);
+
+    progress.searching(Edge::Max, &versions[max_index]);
+    let max = find_boundary(
+        &versions[max_index..],
+        TestResult::Fail,
+        &mut check,
+        progress,
+    )?;
+    progress.found(Edge::Max, &max);
+
+    let requirement = match max == versions[max_index] {
+        true => old.trim().to_owned(),
+        false => raise_upper(old, &max, versions),
+    };
+    Ok(Some(SuggestedBound {
+        min: versions[min_index].clone(),
+        max,
+        requirement,
+    }))
+}
+
+/// Search how far the lower end of the requirement `old` can be lowered, without touching its
+/// upper end.
+///
+/// The counterpart of [`raise_bound`]: the search starts at the oldest version `old` allows,
+/// and the `max` of the result is the newest version it allows, which isnt checked. Returns
+/// `None` when none of `versions` matches `old`.
+pub fn lower_bound(
+    old: &str,
+    versions: &[semver::Version],
+    mut check: impl FnMut(&semver::Version) -> Result<TestResult>,
+    progress: &mut impl Progress,
+) -> Result<Option<SuggestedBound>> {
+    let bound = semver::VersionReq::parse(old)?;
+    let Some(min_index) = versions.iter().position(|version| bound.matches(version)) else {
+        return Ok(None);
+    };
+    let max_index = versions
+        .iter()
+        .rposition(|version| bound.matches(version))
+        .unwrap_or(min_index);
+
+    progress.searching(Edge::Min, &versions[min_index]);
+    let min = find_boundary(
+        &versions[..=min_index],
+        TestResult::Sucess,
+        &mut check,
+        progress,
+    )?;
+    progress.found(Edge::Min, &min);
+
+    // The newest allowed version is the newest of its series when the upper end is a caret,
+    // so restyling keeps the upper end as it was written.
+    let max = versions[max_index].clone();
+    let requirement = restyle_requirement(old, &min, &max, versions);
+    Ok(Some(SuggestedBound {
+        min,
+        max,
+        requirement,
+    }))
+}
+
+/// Find the boundary between passing and failing versions.
+///
+/// `upper_kind` is the result the newer side of the boundary has. This assumes a version works
+/// when the versions between it and the current bound do. If the results contradict that, it
+/// falls back to [`scan_boundary`].
+pub fn find_boundary<P: Progress>(
+    versions: &[semver::Version],
+    upper_kind: TestResult,
+    check: &mut impl FnMut(&semver::Version) -> Result<TestResult>,
+    progress: &mut P,
+) -> Result<semver::Version> {
+    let mut test = |version: &semver::Version, progress: &mut P| -> Result<TestResult> {
+        let res = check(version)?.or_fail();
+        progress.checked(version, res);
+        Ok(res)
+    };
+    let mut low = 0;
+    let mut top = versions.len() - 1;
+    let mut tested = Vec::new();
+
+    while top - low > 1 {
+        progress.narrowed(&versions[low], &versions[top], top - low + 1);
+        let center = (low + top) / 2;
+        let res = test(&versions[center], progress)?;
+        tested.push((center, res));
+
+        if res == upper_kind {
+            top = center;
+        } else {
+            low = center;
+        }
+    }
+
+    let low_res = test(&versions[low], progress)?;
+    let top_res = test(&versions[top], progress)?;
+    tested.extend([(low, low_res), (top, top_res)]);
+
+    // Once a result of `upper_kind` is seen every newer version should have it too.
+    tested.sort_by_key(|(index, _)| *index);
+    let contradiction = tested
+        .windows(2)
+        .any(|pair| pair[0].1 == upper_kind && pair[1].1 != upper_kind);
+    if contradiction {
+        progress.not_monotonic();
+        return scan_boundary(versions, upper_kind, check, progress);
+    }
+
+    if low_res == top_res {
+        if upper_kind == TestResult::Fail {
+            return Ok(versions[top].clone());
+        } else {
+            return Ok(versions[low].clone());
+        }
+    }
+
+    if upper_kind == TestResult::Fail {
+        Ok(versions[low].clone())
+    } else {
+        Ok(versions[top].clone())
+    }
+}
+
+/// Find the boundary by checking versions one by one, starting at the end of `versions` that is
+/// in the current bound and stopping at the first failure.
+pub fn scan_boundary(
+    versions: &[semver::Version],
+    upper_kind: TestResult,
+    check: &mut impl FnMut(&semver::Version) -> Result<TestResult>,
+    progress: &mut impl Progress,
+) -> Result<semver::Version> {
+    // When minimizing the bound is at the top, when maximizing at the bottom.
+    let mut ordered = versions.iter().collect::<Vec<_>>();
+    if upper_kind == TestResult::Sucess {
+        ordered.reverse();
+    }
+    let mut boundary = ordered[0].clone();
+    for version in ordered {
+        let res = check(version)?.or_fail();
+        progress.checked(version, res);
+        if res == TestResult::Fail {
+            break;
+        }
+        boundary = version.clone();
+    }
+    Ok(boundary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn versions(versions: &[&str]) -> Vec<semver::Version> {
+        versions
+            .iter()
+            .map(|version| semver::Version::parse(version).unwrap())
+            .collect()
+    }
+
+    /// `1.0.0` to `1.0.9`.
+    fn patches() -> Vec<semver::Version> {
+        (0..10)
+            .map(|patch| semver::Version::new(1, 0, patch))
+            .collect()
+    }
+
+    /// A check passing the patches of `1.0` in `passing`.
+    fn passing(
+        passing: std::ops::RangeInclusive<u64>,
+    ) -> impl FnMut(&semver::Version) -> Result<TestResult> {
+        move |version| {
+            Ok(match passing.contains(&version.patch) {
+                true => TestResult::Sucess,
+                false => TestResult::Fail,
+            })
+        }
+    }
+
+    /// Records what a search reports.
+    #[derive(Default)]
+    struct Recorded {
+        checked: Vec<(semver::Version, TestResult)>,
+        not_monotonic: bool,
+    }
+
+    impl Progress for Recorded {
+        fn checked(&mut self, version: &semver::Version, result: TestResult) {
+            self.checked.push((version.clone(), result));
+        }
+
+        fn not_monotonic(&mut self) {
+            self.not_monotonic = true;
+        }
+    }
+
+    const PUBLISHED: &[&str] = &[
+        "0.1.0", "0.1.1", "0.2.0", "1.0.0", "1.0.5", "1.2.0", "1.2.3", "2.0.0",
+    ];
+
+    #[test]
+    fn select_versions_per_granularity() {
+        let published = versions(PUBLISHED);
+        let select = |granularity, sample| select_versions(&published, granularity, sample);
+        assert_eq!(
+            select(Granularity::Major, Sample::First),
+            versions(&["0.1.0", "0.2.0", "1.0.0", "2.0.0"])
+        );
+        assert_eq!(
+            select(Granularity::Minor, Sample::First),
+            versions(&["0.1.0", "0.2.0", "1.0.0", "1.2.0", "2.0.0"])
+        );
+        assert_eq!(select(Granularity::Patch, Sample::First), published);
+    }
+
+    #[test]
+    fn select_versions_per_sample() {
+        let published = versions(PUBLISHED);
+        assert_eq!(
+            select_versions(&published, Granularity::Minor, Sample::Last),
+            versions(&["0.1.1", "0.2.0", "1.0.5", "1.2.3", "2.0.0"])
+        );
+        assert_eq!(
+            select_versions(&published, Granularity::Minor, Sample::Both),
+            versions(&[
+                "0.1.0", "0.1.1", "0.2.0", "1.0.0", "1.0.5", "1.2.0", "1.2.3", "2.0.0"
+            ])
+        );
+    }
+
+    #[test]
+    fn select_versions_keeps_the_newest() {
+        let published = versions(&["1.0.0", "1.5.0"]);
+        assert_eq!(
+            select_versions(&published, Granularity::Major, Sample::First),
+            published
+        );
+        assert!(select_versions(&[], Granularity::Major, Sample::First).is_empty());
+    }
+
+    #[test]
+    fn breaking_series_of_zerover() {
+        let published = versions(PUBLISHED);
+        assert_eq!(
+            breaking_series(&published),
+            versions(&["0.1.0", "0.2.0", "1.0.0", "2.0.0"])
+                .iter()
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn spread_minors_keeps_zerover() {
+        let published = versions(&[
+            "0.1.0", "0.2.0", "0.3.0", "1.0.0", "1.1.0", "1.1.1", "1.2.0", "1.3.0",
+        ]);
+        assert_eq!(
+            spread_minors(&published, 2),
+            versions(&["0.1.0", "0.2.0", "0.3.0", "1.0.0", "1.3.0"])
+        );
+    }
+
+    #[test]
+    fn minimize_bound_widens_both_ends() {
+        let found = minimize_bound("=1.0.5", &patches(), passing(3..=7), &mut ())
+            .unwrap()
+            .unwrap();
+        assert_eq!(found.min, semver::Version::new(1, 0, 3));
+        assert_eq!(found.max, semver::Version::new(1, 0, 7));
+        let requirement = semver::VersionReq::parse(&found.requirement).unwrap();
+        for version in patches() {
+            assert_eq!(
+                requirement.matches(&version),
+                (3..=7).contains(&version.patch),
+                "{} and {version}",
+                found.requirement
+            );
+        }
+    }
+
+    #[test]
+    fn minimize_bound_without_a_match() {
+        let found = minimize_bound("^2", &patches(), passing(0..=9), &mut ()).unwrap();
+        assert!(found.is_none());
+    }
+
+    #[test]
+    fn raise_and_lower_keep_the_other_end() {
+        let raised = raise_bound(">=1.0.2, <=1.0.4", &patches(), passing(0..=6), &mut ())
+            .unwrap()
+            .unwrap();
+        assert_eq!(raised.min, semver::Version::new(1, 0, 2));
+        assert_eq!(raised.max, semver::Version::new(1, 0, 6));
+
+        let lowered = lower_bound(">=1.0.5, <=1.0.7", &patches(), passing(1..=9), &mut ())
+            .unwrap()
+            .unwrap();
+        assert_eq!(lowered.min, semver::Version::new(1, 0, 1));
+        assert_eq!(lowered.max, semver::Version::new(1, 0, 7));
+    }
+
+    #[test]
+    fn find_boundary_bisects() {
+        let mut progress = Recorded::default();
+        let min = find_boundary(
+            &patches(),
+            TestResult::Sucess,
+            &mut passing(6..=9),
+            &mut progress,
+        )
+        .unwrap();
+        assert_eq!(min, semver::Version::new(1, 0, 6));
+        assert!(progress.checked.len() < patches().len());
+        assert!(!progress.not_monotonic);
+    }
+
+    #[test]
+    fn find_boundary_counts_indeterminate_as_failing() {
+        let mut check = |version: &semver::Version| {
+            Ok(match version.patch {
+                0..=3 => TestResult::Indeterminate,
+                _ => TestResult::Sucess,
+            })
+        };
+        let min = find_boundary(&patches(), TestResult::Sucess, &mut check, &mut ()).unwrap();
+        assert_eq!(min, semver::Version::new(1, 0, 4));
+    }
+
+    #[test]
+    fn find_boundary_scans_when_not_monotonic() {
+        // 1.0.2 passes once and then fails, which contradicts the bisection.
+        let mut calls = 0;
+        let mut check = |version: &semver::Version| {
+            let passes = match version.patch {
+                2 => {
+                    calls += 1;
+                    calls == 1
+                }
+                patch => patch >= 3,
+            };
+            Ok(match passes {
+                true => TestResult::Sucess,
+                false => TestResult::Fail,
+            })
+        };
+        let mut progress = Recorded::default();
+        let min = find_boundary(
+            &patches()[..5],
+            TestResult::Sucess,
+            &mut check,
+            &mut progress,
+        )
+        .unwrap();
+        assert!(progress.not_monotonic);
+        assert_eq!(min, semver::Version::new(1, 0, 3));
+    }
+
+    #[test]
+    fn scan_boundary_stops_at_the_first_failure() {
+        let max = scan_boundary(
+            &patches()[4..],
+            TestResult::Fail,
+            &mut passing(0..=6),
+            &mut (),
+        )
+        .unwrap();
+        assert_eq!(max, semver::Version::new(1, 0, 6));
+        let min = scan_boundary(
+            &patches()[..6],
+            TestResult::Sucess,
+            &mut passing(2..=9),
+            &mut (),
+        )
+        .unwrap();
+        assert_eq!(min, semver::Version::new(1, 0, 2));
+    }
+
+    #[test]
+    fn thin_out_keeps_both_ends() {
+        let mut numbers = (0..10).collect::<Vec<_>>();
+        thin_out(&mut numbers, 4);
+        assert_eq!(numbers, [0, 3, 6, 9]);
+
+        let mut numbers = (0..10).collect::<Vec<_>>();
+        thin_out(&mut numbers, 2);
+        assert_eq!(numbers, [0, 9]);
+    }
+
+    #[test]
+    fn thin_out_leaves_short_lists_alone() {
+        let mut numbers = vec![1, 2, 3];
+        thin_out(&mut numbers, 3);
+        assert_eq!(numbers, [1, 2, 3]);
+        thin_out(&mut numbers, 5);
+        assert_eq!(numbers, [1, 2, 3]);
+    }
+
+    #[test]
+    fn thin_out_below_two() {
+        let mut numbers = vec![1, 2, 3];
+        thin_out(&mut numbers, 1);
+        assert_eq!(numbers, [3]);
+
+        let mut numbers = vec![1, 2, 3];
+        thin_out(&mut numbers, 0);
+        assert!(numbers.is_empty());
+
+        let mut empty = Vec::<u32>::new();
+        thin_out(&mut empty, 0);
+        assert!(empty.is_empty());
+    }
+
+    /// Refine after checking `tested` in the given times, the way `--adaptive` does.
+    fn refine(tested: &[semver::Version], durations: &[u64], budget: u64) -> Granularity {
+        let spent = durations
+            .iter()
+            .map(|secs| Duration::from_secs(*secs))
+            .sum::<Duration>();
+        let per_check = spent / durations.len().max(1) as u32;
+        let budget = Duration::from_secs(budget).saturating_sub(spent);
+        refine_granularity(
+            &versions(PUBLISHED),
+            tested,
+            per_check,
+            budget,
+            Sample::First,
+        )
+    }
+
+    #[test]
+    fn refine_granularity_fits_the_budget() {
+        let tested = versions(&["0.1.0", "0.2.0", "1.0.0", "2.0.0"]);
+        // Patch leaves 4 versions to check, minor only `1.2.0`.
+        assert_eq!(refine(&tested, &[10, 10, 10, 10], 80), Granularity::Patch);
+        assert_eq!(refine(&tested, &[10, 10, 10, 10], 79), Granularity::Minor);
+        assert_eq!(refine(&tested, &[10, 10, 10, 10], 50), Granularity::Minor);
+        assert_eq!(refine(&tested, &[10, 10, 10, 10], 49), Granularity::Major);
+        // One slow check among fast ones raises the expected time of the rest.
+        assert_eq!(refine(&tested, &[1, 1, 1, 1], 10), Granularity::Patch);
+        assert_eq!(refine(&tested, &[1, 1, 1, 37], 60), Granularity::Minor);
+        // Spending the whole budget or more leaves nothing for the rest.
+        assert_eq!(refine(&tested, &[30, 30, 30, 30], 100), Granularity::Major);
+        assert_eq!(refine(&tested, &[0, 0, 0, 0], 0), Granularity::Patch);
+    }
+
+    #[test]
+    fn refine_granularity_skips_tested_versions() {
+        let everything = versions(PUBLISHED);
+        assert_eq!(refine(&everything, &[10; 8], 0), Granularity::Patch);
+        assert_eq!(refine(&everything, &[], 0), Granularity::Patch);
+        let minors = versions(&["0.1.0", "0.2.0", "1.0.0", "1.2.0", "2.0.0"]);
+        assert_eq!(refine(&minors, &[10; 5], 50), Granularity::Minor);
+        assert_eq!(refine(&minors, &[10; 5], 80), Granularity::Patch);
+    }
+
+    /// A small deterministic generator for the property tests.
+    struct Lcg(u64);
+
+    impl Lcg {
+        fn below(&mut self, below: u64) -> u64 {
+            self.0 = self
+                .0
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (self.0 >> 33) % below
+        }
+
+        /// A sorted list of published versions, with some `0.x` ones.
+        fn published(&mut self) -> Vec<semver::Version> {
+            let mut published = (0..3 + self.below(20))
+                .map(|_| semver::Version::new(self.below(3), self.below(5), self.below(5)))
+                .collect::<Vec<_>>();
+            published.sort();
+            published.dedup();
+            published
+        }
+
+        /// A requirement in one of the styles people write, around one of `published`.
+        fn requirement(&mut self, published: &[semver::Version]) -> String {
+            let version = &published[self.below(published.len() as u64) as usize];
+            let other = &published[self.below(published.len() as u64) as usize];
+            let (major, minor, patch) = (version.major, version.minor, version.patch);
+            match self.below(9) {
+                0 => format!("{version}"),
+                1 => format!("^{major}.{minor}"),
+                2 => format!("~{major}.{minor}"),
+                3 => format!("~{major}.{minor}.{patch}"),
+                4 => format!("={version}"),
+                5 => format!(">={version}, <{}", major + 1),
+                6 => format!(">={version},<={}", version.max(other)),
+                7 => format!(">={major}.{minor}, <{}.0.0-0", major + 1),
+                _ => format!("{major}.*"),
+            }
+        }
+    }
+
+    #[test]
+    fn restyle_keeps_an_unchanged_requirement() {
+        let mut rng = Lcg(1);
+        for _ in 0..2000 {
+            let published = rng.published();
+            let old = rng.requirement(&published);
+            let bound = semver::VersionReq::parse(&old).unwrap();
+            let allowed = published
+                .iter()
+                .filter(|version| bound.matches(version))
+                .collect::<Vec<_>>();
+            let (Some(min), Some(max)) = (allowed.first(), allowed.last()) else {
+                continue;
+            };
+            let new = restyle_requirement(&old, min, max, &published);
+            assert_eq!(new, old, "{published:?}");
+            assert_eq!(
+                semver::VersionReq::parse(&new).unwrap(),
+                semver::VersionReq::parse(&old).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn restyle_allows_exactly_the_found_range() {
+        let mut rng = Lcg(2);
+        for _ in 0..2000 {
+            let published = rng.published();
+            let old = rng.requirement(&published);
+            let mut ends = [
+                rng.below(published.len() as u64),
+                rng.below(published.len() as u64),
+            ];
+            ends.sort();
+            let (min, max) = (&published[ends[0] as usize], &published[ends[1] as usize]);
+            let new = restyle_requirement(&old, min, max, &published);
+            assert!(
+                allows_same(&new, min, max, &published),
+                "{old} became {new} for {min} to {max} of {published:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn suggestions_are_stable() {
+        let mut rng = Lcg(3);
+        for _ in 0..2000 {
+            let published = rng.published();
+            let mut ends = [
+                rng.below(published.len() as u64),
+                rng.below(published.len() as u64),
+            ];
+            ends.sort();
+            let (min, max) = (&published[ends[0] as usize], &published[ends[1] as usize]);
+            let suggested = suggest_requirement(min, max, &published);
+            assert!(allows_same(&suggested, min, max, &published), "{suggested}");
+            assert_eq!(
+                restyle_requirement(&suggested, min, max, &published),
+                suggested
+            );
+        }
+    }
+
+    #[test]
+    fn restyle_keeps_the_style_family() {
+        let published = versions(&[
+            "1.1.0",
+            "1.2.0-rc.1",
+            "1.2.0",
+            "1.2.4",
+            "1.3.0",
+            "1.5.0",
+            "2.0.0",
+        ]);
+        let v = |version: &str| semver::Version::parse(version).unwrap();
+        let restyle = |old: &str, min: &str, max: &str| {
+            restyle_requirement(old, &v(min), &v(max), &published)
+        };
+        assert_eq!(
+            restyle(">=1.2.0-rc.1, <2.0.0-0", "1.2.0-rc.1", "1.5.0"),
+            ">=1.2.0-rc.1, <2.0.0-0"
+        );
+        assert_eq!(
+            restyle(">=1.2.0-rc.1, <2.0.0-0", "1.2.0", "1.5.0"),
+            ">=1.2.0, <2.0.0-0"
+        );
+        assert_eq!(restyle("~1.2", "1.2.4", "1.2.4"), "~1.2.4");
+        assert_eq!(restyle("~1.2", "1.1.0", "1.2.4"), ">=1.1.0, <=1.2.4");
+        assert_eq!(restyle("^1.3", "1.2.0", "1.5.0"), "^1.2");
+        assert_eq!(restyle("1.3", "1.2.0", "1.5.0"), "1.2");
+        assert_eq!(
+            restyle(">=1.3, <=1.5.0", "1.2.4", "1.5.0"),
+            ">=1.2.4, <=1.5.0"
+        );
+    }
+
+    #[test]
+    fn suggest_requirement_for_zerover() {
+        let published = versions(&[
+            "0.5.0", "0.5.2", "0.5.4", "0.6.0", "0.8.0", "0.8.1", "0.9.0",
+        ]);
+        let v = |version: &str| semver::Version::parse(version).unwrap();
+        let suggest = |min: &str, max: &str| suggest_requirement(&v(min), &v(max), &published);
+        assert_eq!(suggest("0.5.2", "0.8.1"), ">=0.5.2, <0.9");
+        assert_eq!(suggest("0.5.2", "0.5.4"), "0.5.2");
+        assert_eq!(suggest("0.5.2", "0.8.0"), ">=0.5.2, <=0.8.0");
+    }
+}
@@ -1,18 +1,272 @@
 use std::{
-    env::args_os,
+    cell::RefCell,
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt::{self, Display},
     fs,
-    io::{BufRead, BufReader},
-    process::{Command, ExitStatus, Stdio},
-    time::Duration,
+    io::{BufRead, BufReader, IsTerminal, Read, Write},
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    str::FromStr,
+    sync::{
+        LazyLock, Mutex, OnceLock,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc,
+    },
+    thread,
+    time::{Duration, Instant},
 };
 
 use anyhow::{Result, anyhow};
-use clap::Parser;
-use indicatif::ProgressStyle;
-use owo_colors::OwoColorize;
+use cargo_bounds::{
+    Edge, Granularity, Progress, Sample, SuggestedBound, TestResult, Tightened, breaking_series,
+    closest_outside, exact_pin, find_boundary, lower_bound, minimize_bound, raise_bound,
+    refine_granularity, restyle_requirement, select_versions, spread_minors, thin_out,
+    tighten_requirement,
+};
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use indicatif::{HumanDuration, MultiProgress, ProgressBar, ProgressStyle};
+use notify::{RecursiveMode, Watcher};
+use owo_colors::Style;
+use serde::{Deserialize, Serialize};
 use toml_edit::DocumentMut;
 
-#[derive(Parser, Debug, Default)]
+/// How many lines of the check output to show when a version fails.
+const FAILURE_TAIL_LINES: usize = 30;
+
+/// How many of the last output lines of a check are shown under its spinner while it runs.
+const OUTPUT_WINDOW_LINES: usize = 5;
+
+/// How many versions of a `*` dependency are tested when `--max-versions` isnt given.
+const WILDCARD_MAX_VERSIONS: u64 = 16;
+
+/// Printed after an `--extremes-only` run, so it isnt mistaken for a full sweep.
+const EXTREMES_ONLY_NOTE: &str =
+    "Only the oldest and newest version of each bound were tested (--extremes-only)";
+
+/// Parse the requirement of `dep`, naming the dep when it isnt one cargo understands.
+///
+/// Cargo requirements are comparators that all have to match, so they always cover one
+/// contiguous range. An exclusion like `!=0.8.0` would break that, but cargo rejects it anyway.
+fn parse_bound(dep: &str, requirement: &str) -> Result<semver::VersionReq> {
+    semver::VersionReq::parse(requirement).map_err(|err| {
+        let hint = if requirement.contains("!=") {
+            ", cargo doesnt support excluding versions with `!=`. Move the bound past the broken version instead"
+        } else {
+            ""
+        };
+        anyhow!("the requirement {requirement:?} of {dep} is invalid: {err}{hint}")
+    })
+}
+
+/// How many failing versions of a dep the summary table lists before "+N more".
+const TABLE_FAILING_SHOWN: usize = 5;
+
+/// How long `watch` waits for the files to settle before starting a run.
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// How long a check gets to exit after being asked to stop, before it is killed.
+const CHECK_GRACE: Duration = Duration::from_secs(2);
+
+/// How progress is shown, decided once from `--display` and whether stderr is a terminal.
+static DISPLAY: OnceLock<DisplayMode> = OnceLock::new();
+
+/// Every progress bar is drawn through this, so printing can hide them while it writes.
+static MULTI: LazyLock<MultiProgress> = LazyLock::new(MultiProgress::new);
+
+/// The dependency being tested, drawn as one line with [`DisplayMode::Compact`].
+static DEP_BAR: Mutex<Option<DepBar>> = Mutex::new(None);
+
+/// The progress over every version of the run, see [`start_run_bar`].
+static RUN_BAR: Mutex<Option<RunBar>> = Mutex::new(None);
+
+/// `println!` that doesnt draw over the progress bars.
+macro_rules! out {
+    ($($arg:tt)*) => {
+        print_line(format!($($arg)*))
+    };
+}
+
+thread_local! {
+    /// What a `minimize --jobs` worker printed for its current dependency, see [`minimize_parallel`].
+    static CAPTURED: RefCell<Option<Vec<(Stream, String)>>> = const { RefCell::new(None) };
+}
+
+/// Where a printed line goes.
+#[derive(Clone, Copy, Debug)]
+enum Stream {
+    Stdout,
+    Stderr,
+}
+
+/// Print a line of [`out!`], or hold it back when the thread is capturing its output.
+fn print_line(line: String) {
+    print_to(Stream::Stdout, line);
+}
+
+/// Print a line of cargo's output for `--verbose`, on stderr where cargo prints it.
+///
+/// A capturing thread holds it back with its other lines, so checks running in parallel dont
+/// interleave their output.
+fn print_output(line: String) {
+    print_to(Stream::Stderr, line);
+}
+
+/// Print `line` to `stream`, or hold it back when the thread is capturing its output.
+fn print_to(stream: Stream, line: String) {
+    let line = CAPTURED.with_borrow_mut(|captured| match captured {
+        Some(captured) => {
+            captured.push((stream, line));
+            None
+        }
+        None => Some(line),
+    });
+    if let Some(line) = line {
+        match stream {
+            Stream::Stdout => MULTI.suspend(|| println!("{line}")),
+            Stream::Stderr => MULTI.suspend(|| eprintln!("{line}")),
+        }
+    }
+}
+
+/// Whether [`out!`] is held back on this thread.
+fn capturing() -> bool {
+    CAPTURED.with_borrow(Option::is_some)
+}
+
+/// What happened to the checks of this run besides their result, see [`CheckNote`].
+static CHECK_NOTES: LazyLock<Mutex<HashMap<(String, semver::Version), CheckNote>>> =
+    LazyLock::new(Mutex::default);
+
+/// The last dependency version checked in each project copy, to notice checks that compiled
+/// nothing.
+static LAST_CHECKED: LazyLock<Mutex<HashMap<PathBuf, (String, semver::Version)>>> =
+    LazyLock::new(Mutex::default);
+
+/// The version of cargo on the `PATH`, probed once at startup.
+static CARGO_VERSION: OnceLock<semver::Version> = OnceLock::new();
+
+/// Version lists fetched this run, by `registry/crate`, see [`fetch_published`].
+static PUBLISHED: LazyLock<Mutex<HashMap<String, Vec<PublishedVersion>>>> =
+    LazyLock::new(Mutex::default);
+
+/// Results from earlier runs, loaded by `test` and `minimize`.
+static KNOWLEDGE: Mutex<Option<Knowledge>> = Mutex::new(None);
+
+/// Set by `check-crate`, the manifest under test is then a published one.
+static CHECKING_PUBLISHED: AtomicBool = AtomicBool::new(false);
+
+/// Set by `--no-cache`, results of earlier runs are then recorded but never reused.
+static NO_CACHE: AtomicBool = AtomicBool::new(false);
+
+/// Set by `--cache-failures`, otherwise only earlier passes are reused.
+static CACHE_FAILURES: AtomicBool = AtomicBool::new(false);
+
+/// Whether to color the output, decided once from `--color` and `NO_COLOR`.
+static COLOR: AtomicBool = AtomicBool::new(true);
+
+/// The manifest and lockfile from the latest [`State::store`], written back by [`shutdown`].
+static RESTORE_ON_EXIT: Mutex<Option<State>> = Mutex::new(None);
+
+/// The workspace manifest with its `[patch]` and `[replace]` sections, which `--ignore-patches`
+/// took out for the run. Put back by [`shutdown`].
+static PATCHES: OnceLock<(PathBuf, Box<str>)> = OnceLock::new();
+
+/// The crates `[patch]` or `[replace]` overrides and the section doing it, see [`patched_crates`].
+static PATCHED_CRATES: Mutex<Vec<(String, String)>> = Mutex::new(Vec::new());
+
+/// Set by `--keep-manifest`, or by `--keep-manifest-on-failure` once a version failed. The
+/// manifest is then left pinned and its snapshot kept for `cargo bounds restore`.
+static KEEP_MANIFEST: AtomicBool = AtomicBool::new(false);
+
+/// The check commands that are running, so `watch` can stop them when the sources change.
+///
+/// Only `minimize --jobs` runs more than one at a time.
+static RUNNING_CHECKS: Mutex<Vec<Child>> = Mutex::new(Vec::new());
+
+/// Set by `watch` to abandon the current run, see [`Cancelled`].
+static CANCELLED: AtomicBool = AtomicBool::new(false);
+
+/// Whether [`shutdown`] has begun.
+///
+/// Held while pinning the manifest or starting a check, so neither happens after the files are
+/// put back.
+static SHUTTING_DOWN: Mutex<bool> = Mutex::new(false);
+
+/// Whether `watch` is in the middle of a run, during which it writes the manifest itself.
+static WATCH_RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// Where the state of this run is kept, decided once at startup.
+static PATHS: OnceLock<Paths> = OnceLock::new();
+
+/// The `--target-dir` the checks build in, instead of the one in the state directory.
+static TARGET_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// The check logs written during this run, collected into the `--artifacts` bundle.
+static WRITTEN_LOGS: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+/// Edits `update --apply` makes once the manifest is restored, so restoring doesnt undo them.
+static PENDING_FIXES: Mutex<Vec<Fix>> = Mutex::new(Vec::new());
+
+/// The deps of this `minimize` or `update` whose bound no published version matches.
+static NO_MATCH_DEPS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// The deps `minimize --zerover-strict` found no requirement for, since a `0.x` series in the
+/// range wasnt verified.
+static UNVERIFIED_DEPS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// The dependencies of the package, see [`declared_deps`].
+static DECLARED_DEPS: Mutex<Vec<DeclaredDep>> = Mutex::new(Vec::new());
+
+/// Bumped whenever a file of the `--artifacts` bundle changes incompatibly.
+const ARTIFACTS_SCHEMA: u32 = 1;
+
+/// How many checks the "Slowest checks" list at the end of `test` shows.
+const SLOWEST_SHOWN: usize = 5;
+
+/// How each version is checked, shared by `test`, `minimize` and `update`.
+#[derive(clap::Args, Debug, Default, Clone)]
+struct CheckArgs {
+    /// Overwrite the check command (DEFAULT: "cargo check --all-features")
+    ///
+    /// `{dep}` and `{version}` are replaced with the version under test, use `{{` and `}}` for literal braces.
+    #[arg(short, long)]
+    command: Option<String>,
+    /// Which cargo command to check with, ignored when a custom command is given
+    #[arg(long, value_enum, default_value_t)]
+    mode: Mode,
+    /// Treat warnings as errors in `--mode clippy`
+    #[arg(long)]
+    deny_warnings: bool,
+    /// Features to enable instead of all features (comma separated)
+    #[arg(long, value_delimiter = ',')]
+    features: Vec<String>,
+    /// Disable the default features instead of enabling all features
+    #[arg(long)]
+    no_default_features: bool,
+    /// Only enable the default features, instead of all features
+    #[arg(long)]
+    no_all_features: bool,
+    /// Use a different check command for one dependency, e.g. `openssl="cargo check --features vendored"`
+    #[arg(long, value_name = "DEP=COMMAND", value_parser = parse_key_value)]
+    command_for: Vec<(String, String)>,
+    /// Set an environment variable for every check, e.g. `RUSTFLAGS="--cfg tokio_unstable"`.
+    ///
+    /// `{dep}` and `{version}` in the value are replaced like in `--command`.
+    #[arg(long, value_name = "KEY=VALUE", value_parser = parse_key_value)]
+    env: Vec<(String, String)>,
+    /// Run custom commands through this shell instead of splitting them ourselves
+    #[arg(long)]
+    shell: Option<String>,
+    /// Stream the full output of the check command instead of a spinner
+    #[arg(short, long)]
+    verbose: bool,
+    /// Check a version that failed with a network error again, up to this many times
+    #[arg(long, default_value_t = 0)]
+    retries: u32,
+}
+
+#[derive(Parser, Debug, Default, Clone)]
 struct TestConfig {
     /// Test minor versions as well.
     #[arg(short, long)]
@@ -26,461 +280,9726 @@ struct TestConfig {
     /// Test a specific dependency
     #[arg(short, long)]
     dep: Option<String>,
-    /// Overwrite the check command (DEFAULT: "cargo check --all-features")
+    /// Sweep the versions of this requirement instead of the declared one, to see if it would
+    /// hold before changing `Cargo.toml`
+    #[arg(long, requires = "dep", value_name = "REQUIREMENT")]
+    bound: Option<String>,
+    /// Test the deps of these workspace members, one after the other
+    #[arg(long, value_name = "MEMBER")]
+    package: Vec<String>,
+    /// Only test the deps that were added or whose requirement changed since this git ref
+    #[arg(long, value_name = "REF", conflicts_with = "dep")]
+    since_ref: Option<String>,
+    #[command(flatten)]
+    check: CheckArgs,
+    /// Check every version once per feature of the crate, and once without default features
+    #[arg(long, conflicts_with_all = ["command", "features", "no_default_features"])]
+    each_feature: bool,
+    /// Check with this rustup toolchain, e.g. `1.70`, repeat it to check every version with each
+    #[arg(long, value_name = "NAME")]
+    toolchain: Vec<String>,
+    /// Check for this target triple, e.g. `wasm32-unknown-unknown`, repeat it to check every
+    /// version for each
+    #[arg(long, value_name = "TRIPLE")]
+    target: Vec<String>,
+    /// Map out where versions start failing, without failing the run
+    #[arg(long)]
+    explore: bool,
+    /// Test the newest versions of each bound first, where fresh breakage shows up. The same
+    /// versions are tested, only the order changes
+    #[arg(long)]
+    reverse: bool,
+    /// Dont check that the project builds with its current dependencies first
+    #[arg(long)]
+    skip_baseline: bool,
+    /// Dont mention releases newer than the bound allows
+    #[arg(long)]
+    no_outdated_hint: bool,
+    /// Count a bound that excludes the latest release as a failure
+    #[arg(long, conflicts_with = "no_outdated_hint")]
+    strict_outdated: bool,
+    /// Stop testing a dependency at its first failing version, `all` stops the whole run
+    #[arg(
+        long,
+        value_enum,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "dep",
+        conflicts_with = "explore"
+    )]
+    fail_fast: Option<FailFast>,
+    /// Stop at the first failing version and leave the manifest and lockfile pinned at it, to
+    /// look into it. `cargo bounds restore` puts them back
+    #[arg(long)]
+    keep_manifest_on_failure: bool,
+    /// Leave the manifest and lockfile as the run ends, pinned at the last version checked
+    #[arg(long, conflicts_with = "package")]
+    keep_manifest: bool,
+    /// Whether versions cargo cant resolve a dependency graph for fail the run
+    #[arg(long, value_enum, default_value_t = Conflicts::Fail)]
+    conflicts: Conflicts,
+    /// Stop exploring a dependency after this many failures
+    #[arg(long, requires = "explore")]
+    max_failures: Option<usize>,
+    /// Test only the oldest and newest version in each bound
+    #[arg(long, conflicts_with_all = ["minor", "patch", "adaptive", "max_versions"])]
+    extremes_only: bool,
+    /// Test at most this many versions per dependency, spread over its bound
+    #[arg(long, value_parser = clap::value_parser!(u64).range(2..))]
+    max_versions: Option<u64>,
+    /// Test this many minors of each major, spread evenly from its first to its last minor
+    #[arg(
+        long,
+        value_parser = clap::value_parser!(u64).range(2..),
+        conflicts_with_all = ["minor", "patch", "adaptive", "extremes_only"]
+    )]
+    per_major: Option<u64>,
+    /// Skip versions whose requirements conflict with the locked versions of the other dependencies
+    #[arg(long)]
+    preflight: bool,
+    /// How to pin the version under test, `lockfile` leaves the manifest alone where it can
+    #[arg(long, value_enum, default_value_t)]
+    pin_strategy: PinStrategy,
+    /// Resolve the other crates to their minimal versions for every check, needs a nightly toolchain
+    #[arg(
+        long,
+        value_enum,
+        num_args = 0..=1,
+        default_missing_value = "all",
+        conflicts_with = "pin_strategy"
+    )]
+    minimal_versions: Option<MinimalVersions>,
+    #[command(flatten)]
+    rust_version: RustVersionArgs,
+    /// Test this version with a local checkout patched in, e.g. `serde=1.0.210=../serde`
+    #[arg(long, value_name = "DEP=VERSION=PATH")]
+    patch_version: Vec<PatchedVersion>,
+    /// Pick the granularity per dependency based on how long its checks take
+    #[arg(long, conflicts_with_all = ["minor", "patch"])]
+    adaptive: bool,
+    /// Time budget per dependency for `--adaptive`, in seconds
+    #[arg(long, default_value_t = 300, requires = "adaptive")]
+    adaptive_budget: u64,
+    /// Which version of each series to test: first, last or both
+    #[arg(long, default_value_t = Sample::First, conflicts_with = "extremes_only")]
+    sample: Sample,
+    /// Where to write the logs of failing checks (DEFAULT: "logs" in the state directory)
+    #[arg(long)]
+    log_dir: Option<PathBuf>,
+    /// Keep the logs of successful checks as well
+    #[arg(long)]
+    keep_all_logs: bool,
+    /// Write the results, summary and logs of the run to this directory, for CI to archive
+    #[arg(long)]
+    artifacts: Option<PathBuf>,
+    /// Also write the results as a report, e.g. `html=bounds.html` or `json=results.json`
+    #[arg(long, value_name = "FORMAT=PATH")]
+    report: Vec<ReportTarget>,
+    /// Save the results to this file, to compare later runs with using `--baseline` or `diff`
+    #[arg(long, value_name = "FILE")]
+    save_baseline: Option<PathBuf>,
+    /// Compare the results with ones saved by `--save-baseline`, only failing on regressions
+    #[arg(long = "baseline", value_name = "FILE", conflicts_with = "explore")]
+    baseline_file: Option<PathBuf>,
+    /// Write a shields.io endpoint badge with the result of the run to this file
+    #[arg(long)]
+    badge: Option<PathBuf>,
+    /// What the badge says, how many deps hold their bounds or just passing/failing
+    #[arg(long, value_enum, default_value_t, requires = "badge")]
+    badge_message: BadgeMessage,
+    /// Print failures as CI annotations (DEFAULT: "github" when `GITHUB_ACTIONS=true`)
+    #[arg(long, value_enum)]
+    annotations: Option<Annotations>,
+    /// Only test these versions of each dep, or the edges of its bound for deps that arent listed.
+    ///
+    /// Set by `watch` to rerun what failed last time.
+    #[arg(skip)]
+    focus: Option<HashMap<String, Vec<semver::Version>>>,
+    /// Extra arguments for the built-in command, e.g. `-- --locked --profile check-fast`
+    #[arg(last = true, value_name = "CARGO_ARGS", conflicts_with = "command")]
+    cargo_args: Vec<String>,
+    /// The configuration of `--each-feature` this check is for, shown next to the version.
+    #[arg(skip)]
+    variant: Option<String>,
+    /// The toolchain of this check when there are several, see [`TestConfig::per_toolchain`].
+    #[arg(skip)]
+    on_toolchain: Option<String>,
+    /// The target of this check when there are several, see [`TestConfig::per_target`].
+    #[arg(skip)]
+    on_target: Option<String>,
+    /// The `[workspace.dependencies]` an earlier `--package` already tested, with that package.
+    #[arg(skip)]
+    tested_shared: HashMap<String, String>,
+    /// The project copy the checks run in, see [`minimize_parallel`].
+    #[arg(skip)]
+    worker: Option<Worker>,
+}
+
+impl TestConfig {
+    fn granularity(&self) -> Granularity {
+        if self.patch {
+            Granularity::Patch
+        } else if self.minor || self.per_major.is_some() {
+            Granularity::Minor
+        } else {
+            Granularity::Major
+        }
+    }
+
+    /// The config to test `dep` with, picking its check command.
+    ///
+    /// `--command-for` wins over the metadata `commands` table, which wins over `--command`.
+    fn for_dep(&self, dep: &str, metadata: &Metadata) -> TestConfig {
+        let command = self
+            .check
+            .command_for
+            .iter()
+            .rev()
+            .find(|(name, _)| name == dep)
+            .map(|(_, command)| command)
+            .or_else(|| metadata.commands.get(dep))
+            .or(self.check.command.as_ref());
+        let mut config = self.clone();
+        config.check.command = command.cloned();
+        config
+    }
+
+    /// Add the `env` table of the metadata, where `--env` doesnt set the same variable, and
+    /// say what every check gets.
+    fn add_metadata_env(&mut self, metadata: &Metadata) {
+        for (key, value) in &metadata.env {
+            if !self.check.env.iter().any(|(set, _)| set == key) {
+                self.check.env.push((key.clone(), value.clone()));
+            }
+        }
+        if !self.check.env.is_empty() {
+            let env = self
+                .check
+                .env
+                .iter()
+                .map(|(key, value)| format!("{key}={value}"))
+                .collect::<Vec<_>>();
+            out!("Setting {} for every check", env.join(" ").yellow());
+        }
+    }
+
+    /// Everything about how a version is checked, results are only reused when this matches.
+    fn check_context(&self) -> String {
+        let context = format!(
+            "{:?} {:?} {:?} {} {}",
+            self.check.command,
+            self.check.shell,
+            self.check.mode,
+            self.check.deny_warnings,
+            self.describe_features()
+        );
+        let context = if self.cargo_args.is_empty() {
+            context
+        } else {
+            format!("{context} -- {:?}", self.cargo_args)
+        };
+        let context = if self.check.env.is_empty() {
+            context
+        } else {
+            format!("{context} env {:?}", self.check.env)
+        };
+        // Lockfile pins resolve the other deps differently, manifest pins keep the old contexts.
+        let context = match self.pin_strategy {
+            PinStrategy::Manifest => context,
+            PinStrategy::Lockfile => format!("{context} lockfile pins"),
+        };
+        let context = match self.minimal_versions {
+            Some(minimal) => format!("{context} {} minimal versions", minimal.flag()),
+            None => context,
+        };
+        let context = match (self.active_toolchain(), self.toolchain.is_empty()) {
+            (Some(toolchain), _) => format!("{context} +{toolchain}"),
+            (None, true) => context,
+            (None, false) => format!("{context} +{}", self.toolchain.join(",+")),
+        };
+        match (self.active_target(), self.target.is_empty()) {
+            (Some(target), _) => format!("{context} --target {target}"),
+            (None, true) => context,
+            (None, false) => format!("{context} --target {}", self.target.join(",")),
+        }
+    }
+
+    /// The toolchain this check runs with, `None` for the one rustup picks by itself.
+    fn active_toolchain(&self) -> Option<&str> {
+        match (&self.on_toolchain, self.toolchain.as_slice()) {
+            (Some(toolchain), _) | (None, [toolchain]) => Some(toolchain),
+            _ => None,
+        }
+    }
+
+    /// One config per `--toolchain` when it was given more than once, else just this one.
+    fn per_toolchain(&self) -> Vec<TestConfig> {
+        if self.toolchain.len() < 2 {
+            return vec![self.clone()];
+        }
+        self.toolchain
+            .iter()
+            .map(|toolchain| TestConfig {
+                on_toolchain: Some(toolchain.clone()),
+                ..self.clone()
+            })
+            .collect()
+    }
+
+    /// The target this check builds for, `None` for the host.
+    fn active_target(&self) -> Option<&str> {
+        match (&self.on_target, self.target.as_slice()) {
+            (Some(target), _) | (None, [target]) => Some(target),
+            _ => None,
+        }
+    }
+
+    /// One config per `--target` when it was given more than once, else just this one.
+    fn per_target(&self) -> Vec<TestConfig> {
+        if self.target.len() < 2 {
+            return vec![self.clone()];
+        }
+        self.target
+            .iter()
+            .map(|target| TestConfig {
+                on_target: Some(target.clone()),
+                ..self.clone()
+            })
+            .collect()
+    }
+
+    /// One config per combination of `--toolchain` and `--target`.
+    fn per_toolchain_and_target(&self) -> Vec<TestConfig> {
+        self.per_toolchain()
+            .iter()
+            .flat_map(TestConfig::per_target)
+            .collect()
+    }
+
+    /// Resolve the paths of the outputs against the current directory, before leaving it.
+    fn make_paths_absolute(&mut self) -> Result<()> {
+        let paths = [
+            &mut self.log_dir,
+            &mut self.artifacts,
+            &mut self.badge,
+            &mut self.save_baseline,
+            &mut self.baseline_file,
+        ];
+        for path in paths.into_iter().flatten() {
+            *path = std::path::absolute(&*path)?;
+        }
+        for report in &mut self.report {
+            report.path = std::path::absolute(&report.path)?;
+        }
+        for patch in &mut self.patch_version {
+            patch.path = std::path::absolute(&patch.path)?;
+        }
+        Ok(())
+    }
+
+    /// Whether to print GitHub annotations, on by default inside GitHub Actions.
+    fn github_annotations(&self) -> bool {
+        match self.annotations {
+            Some(annotations) => annotations == Annotations::Github,
+            None => std::env::var("GITHUB_ACTIONS").is_ok_and(|value| value == "true"),
+        }
+    }
+
+    fn all_features(&self) -> bool {
+        self.check.features.is_empty()
+            && !self.check.no_default_features
+            && !self.check.no_all_features
+            && !self.each_feature
+    }
+
+    /// Describe the features the built-in command enables.
+    fn describe_features(&self) -> String {
+        if self.each_feature {
+            return "each feature separately".to_owned();
+        }
+        if self.all_features() {
+            return "all features".to_owned();
+        }
+        let base = if self.check.no_default_features {
+            "no default features"
+        } else {
+            "default features"
+        };
+        if self.check.features.is_empty() {
+            base.to_owned()
+        } else {
+            format!("{base} + {}", self.check.features.join(","))
+        }
+    }
+
+    /// The configs every version is checked with, more than one with `--each-feature` or
+    /// several `--toolchain`s or `--target`s.
+    ///
+    /// That is one without default features, and one per feature of the crate on top of the
+    /// default ones. Crates without features get a run with just the default features instead.
+    /// Each of those is checked once per toolchain and target.
+    fn variants(&self, cargo_toml: &DocumentMut) -> Vec<TestConfig> {
+        self.feature_variants(cargo_toml)
+            .iter()
+            .flat_map(TestConfig::per_toolchain_and_target)
+            .collect()
+    }
+
+    fn feature_variants(&self, cargo_toml: &DocumentMut) -> Vec<TestConfig> {
+        if !self.each_feature || self.check.command.is_some() {
+            return vec![self.clone()];
+        }
+        let mut single = TestConfig {
+            each_feature: false,
+            ..self.clone()
+        };
+        single.check.no_all_features = true;
+        let mut no_default = TestConfig {
+            variant: Some("no-default-features".to_owned()),
+            ..single.clone()
+        };
+        no_default.check.no_default_features = true;
+        let mut variants = vec![no_default];
+        let features = cargo_toml
+            .get("features")
+            .and_then(|features| features.as_table_like())
+            .map(|features| {
+                features
+                    .iter()
+                    .map(|(name, _)| name.to_owned())
+                    .filter(|name| name != "default")
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        if features.is_empty() {
+            variants.push(TestConfig {
+                variant: Some("default".to_owned()),
+                ..single
+            });
+            return variants;
+        }
+        for feature in features {
+            let mut variant = single.clone();
+            if !variant.check.features.contains(&feature) {
+                variant.check.features.push(feature.clone());
+            }
+            variant.variant = Some(feature);
+            variants.push(variant);
+        }
+        variants
+    }
+
+    /// The version under test, with the `--each-feature` configuration, the toolchain, the
+    /// target and the `--patch-version` checkout if there are any.
+    fn label(&self, dep: &str, version: &semver::Version) -> String {
+        let mut label = version.blue().to_string();
+        if let Some(variant) = &self.variant {
+            label = format!("{label} {}", format!("[{variant}]").bright_black());
+        }
+        if let Some(toolchain) = &self.on_toolchain {
+            label = format!("{label} {}", format!("[+{toolchain}]").bright_black());
+        }
+        if let Some(target) = &self.on_target {
+            label = format!("{label} {}", format!("[{target}]").bright_black());
+        }
+        if let Some(path) = self.patch_for(dep, version) {
+            let patched = format!("(patched from {})", path.display());
+            label = format!("{label} {}", patched.bright_black());
+        }
+        label
+    }
+
+    /// The local checkout to test this version of `dep` with, see `--patch-version`.
+    fn patch_for(&self, dep: &str, version: &semver::Version) -> Option<&Path> {
+        self.patch_version
+            .iter()
+            .find(|patch| patch.dep == dep && patch.version == *version)
+            .map(|patch| patch.path.as_path())
+    }
+
+    fn log_dir(&self) -> PathBuf {
+        self.log_dir.clone().unwrap_or_else(|| paths().logs())
+    }
+
+    /// Where the manifest is pinned and the checks run, the project itself unless in a [`Worker`].
+    fn project_dir(&self) -> &Path {
+        self.worker
+            .as_ref()
+            .map_or(Path::new("."), |worker| worker.dir.as_path())
+    }
+
+    /// The `CARGO_TARGET_DIR` of the checks.
+    fn target_dir(&self) -> PathBuf {
+        self.worker
+            .as_ref()
+            .map_or_else(|| paths().target(), |worker| worker.target.clone())
+    }
+}
+
+/// A copy of the project for a `minimize --jobs` thread, so it can pin versions without getting
+/// in the way of the others.
+#[derive(Debug, Clone)]
+struct Worker {
+    /// The package in the copy, where its checks run.
+    dir: PathBuf,
+    /// Its own target directory, with a shared one cargo's lock would build one at a time.
+    target: PathBuf,
+}
+
+impl Worker {
+    /// Copy the workspace for worker `index`, leaving out what was built and the git history.
+    fn create(index: usize) -> Result<Worker> {
+        let root = fs::canonicalize(workspace_root())?;
+        let package = fs::canonicalize(std::env::current_dir()?)?;
+        let copy = std::path::absolute(paths().workers().join(index.to_string()))?;
+        let skip = [paths().root.clone(), paths().target()]
+            .iter()
+            .filter_map(|dir| fs::canonicalize(dir).ok())
+            .collect::<Vec<_>>();
+        // The sources could have changed since the last run, so nothing of the copy is kept.
+        if copy.exists() {
+            fs::remove_dir_all(&copy)?;
+        }
+        copy_project(&root, &copy, &root, &skip)?;
+        Ok(Worker {
+            dir: copy.join(package.strip_prefix(&root)?),
+            target: paths().target().join(format!("worker-{index}")),
+        })
+    }
+}
+
+#[derive(Parser, Debug)]
+#[command(name = "cargo-bounds")]
+struct Args {
+    #[command(subcommand)]
+    cli: Cli,
+    /// How to show progress (DEFAULT: spinner, or plain when stderr isnt a terminal)
+    #[arg(long, global = true, value_enum)]
+    display: Option<DisplayMode>,
+    /// Same as `--display plain`
+    #[arg(long, global = true)]
+    no_progress: bool,
+    /// When to color the output, `auto` respects `NO_COLOR`
+    #[arg(long, global = true, value_enum, default_value_t)]
+    color: ColorChoice,
+    /// Wait for another run in this project to finish instead of failing
+    #[arg(long, global = true)]
+    wait: bool,
+    /// Start even while another run in this project is active, taking over its lock
+    #[arg(long, global = true, conflicts_with = "wait")]
+    force: bool,
+    /// Where to keep logs, results and everything else written besides the manifest
+    /// (DEFAULT: "target/cargo-bounds")
+    #[arg(long, global = true, env = "CARGO_BOUNDS_STATE_DIR")]
+    state_dir: Option<PathBuf>,
+    /// Where the checks build, so the project's own target directory is left alone
+    /// (DEFAULT: "target" in the state directory)
+    #[arg(long, global = true)]
+    target_dir: Option<PathBuf>,
+    /// Remove the target directory of the checks first, for results without a warm cache
+    #[arg(long, global = true)]
+    clean: bool,
+    /// Take the `[patch]` and `[replace]` sections out of the workspace manifest while testing,
+    /// so the checks build what users of the published crate get. They are put back afterwards
+    #[arg(long, global = true)]
+    ignore_patches: bool,
+    /// Check every version again instead of reusing results of earlier runs
+    #[arg(long, global = true)]
+    no_cache: bool,
+    /// Also reuse failures of earlier runs, instead of checking them again
+    #[arg(long, global = true, conflicts_with = "no_cache")]
+    cache_failures: bool,
+    /// At most this many requests to crates.io per minute
+    #[arg(long, global = true, default_value_t = 60, value_parser = clap::value_parser!(u32).range(1..))]
+    requests_per_minute: u32,
+    /// Wait at least this long between requests to crates.io, instead of `--requests-per-minute`.
+    /// The crates.io crawler policy asks for at most one request per second
+    #[arg(
+        long,
+        global = true,
+        conflicts_with = "requests_per_minute",
+        value_parser = clap::value_parser!(u64).range(1000..)
+    )]
+    api_rate_limit_ms: Option<u64>,
+    /// Who to tell crates.io is asking, so it can contact you about a bulk run
+    #[arg(long, global = true, env = "CARGO_BOUNDS_USER_AGENT")]
+    user_agent: Option<String>,
+    /// Go easy on crates.io: at most 20 requests per minute, and no refetching of data cached in the last day
+    #[arg(long, global = true)]
+    courtesy: bool,
+}
+
+/// The layout of the state directory, everything cargo-bounds writes besides the manifest.
+///
+/// ```text
+/// <state dir>/
+///   .lock            held by the running invocation
+///   knowledge.json   results of earlier checks
+///   snapshot.json    the manifest and lockfile to put back, see [`State::restore_on_exit`]
+///   history.jsonl    a line per `test` run, for `trend`
+///   logs/            logs of failing checks
+///     <package>/     the logs of each `--package`
+///   registry/        responses from crates.io, see [`registry_fetch`]
+///     <name>-index/  checkout of a git index, see [`get_registry_versions`]
+///   workers/<n>/     project copies of `minimize --jobs`, see [`Worker`]
+///   target/          what the checks build, unless `--target-dir` moves it
+///     worker-<n>/    the target directory of each worker
+/// ```
+struct Paths {
+    root: PathBuf,
+}
+
+impl Paths {
+    fn lock(&self) -> PathBuf {
+        self.root.join(".lock")
+    }
+
+    fn knowledge(&self) -> PathBuf {
+        self.root.join("knowledge.json")
+    }
+
+    fn snapshot(&self) -> PathBuf {
+        self.root.join("snapshot.json")
+    }
+
+    fn logs(&self) -> PathBuf {
+        self.root.join("logs")
+    }
+
+    fn history(&self) -> PathBuf {
+        self.root.join("history.jsonl")
+    }
+
+    fn registry(&self) -> PathBuf {
+        self.root.join("registry")
+    }
+
+    fn workers(&self) -> PathBuf {
+        self.root.join("workers")
+    }
+
+    /// The `CARGO_TARGET_DIR` of the checks, absolute since custom commands may change directory.
+    fn target(&self) -> PathBuf {
+        let target = TARGET_DIR
+            .get()
+            .cloned()
+            .unwrap_or_else(|| self.root.join("target"));
+        std::path::absolute(&target).unwrap_or(target)
+    }
+}
+
+/// Where the state goes without `--state-dir`.
+const DEFAULT_STATE_DIR: &str = "target/cargo-bounds";
+
+fn paths() -> &'static Paths {
+    PATHS.get_or_init(|| Paths {
+        root: PathBuf::from(DEFAULT_STATE_DIR),
+    })
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum DisplayMode {
+    /// One line when a check starts and one when it finishes.
+    Plain,
+    /// A spinner per check showing cargo's output.
+    Spinner,
+    /// One line per dependency with a progress bar and its failures so far.
+    Compact,
+}
+
+fn display_mode() -> DisplayMode {
+    DISPLAY.get().copied().unwrap_or(DisplayMode::Plain)
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default)]
+enum ColorChoice {
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                std::io::stdout().is_terminal()
+                    && std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+            }
+        }
+    }
+}
+
+#[derive(Parser, Debug)]
+struct MinimizeConfig {
+    /// Minimize a specific dependency
+    dep: Option<String>,
+    /// Skip the sanity check
     #[arg(short, long)]
-    command: Option<String>,
+    skip_sanity: bool,
+    /// Skip checking the found bounds of all dependencies together
+    #[arg(long)]
+    skip_combined: bool,
+    /// Check every version in the found range, the same as `--sanity-granularity patch`
+    #[arg(long, conflicts_with_all = ["skip_sanity", "sanity_granularity"])]
+    exhaustive: bool,
+    /// How densely the sanity check covers the found range: one version per breaking series,
+    /// per minor or every version
+    #[arg(long, default_value_t = Granularity::Minor, conflicts_with = "skip_sanity")]
+    sanity_granularity: Granularity,
+    /// Print the versions that arent checked
+    #[arg(long)]
+    print_skiped: bool,
+    /// Only search for the minimum, keeping the upper end of the current requirement
+    #[arg(long, conflicts_with = "upper_only")]
+    lower_only: bool,
+    /// Only search for the maximum, keeping the lower end of the current requirement
+    #[arg(long)]
+    upper_only: bool,
+    /// Dont suggest a range spanning several `0.x` minors unless a version of each one passed
+    #[arg(long)]
+    zerover_strict: bool,
+    /// Once the minimum is found, check it again with a lockfile resolved by
+    /// `-Zdirect-minimal-versions` and raise it until that builds too. Needs a nightly toolchain
+    #[arg(long, conflicts_with = "upper_only")]
+    verify_minimal: bool,
+    /// Dont search below this version, for every dep or with `dep=version` for one of them
+    #[arg(long, value_name = "[DEP=]VERSION")]
+    floor: Vec<VersionLimit>,
+    /// Dont search above this version, for every dep or with `dep=version` for one of them
+    #[arg(long, value_name = "[DEP=]VERSION")]
+    ceiling: Vec<VersionLimit>,
+    /// Minimize this many dependencies at once. Each job checks in its own copy of the project
+    /// with its own target directory, so disk and memory use grow with every job
+    #[arg(short, long, default_value_t = 1, value_parser = clap::value_parser!(u16).range(1..))]
+    jobs: u16,
+    /// Also write the results to a file, e.g. `fixes-json=fixes.json`
+    #[arg(short, long)]
+    output: Option<OutputTarget>,
+    #[command(flatten)]
+    search: SearchArgs,
+}
+
+#[derive(Parser, Debug)]
+struct UpdateConfig {
+    /// Update a specific dependency
+    dep: Option<String>,
+    /// Write the raised bounds to the manifest
+    #[arg(long)]
+    apply: bool,
+    #[command(flatten)]
+    search: SearchArgs,
+}
+
+/// How `minimize` and `update` check, with a single toolchain and target.
+#[derive(clap::Args, Debug, Default, Clone)]
+struct SearchArgs {
+    /// Dont check that the project builds with its current dependencies first
+    #[arg(long)]
+    skip_baseline: bool,
+    /// Check with this rustup toolchain, e.g. `1.70`
+    #[arg(long, value_name = "NAME")]
+    toolchain: Option<String>,
+    /// Check for this target triple, e.g. `wasm32-unknown-unknown`
+    #[arg(long, value_name = "TRIPLE")]
+    target: Option<String>,
+    /// Extra arguments for the built-in command, e.g. `-- --locked`
+    #[arg(last = true, value_name = "CARGO_ARGS", conflicts_with = "command")]
+    cargo_args: Vec<String>,
+    #[command(flatten)]
+    rust_version: RustVersionArgs,
+    #[command(flatten)]
+    check: CheckArgs,
+}
+
+impl SearchArgs {
+    /// The `test` options these check with.
+    fn test_config(&self) -> TestConfig {
+        TestConfig {
+            rust_version: self.rust_version,
+            skip_baseline: self.skip_baseline,
+            toolchain: self.toolchain.clone().into_iter().collect(),
+            target: self.target.clone().into_iter().collect(),
+            cargo_args: self.cargo_args.clone(),
+            check: self.check.clone(),
+            ..TestConfig::default()
+        }
+    }
+}
+
+/// Whether versions that need a newer Rust than the toolchain are checked.
+#[derive(clap::Args, Debug, Default, Clone, Copy)]
+struct RustVersionArgs {
+    /// Skip versions whose `rust-version` is newer than the toolchain (the default)
+    #[arg(long, overrides_with = "ignore_rust_version")]
+    respect_rust_version: bool,
+    /// Check versions even when their `rust-version` is newer than the toolchain
+    #[arg(long, overrides_with = "respect_rust_version")]
+    ignore_rust_version: bool,
+}
+
+impl MinimizeConfig {
+    /// How densely the sanity check covers the found range.
+    fn sanity_granularity(&self) -> Granularity {
+        match self.exhaustive {
+            true => Granularity::Patch,
+            false => self.sanity_granularity,
+        }
+    }
+}
+
+impl RustVersionArgs {
+    /// The toolchain version releases are held to, `None` when they arent.
+    fn toolchain(self) -> Option<&'static semver::Version> {
+        if self.ignore_rust_version && !self.respect_rust_version {
+            return None;
+        }
+        CARGO_VERSION.get()
+    }
+}
+
+#[derive(Subcommand, Debug)]
+enum Cli {
+    /// Test if your current depedency bounds are valid.
+    Test(TestConfig),
+    /// Find the most flexible range you could support
+    Minimize(MinimizeConfig),
+    /// Raise the upper end of the bounds to the newest version that still builds
+    Update(UpdateConfig),
+    /// Tighten the requirements to leave out the versions the last `test` run found failing
+    Fix(FixConfig),
+    /// Apply the edits from a `fixes-json` file to the manifests
+    ApplyFixes {
+        /// The file written by `minimize --output fixes-json=<file>`
+        file: PathBuf,
+    },
+    /// Rerun the failing versions whenever `src` or `Cargo.toml` changes
+    Watch(TestConfig),
+    /// Show how the working window of each dependency changed over the recorded `test` runs
+    ///
+    /// Only runs with the same check command and granularity as the latest one are compared,
+    /// the others are left out rather than aligned.
+    Trend(TrendConfig),
+    /// Explain why `test` would or wouldnt test a specific version
+    ExplainSkip(ExplainConfig),
+    /// Print the edges of each bound as a CI build matrix, without building anything
+    Matrix(MatrixConfig),
+    /// Find the version between a working and a broken one where `--dep` changed
+    Bisect(BisectConfig),
+    /// Flag suspicious requirements, without building anything
+    Lint(LintConfig),
+    /// Test the bounds of a crate published on crates.io, in a copy outside this project
+    CheckCrate(CheckCrateConfig),
+    /// Put back the manifest and lockfile a run left pinned with `--keep-manifest`
+    Restore,
+    /// Compare two runs saved with `test --save-baseline`, failing when a version regressed
+    Diff {
+        /// The results of the earlier run
+        old: PathBuf,
+        /// The results of the later run
+        new: PathBuf,
+    },
+    /// Check that cargo, the registries and the project are ready for a run
+    Doctor,
+    /// Print the completion script for a shell
+    Completions {
+        /// The shell to complete in
+        shell: Shell,
+    },
+    /// Print the man page, for packagers
+    #[command(hide = true)]
+    Manpage,
+}
+
+impl Cli {
+    /// The toolchains the checks run with, see `--toolchain`.
+    fn toolchains(&self) -> &[String] {
+        match self {
+            Cli::Test(config) | Cli::Watch(config) => &config.toolchain,
+            Cli::Bisect(config) => &config.test.toolchain,
+            Cli::Minimize(MinimizeConfig { search, .. })
+            | Cli::Update(UpdateConfig { search, .. }) => search.toolchain.as_slice(),
+            _ => &[],
+        }
+    }
+
+    /// The targets the checks build for, see `--target`.
+    fn targets(&self) -> &[String] {
+        match self {
+            Cli::Test(config) | Cli::Watch(config) => &config.target,
+            Cli::Bisect(config) => &config.test.target,
+            Cli::Minimize(MinimizeConfig { search, .. })
+            | Cli::Update(UpdateConfig { search, .. }) => search.target.as_slice(),
+            _ => &[],
+        }
+    }
+
+    /// Make the paths given on the command line absolute, before changing directory.
+    fn make_paths_absolute(&mut self) -> Result<()> {
+        match self {
+            Cli::Test(config) | Cli::Watch(config) => config.make_paths_absolute(),
+            Cli::Bisect(config) => config.test.make_paths_absolute(),
+            Cli::Minimize(MinimizeConfig {
+                output: Some(output),
+                ..
+            }) => {
+                output.path = std::path::absolute(&output.path)?;
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Parser, Debug)]
+struct FixConfig {
+    /// Fix a specific dependency
+    dep: Option<String>,
+    /// Take the results from a file of `test --save-baseline` or `--artifacts` instead of the
+    /// last `test` run
+    #[arg(long, value_name = "FILE")]
+    from: Option<PathBuf>,
+    /// Write the tightened requirements to the manifest
+    #[arg(long)]
+    apply: bool,
+}
+
+#[derive(Parser, Debug)]
+struct BisectConfig {
+    /// A version that is known to work
+    #[arg(long)]
+    good: semver::Version,
+    /// A version that is known to fail
+    #[arg(long)]
+    bad: semver::Version,
+    #[command(flatten)]
+    test: TestConfig,
+}
+
+#[derive(Parser, Debug)]
+struct MatrixConfig {
+    /// Only include these dependencies
+    #[arg(short, long)]
+    dep: Vec<String>,
+    /// Include one version per minor instead of just the oldest and newest
+    #[arg(short, long)]
+    minor: bool,
+    /// How to print the matrix
+    #[arg(long, value_enum, default_value_t)]
+    format: MatrixFormat,
+    #[command(flatten)]
+    rust_version: RustVersionArgs,
+}
+
+#[derive(Parser, Debug)]
+struct LintConfig {
+    /// Report these rules as errors, which fail the run
+    #[arg(long, value_enum, value_name = "RULE")]
+    deny: Vec<LintRule>,
+    /// Report these rules as warnings
+    #[arg(long, value_enum, value_name = "RULE")]
+    warn: Vec<LintRule>,
+    /// Dont check these rules
+    #[arg(long, value_enum, value_name = "RULE")]
+    allow: Vec<LintRule>,
+}
+
+/// What `lint` looks for in a requirement.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum LintRule {
+    /// `=1.2.3`, unless the dep has the `exact` policy
+    ExactPin,
+    /// `*`, which allows every release ever published
+    Wildcard,
+    /// `0`, which allows every `0.x` release even though each minor is a breaking one
+    BareZero,
+    /// A requirement no published version matches
+    NoMatch,
+    /// A requirement whose oldest allowed version was yanked
+    YankedMinimum,
+}
+
+impl LintRule {
+    /// The name of the rule on the command line and in the metadata.
+    fn name(self) -> &'static str {
+        match self {
+            LintRule::ExactPin => "exact-pin",
+            LintRule::Wildcard => "wildcard",
+            LintRule::BareZero => "bare-zero",
+            LintRule::NoMatch => "no-match",
+            LintRule::YankedMinimum => "yanked-minimum",
+        }
+    }
+
+    fn default_level(self) -> LintLevel {
+        match self {
+            LintRule::NoMatch => LintLevel::Deny,
+            _ => LintLevel::Warn,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum LintLevel {
+    Allow,
+    Warn,
+    Deny,
+}
+
+/// How `matrix` prints the include list.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum MatrixFormat {
+    /// A JSON array, for `fromJSON` in a workflow
+    #[default]
+    Json,
+    /// A YAML list, to paste under `matrix: include:`
+    Yaml,
+}
+
+#[derive(Parser, Debug)]
+struct ExplainConfig {
+    /// The dependency the version belongs to
+    #[arg(value_name = "DEP")]
+    name: String,
+    /// The version to explain
+    version: semver::Version,
+    #[command(flatten)]
+    test: TestConfig,
+}
+
+#[derive(Parser, Debug)]
+struct CheckCrateConfig {
+    /// The crate on crates.io to check
+    #[arg(value_name = "CRATE")]
+    name: String,
+    /// The published version to check (DEFAULT: the newest one)
+    #[arg(long)]
+    version: Option<semver::Version>,
+    #[command(flatten)]
+    test: TestConfig,
+}
+
+#[derive(Parser, Debug)]
+struct TrendConfig {
+    /// How many of the latest runs to look at
+    #[arg(long, default_value_t = 10, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    runs: usize,
+    /// Print the trends as JSON instead of a table
+    #[arg(long)]
+    json: bool,
+}
+
+/// The built-in check commands.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum Mode {
+    #[default]
+    Check,
+    Build,
+    Test,
+    Clippy,
+}
+
+/// How the version under test is pinned.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum PinStrategy {
+    /// Write `=version` into the manifest
+    #[default]
+    Manifest,
+    /// Run `cargo update --precise`, falling back to the manifest for versions outside the requirement
+    Lockfile,
+}
+
+/// What the `--badge` says.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum BadgeMessage {
+    /// How many deps hold their bounds, like `12/12 ok`
+    #[default]
+    Count,
+    /// `passing` or `failing`
+    Word,
+}
+
+/// Which CI `--annotations` are printed for.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Annotations {
+    /// GitHub Actions workflow commands, `::error file=...::`
+    Github,
+    /// No annotations, even on GitHub Actions
+    #[value(name = "none")]
+    Off,
+}
+
+/// What `--fail-fast` stops at the first failing version.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum FailFast {
+    /// The rest of that dependency, moving on to the next one
+    Dep,
+    /// The whole run
+    All,
+}
+
+/// What `--conflicts` makes of a version cargo cant resolve the dependency graph for.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq, Default)]
+enum Conflicts {
+    /// It fails like a version the crate doesnt compile with
+    #[default]
+    Fail,
+    /// It is reported as untestable and doesnt count as a failure
+    Untestable,
+}
+
+/// Why a check failed.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+enum FailureKind {
+    /// Cargo couldnt resolve the dependency graph with the version pinned.
+    ResolutionConflict,
+    /// The crate didnt compile against the version.
+    CompileError,
+    /// Downloading or fetching something failed, which is often transient.
+    Network,
+    /// Anything else, like a failing custom command.
+    Other,
+    /// The check was killed by a signal, e.g. by the OOM killer, so it says nothing about the
+    /// version.
+    Killed,
+}
+
+impl Display for FailureKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FailureKind::ResolutionConflict => write!(f, "resolution conflict"),
+            FailureKind::CompileError => write!(f, "compile error"),
+            FailureKind::Network => write!(f, "network error"),
+            FailureKind::Other => write!(f, "other"),
+            FailureKind::Killed => write!(f, "killed"),
+        }
+    }
+}
+
+/// Tell from the stderr of a failed check whether cargo couldnt resolve, compile or download.
+fn classify_failure(output: &[String]) -> FailureKind {
+    const NETWORK: [&str; 7] = [
+        "failed to download",
+        "spurious network error",
+        "Couldn't resolve host",
+        "Connection reset",
+        "Connection refused",
+        "timed out",
+        "failed to update registry",
+    ];
+    const RESOLUTION: [&str; 4] = [
+        "failed to select a version",
+        "no matching package named",
+        "failed to get `",
+        "cyclic package dependency",
+    ];
+    // Cargo prints in color when asked to, which would hide the prefixes.
+    let lines = output
+        .iter()
+        .map(|line| strip_ansi(line))
+        .collect::<Vec<_>>();
+    let mentions = |patterns: &[&str]| {
+        lines
+            .iter()
+            .any(|line| patterns.iter().any(|pattern| line.contains(pattern)))
+    };
+    if mentions(&NETWORK) {
+        FailureKind::Network
+    } else if mentions(&RESOLUTION) {
+        FailureKind::ResolutionConflict
+    } else if lines
+        .iter()
+        .any(|line| line.starts_with("error[E") || line.starts_with("error: could not compile"))
+    {
+        FailureKind::CompileError
+    } else {
+        FailureKind::Other
+    }
+}
+
+/// What happened to the latest check of a version, for the summary.
+#[derive(Clone, Default)]
+struct CheckNote {
+    /// Why it failed, see [`classify_failure`].
+    failure: Option<FailureKind>,
+    /// How often it was tried again after a network error, see `--retries`.
+    retries: u32,
+    /// The logs of its checks, one per configuration it was checked in.
+    logs: Vec<PathBuf>,
+}
+
+/// Remember why `version` of `dep` failed, or that it passed when `failure` is `None`.
+fn record_failure(dep: &str, version: &semver::Version, failure: Option<FailureKind>) {
+    let mut notes = CHECK_NOTES.lock().unwrap();
+    notes
+        .entry((dep.to_owned(), version.clone()))
+        .or_default()
+        .failure = failure;
+}
+
+fn record_retry(dep: &str, version: &semver::Version) {
+    let mut notes = CHECK_NOTES.lock().unwrap();
+    notes
+        .entry((dep.to_owned(), version.clone()))
+        .or_default()
+        .retries += 1;
+}
+
+/// What happened to `version` of `dep` in this run, the default for results reused from
+/// earlier runs.
+fn check_note(dep: &str, version: &semver::Version) -> CheckNote {
+    CHECK_NOTES
+        .lock()
+        .unwrap()
+        .get(&(dep.to_owned(), version.clone()))
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// Remember the log written for a check of `version` of `dep`.
+fn record_log(dep: &str, version: &semver::Version, log: PathBuf) {
+    let mut notes = CHECK_NOTES.lock().unwrap();
+    let logs = &mut notes
+        .entry((dep.to_owned(), version.clone()))
+        .or_default()
+        .logs;
+    if !logs.contains(&log) {
+        logs.push(log);
+    }
+}
+
+/// Which crates `--minimal-versions` resolves to their oldest allowed version.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum MinimalVersions {
+    /// Every crate in the graph
+    All,
+    /// Only the direct dependencies
+    Direct,
+}
+
+impl MinimalVersions {
+    fn flag(self) -> &'static str {
+        match self {
+            MinimalVersions::All => "-Zminimal-versions",
+            MinimalVersions::Direct => "-Zdirect-minimal-versions",
+        }
+    }
+}
+
+impl Mode {
+    fn subcommand(self) -> &'static str {
+        match self {
+            Mode::Check => "check",
+            Mode::Build => "build",
+            Mode::Test => "test",
+            Mode::Clippy => "clippy",
+        }
+    }
+}
+
+/// Parse a `key=value` argument.
+fn parse_key_value(arg: &str) -> Result<(String, String)> {
+    let (key, value) = arg
+        .split_once('=')
+        .ok_or(anyhow!("expected <key>=<value>"))?;
+    Ok((key.to_owned(), value.to_owned()))
+}
+
+/// A machine readable output format and where to write it.
+#[derive(Clone, Debug)]
+struct OutputTarget {
+    format: OutputFormat,
+    path: PathBuf,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum OutputFormat {
+    /// Proposed manifest edits, see [`Fixes`].
+    FixesJson,
+}
+
+/// A report of the results of `test` and where to write it.
+#[derive(Clone, Debug)]
+struct ReportTarget {
+    format: ReportFormat,
+    path: PathBuf,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum ReportFormat {
+    /// A single page to share, see [`render_html`].
+    Html,
+    /// The same as `results.json` of `--artifacts`.
+    Json,
+}
+
+impl FromStr for ReportTarget {
+    type Err = anyhow::Error;
+
+    fn from_str(target: &str) -> Result<Self> {
+        let (format, path) = target
+            .split_once('=')
+            .ok_or(anyhow!("expected <format>=<path>"))?;
+        let format = match format {
+            "html" => ReportFormat::Html,
+            "json" => ReportFormat::Json,
+            _ => return Err(anyhow!("unknown format {format}, expected html or json")),
+        };
+        Ok(ReportTarget {
+            format,
+            path: path.into(),
+        })
+    }
+}
+
+/// A version of a dep to test with a local checkout in its place.
+#[derive(Clone, Debug)]
+struct PatchedVersion {
+    dep: String,
+    version: semver::Version,
+    path: PathBuf,
+}
+
+impl FromStr for PatchedVersion {
+    type Err = anyhow::Error;
+
+    fn from_str(patch: &str) -> Result<Self> {
+        let mut parts = patch.splitn(3, '=');
+        let (Some(dep), Some(version), Some(path)) = (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(anyhow!("expected <dep>=<version>=<path>"));
+        };
+        Ok(PatchedVersion {
+            dep: dep.to_owned(),
+            version: semver::Version::parse(version)?,
+            path: path.into(),
+        })
+    }
+}
+
+/// A `--floor` or `--ceiling` of `minimize`, for every dep unless it names one.
+#[derive(Clone, Debug)]
+struct VersionLimit {
+    dep: Option<String>,
+    version: semver::Version,
+}
+
+impl VersionLimit {
+    /// The limit for `dep`, one naming it wins over one for every dep.
+    fn find<'a>(limits: &'a [VersionLimit], dep: &str) -> Option<&'a semver::Version> {
+        let named = limits
+            .iter()
+            .find(|limit| limit.dep.as_deref() == Some(dep));
+        named
+            .or_else(|| limits.iter().find(|limit| limit.dep.is_none()))
+            .map(|limit| &limit.version)
+    }
+}
+
+impl FromStr for VersionLimit {
+    type Err = anyhow::Error;
+
+    fn from_str(limit: &str) -> Result<Self> {
+        let (dep, version) = match limit.split_once('=') {
+            Some((dep, version)) => (Some(dep.to_owned()), version),
+            None => (None, limit),
+        };
+        Ok(VersionLimit {
+            dep,
+            version: semver::Version::parse(version)?,
+        })
+    }
+}
+
+impl FromStr for OutputTarget {
+    type Err = anyhow::Error;
+
+    fn from_str(target: &str) -> Result<Self> {
+        let (format, path) = target
+            .split_once('=')
+            .ok_or(anyhow!("expected <format>=<path>"))?;
+        let format = match format {
+            "fixes-json" => OutputFormat::FixesJson,
+            _ => return Err(anyhow!("unknown format {format}, expected fixes-json")),
+        };
+        Ok(OutputTarget {
+            format,
+            path: path.into(),
+        })
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct State {
+    /// Where the manifest is, absolute since `--package` changes directory.
+    manifest: PathBuf,
+    cargo_toml: Box<str>,
+    lockfile: Lockfile,
+    /// The workspace manifest as it was before `--ignore-patches`, see [`PATCHES`].
+    patches: Option<(PathBuf, Box<str>)>,
+}
+
+impl State {
+    fn store() -> Result<Self> {
+        let state = State {
+            manifest: std::path::absolute("Cargo.toml")?,
+            cargo_toml: fs::read_to_string("Cargo.toml")?.into(),
+            lockfile: Lockfile::store()?,
+            patches: PATCHES.get().cloned(),
+        };
+        state.restore_on_exit()?;
+        Ok(state)
+    }
+
+    /// Make this the state [`shutdown`] puts back.
+    ///
+    /// It is written to the state directory too, so `cargo bounds restore` can put it back
+    /// after the process is gone.
+    fn restore_on_exit(&self) -> Result<()> {
+        *RESTORE_ON_EXIT.lock().unwrap() = Some(self.clone());
+        fs::create_dir_all(&paths().root)?;
+        write_atomic(paths().snapshot(), serde_json::to_string(self)?)
+    }
+
+    /// Drop the snapshot of [`State::restore_on_exit`], once nothing is left to put back.
+    fn forget() -> Result<()> {
+        match fs::remove_file(paths().snapshot()) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn restore(&self) -> Result<()> {
+        write_atomic(&self.manifest, self.cargo_toml.as_bytes())?;
+        self.lockfile.restore()?;
+
+        Ok(())
+    }
+}
+
+/// Replace the file at `path` with `contents`, so it is never left half written.
+///
+/// The contents go to a temporary file next to it first, which is then renamed over it. The
+/// permissions of the file it replaces are kept.
+fn write_atomic(path: impl AsRef<Path>, contents: impl AsRef<[u8]>) -> Result<()> {
+    write_atomic_with(path.as_ref(), |file| file.write_all(contents.as_ref()))
+}
+
+/// [`write_atomic`] with `write` filling in the temporary file, which tests use to fail midway.
+fn write_atomic_with(
+    path: &Path,
+    write: impl FnOnce(&mut fs::File) -> std::io::Result<()>,
+) -> Result<()> {
+    let name = path
+        .file_name()
+        .ok_or(anyhow!("cant write to {}", path.display()))?;
+    let temp = path.with_file_name(format!(".{}.cargo-bounds-tmp", name.to_string_lossy()));
+    let written = (|| -> Result<()> {
+        let mut file = fs::File::create(&temp)?;
+        write(&mut file)?;
+        if let Ok(metadata) = fs::metadata(path) {
+            file.set_permissions(metadata.permissions())?;
+        }
+        file.sync_all()?;
+        fs::rename(&temp, path)?;
+        Ok(())
+    })();
+    if written.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    written
+}
+
+/// The `Cargo.lock` of the workspace, which cargo rewrites for every pinned version.
+#[derive(Clone, Serialize, Deserialize)]
+struct Lockfile {
+    path: PathBuf,
+    /// `None` when there was no lockfile, restoring then removes the one cargo created.
+    contents: Option<Box<str>>,
+}
+
+impl Lockfile {
+    fn store() -> Result<Self> {
+        let path = workspace_root().join("Cargo.lock");
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => Some(contents.into()),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => None,
+            Err(err) => return Err(err.into()),
+        };
+        Ok(Lockfile { path, contents })
+    }
+
+    fn restore(&self) -> Result<()> {
+        match &self.contents {
+            Some(contents) => write_atomic(&self.path, contents.as_bytes())?,
+            None => match fs::remove_file(&self.path) {
+                Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(err.into()),
+                _ => {}
+            },
+        }
+        Ok(())
+    }
+}
+
+/// The directory of the workspace the current package is in, where its lockfile lives.
+fn workspace_root() -> PathBuf {
+    Command::new("cargo")
+        .args(["locate-project", "--workspace", "--message-format", "plain"])
+        .stderr(Stdio::null())
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| {
+            let manifest = PathBuf::from(String::from_utf8(output.stdout).ok()?.trim());
+            Some(manifest.parent()?.to_owned())
+        })
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+/// The command line as a whole, `cargo bounds` runs us as `cargo-bounds bounds`.
+fn cli_command() -> clap::Command {
+    let bounds = Args::command();
+    bounds.clone().subcommand(bounds.name("bounds").hide(true))
+}
+
+fn main() -> Result<()> {
+    // So the usage reads `cargo bounds ...` either way.
+    let mut command = if std::env::args_os()
+        .nth(1)
+        .is_some_and(|arg| arg == "bounds")
+    {
+        cli_command().bin_name("cargo")
+    } else {
+        cli_command().bin_name("cargo bounds")
+    };
+    let matches = command.get_matches_mut();
+    let matches = matches.subcommand_matches("bounds").unwrap_or(&matches);
+    let mut args =
+        Args::from_arg_matches(matches).unwrap_or_else(|err| err.format(&mut command).exit());
+    // The matrix is printed to stdout, spinners go to stderr and hide themselves without a terminal.
+    let display = if matches!(args.cli, Cli::Matrix(_)) {
+        DisplayMode::Spinner
+    } else if args.no_progress || !std::io::stderr().is_terminal() {
+        DisplayMode::Plain
+    } else {
+        let watching = matches!(args.cli, Cli::Watch(_));
+        args.display.unwrap_or(if watching {
+            DisplayMode::Compact
+        } else {
+            DisplayMode::Spinner
+        })
+    };
+    let _ = DISPLAY.set(display);
+    COLOR.store(args.color.enabled(), Ordering::Relaxed);
+    NO_CACHE.store(args.no_cache, Ordering::Relaxed);
+    CACHE_FAILURES.store(args.cache_failures, Ordering::Relaxed);
+    let spacing = match args.api_rate_limit_ms {
+        Some(ms) => Duration::from_millis(ms),
+        None => Duration::from_secs(60) / args.requests_per_minute,
+    };
+    REGISTRY_BUDGET
+        .lock()
+        .unwrap()
+        .configure(spacing, args.courtesy);
+    if let Some(user_agent) = args.user_agent {
+        let _ = USER_AGENT.set(user_agent);
+    }
+    let cli = args.cli;
+
+    // Applying fixes edits the manifests on purpose, so it must not restore them afterwards.
+    if let Cli::ApplyFixes { file } = &cli {
+        return apply_fixes(file);
+    }
+    // Neither needs a project.
+    if let Cli::Completions { shell } = &cli {
+        // Buffered, `generate` panics instead of returning write errors.
+        let mut script = Vec::new();
+        clap_complete::generate(*shell, &mut cli_command(), "cargo-bounds", &mut script);
+        std::io::stdout().write_all(&script)?;
+        return Ok(());
+    }
+    if let Cli::Manpage = &cli {
+        clap_mangen::Man::new(Args::command()).render(&mut std::io::stdout())?;
+        return Ok(());
+    }
+    // Only reads the two files, it doesnt even need a manifest.
+    if let Cli::Diff { old, new } = &cli {
+        let diff = ResultsDiff::between(&SavedResults::load(old)?, &SavedResults::load(new)?);
+        diff.print();
+        return diff.check();
+    }
+    // From here on it is an ordinary `test`, just in the directory of the published crate.
+    let cli = match cli {
+        Cli::CheckCrate(config) => {
+            let dir = fetch_crate(&config.name, config.version.as_ref())?;
+            let mut cli = Cli::Test(config.test);
+            enter_dir(&dir, &mut cli, [&mut args.state_dir, &mut args.target_dir])?;
+            CHECKING_PUBLISHED.store(true, Ordering::Relaxed);
+            cli
+        }
+        mut cli => {
+            let cwd = std::env::current_dir()?;
+            let dir = cwd
+                .ancestors()
+                .find(|dir| dir.join("Cargo.toml").is_file())
+                .ok_or_else(|| {
+                    anyhow!(
+                        "could not find Cargo.toml in {} or any parent directory",
+                        cwd.display()
+                    )
+                })?;
+            if dir != cwd {
+                // On stderr, so it doesnt end up in `matrix --format json`.
+                eprintln!(
+                    "{}",
+                    format!("Using {}", dir.join("Cargo.toml").display()).bright_black()
+                );
+                enter_dir(dir, &mut cli, [&mut args.state_dir, &mut args.target_dir])?;
+            }
+            cli
+        }
+    };
+
+    // `--state-dir` and the environment win over the manifest.
+    let state_dir = match args.state_dir {
+        Some(state_dir) => Some(state_dir),
+        None => {
+            Metadata::load(&fs::read_to_string("Cargo.toml")?.parse::<DocumentMut>()?)?.state_dir
+        }
+    };
+    // `--package` moves between the members, the state stays where the run started.
+    let state_dir = match (&cli, state_dir) {
+        (Cli::Test(test), state_dir) if !test.package.is_empty() => Some(std::path::absolute(
+            state_dir.unwrap_or_else(|| PathBuf::from(DEFAULT_STATE_DIR)),
+        )?),
+        (_, state_dir) => state_dir,
+    };
+    if let Some(root) = state_dir {
+        let _ = PATHS.set(Paths { root });
+    }
+    if let Some(target_dir) = args.target_dir {
+        let _ = TARGET_DIR.set(target_dir);
+    }
+    // Only reads the history, so it doesnt need the lock.
+    if let Cli::Trend(config) = &cli {
+        return trend(config);
+    }
+    if let Cli::ExplainSkip(config) = &cli {
+        return explain_skip(config);
+    }
+    // Only reads the manifest and the registry.
+    if let Cli::Matrix(config) = &cli {
+        return matrix(config);
+    }
+    if let Cli::Lint(config) = &cli {
+        return lint(config);
+    }
+    // Looks at the lock instead of taking it.
+    if let Cli::Doctor = &cli {
+        return doctor();
+    }
+    let lock = ProjectLock::acquire(args.wait, args.force)?;
+    if let Cli::Restore = cli {
+        return restore_snapshot();
+    }
+    // Snapshotting a pinned manifest as the original would lose the real one.
+    if paths().snapshot().exists() {
+        return Err(anyhow!(
+            "An earlier run left the manifest pinned, run `cargo bounds restore` first"
+        ));
+    }
+    // Only edits the manifest with `--apply`, but not under a running test.
+    if let Cli::Fix(config) = &cli {
+        return fix(config);
+    }
+    // Versions are held to the newest toolchain, the older ones are what the checks find out.
+    let mut cargo_version = None;
+    for toolchain in cli.toolchains() {
+        cargo_version = cargo_version.max(Some(probe_cargo(Some(toolchain))?));
+    }
+    let _ = CARGO_VERSION.set(match cargo_version {
+        Some(version) => version,
+        None => probe_cargo(None)?,
+    });
+    check_targets_installed(cli.targets(), cli.toolchains())?;
+    if args.clean {
+        let target = paths().target();
+        if target.exists() {
+            out!("Removing {}", target.display());
+            fs::remove_dir_all(&target)?;
+        }
+    }
+
+    ctrlc::set_handler(|| {
+        let _ = shutdown();
+        ProjectLock::release();
+        std::process::exit(1);
+    })?;
+    handle_patches(args.ignore_patches)?;
+
+    let res = match cli {
+        // Watching takes a new snapshot of the manifest for every run, so edits between runs stay.
+        Cli::Watch(config) => watch(config),
+        cli => State::store().and_then(|state| main_impl(&state, cli)),
+    };
+    shutdown()?;
+    print_registry_stats();
+    let fixes = std::mem::take(&mut *PENDING_FIXES.lock().unwrap());
+    apply_all(fixes)?;
+    // Only now, so no other run snapshots the manifest while the fixes are written.
+    drop(lock);
+
+    res
+}
+
+/// Tear the run down, the same way whether it finished, failed or was interrupted.
+///
+/// Nothing new is started, then the running check is stopped, and only then are the manifest
+/// and lockfile put back. Restoring while cargo still runs could leave it reading a half written
+/// manifest or writing a pinned lockfile after ours. The project lock is left to the caller,
+/// which may still apply the fixes of `--apply`.
+fn shutdown() -> Result<()> {
+    CANCELLED.store(true, Ordering::SeqCst);
+    let mut shutting_down = SHUTTING_DOWN.lock().unwrap();
+    *shutting_down = true;
+
+    for child in RUNNING_CHECKS.lock().unwrap().iter_mut() {
+        stop_check(child);
+    }
+
+    let restored = match RESTORE_ON_EXIT.lock().unwrap().take() {
+        Some(state) if run_keeps_manifest() => {
+            print_kept(&state);
+            Ok(())
+        }
+        state => state
+            .map_or(Ok(()), |state| state.restore())
+            .and_then(|()| restore_patches(PATCHES.get()))
+            .and_then(|()| State::forget()),
+    };
+    drop(shutting_down);
+    restored
+}
+
+/// Whether the manifest should be left pinned rather than put back.
+fn run_keeps_manifest() -> bool {
+    KEEP_MANIFEST.load(Ordering::SeqCst)
+}
+
+/// Say that the manifest is left pinned on purpose, and how to put it back.
+fn print_kept(state: &State) {
+    let pin = LAST_CHECKED
+        .lock()
+        .unwrap()
+        .get(Path::new("."))
+        .map(|(dep, version)| format!(" at {} {}", dep.blue(), version.red()))
+        .unwrap_or_default();
+    out!(
+        "{} {} is left pinned{pin}, run {} to put it back",
+        "warning:".yellow(),
+        state.manifest.display(),
+        "cargo bounds restore".green()
+    );
+}
+
+/// Put the `[patch]` and `[replace]` sections `--ignore-patches` took out back.
+fn restore_patches(patches: Option<&(PathBuf, Box<str>)>) -> Result<()> {
+    match patches {
+        Some((manifest, original)) => write_atomic(manifest, original.as_bytes()),
+        None => Ok(()),
+    }
+}
+
+/// The crates the `[patch]` and `[replace]` sections of `manifest` override, each with the
+/// section doing it.
+fn patched_crates(manifest: &DocumentMut) -> Vec<(String, String)> {
+    let mut patched = Vec::new();
+    let sources = manifest
+        .get("patch")
+        .and_then(|patch| patch.as_table_like());
+    for (source, crates) in sources.into_iter().flat_map(|sources| sources.iter()) {
+        let crates = crates.as_table_like();
+        for (name, entry) in crates.into_iter().flat_map(|crates| crates.iter()) {
+            let name = entry
+                .get("package")
+                .and_then(|package| package.as_str())
+                .unwrap_or(name);
+            patched.push((name.to_owned(), format!("[patch.{source}]")));
+        }
+    }
+    let replaced = manifest
+        .get("replace")
+        .and_then(|replace| replace.as_table_like());
+    // Keys look like `foo:1.2.3`.
+    for (spec, _) in replaced.into_iter().flat_map(|replaced| replaced.iter()) {
+        let name = spec.split([':', '@']).next().unwrap_or(spec);
+        patched.push((name.to_owned(), "[replace]".to_owned()));
+    }
+    patched
+}
+
+/// Warn about the `[patch]` and `[replace]` sections of the workspace, or take them out with
+/// `--ignore-patches`.
+///
+/// Cargo only reads them from the workspace manifest, so that is the one looked at.
+fn handle_patches(ignore: bool) -> Result<()> {
+    let manifest = std::path::absolute(workspace_root().join("Cargo.toml"))?;
+    let original = fs::read_to_string(&manifest)?;
+    let mut document = original.parse::<DocumentMut>()?;
+    let patched = patched_crates(&document);
+    if patched.is_empty() {
+        return Ok(());
+    }
+    if ignore {
+        document.remove("patch");
+        document.remove("replace");
+        let _ = PATCHES.set((manifest.clone(), original.into()));
+        write_atomic(&manifest, document.to_string())?;
+        out!(
+            "{}",
+            format!(
+                "Testing without the [patch] and [replace] sections of {}, they are put back afterwards",
+                manifest.display()
+            )
+            .bright_black()
+        );
+        return Ok(());
+    }
+    let names = patched
+        .iter()
+        .map(|(name, section)| format!("{} ({section})", name.blue()))
+        .collect::<Vec<_>>()
+        .join(", ");
+    out!(
+        "{} the workspace overrides {names}, so the checks may not build what users of the published crate get. {} tests without the overrides",
+        "warning:".yellow(),
+        "--ignore-patches".green()
+    );
+    *PATCHED_CRATES.lock().unwrap() = patched;
+    Ok(())
+}
+
+/// The `[patch]` or `[replace]` section overriding `dep`, see [`PATCHED_CRATES`].
+fn patched_section(dep: &str) -> Option<String> {
+    let package = package_name(dep);
+    PATCHED_CRATES
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|(name, _)| *name == package)
+        .map(|(_, section)| section.clone())
+}
+
+/// Say that `dep` is skipped because the workspace overrides it, and whether it is.
+///
+/// Pinning a version of an overridden crate doesnt change what is built.
+fn print_patched(dep: &str) -> bool {
+    let Some(section) = patched_section(dep) else {
+        return false;
+    };
+    out!(
+        "{} {} {}",
+        "Skipping".yellow(),
+        dep.green(),
+        format!("as {section} overrides it, pinning its versions wouldnt change what is built")
+            .yellow()
+    );
+    true
+}
+
+/// Put back the manifest and lockfile a run left pinned, from its snapshot.
+fn restore_snapshot() -> Result<()> {
+    let snapshot = match fs::read_to_string(paths().snapshot()) {
+        Ok(snapshot) => snapshot,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            out!("Nothing to restore, no run left the manifest pinned");
+            return Ok(());
+        }
+        Err(err) => return Err(err.into()),
+    };
+    let state = serde_json::from_str::<State>(&snapshot)?;
+    state.restore()?;
+    restore_patches(state.patches.as_ref())?;
+    State::forget()?;
+    out!(
+        "Restored {} and its lockfile",
+        state.manifest.display().green()
+    );
+    Ok(())
+}
+
+/// Ask a check to stop, kill it when it hasnt after [`CHECK_GRACE`], and wait for it to exit.
+///
+/// On unix the whole process group of the check is signalled, so the rustc processes cargo
+/// started stop with it. Elsewhere only the check itself can be killed.
+fn stop_check(child: &mut Child) {
+    if cfg!(unix) {
+        signal_group(child, "TERM");
+        let deadline = Instant::now() + CHECK_GRACE;
+        while Instant::now() < deadline {
+            if let Ok(Some(_)) = child.try_wait() {
+                // Whatever it started and left behind.
+                signal_group(child, "KILL");
+                return;
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+        signal_group(child, "KILL");
+    }
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+/// Send `signal` to the process group a check was started in, see [`run_check`].
+fn signal_group(child: &Child, signal: &str) {
+    let _ = Command::new("kill")
+        .args([&format!("-{signal}"), "--", &format!("-{}", child.id())])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+}
+
+/// Pin `dep` to `version` with `cargo update --precise`, leaving the manifest alone.
+///
+/// Returns false when cargo rejects the version for being outside the requirement, which only
+/// a manifest pin can test.
+fn pin_in_lockfile(dep: &str, version: &semver::Version, dir: &Path) -> Result<bool> {
+    // With two majors of a package in the lockfile only `name@version` says which one.
+    let mut spec = package_name(dep);
+    if package_declared_twice(dep)
+        && let Some((locked, _)) = resolved_version(dep, dir)
+    {
+        spec = format!("{spec}@{locked}");
+    }
+    // The lockfile is ours to restore, so the update cant overlap a shutdown.
+    let shutting_down = SHUTTING_DOWN.lock().unwrap();
+    if *shutting_down {
+        return Err(Cancelled.into());
+    }
+    let output = Command::new("cargo")
+        .args([
+            "update",
+            "--package",
+            &spec,
+            "--precise",
+            &version.to_string(),
+        ])
+        .current_dir(dir)
+        .output()?;
+    if output.status.success() {
+        return Ok(true);
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if stderr.contains("failed to select a version for the requirement") {
+        return Ok(false);
+    }
+    Err(anyhow!(
+        "`cargo update --precise {version}` failed for {dep}:\n{}",
+        stderr.trim()
+    ))
+}
+
+/// Regenerate the lockfile with the oldest versions the requirements allow.
+///
+/// Returns cargo's error when there is no such resolution, which means the pinned version
+/// doesnt build with the minimums either.
+fn resolve_minimal(minimal: MinimalVersions, dir: &Path) -> Result<Option<String>> {
+    let shutting_down = SHUTTING_DOWN.lock().unwrap();
+    if *shutting_down {
+        return Err(Cancelled.into());
+    }
+    let output = Command::new("cargo")
+        .args(["+nightly", "update", minimal.flag()])
+        .current_dir(dir)
+        .output()?;
+    drop(shutting_down);
+    if output.status.success() {
+        return Ok(None);
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let error = stderr
+        .lines()
+        .find_map(|line| line.strip_prefix("error: "))
+        .unwrap_or(stderr.trim());
+    Ok(Some(error.to_owned()))
+}
+
+/// Write a manifest with pinned versions to the package in `dir`, unless the run is shutting
+/// down.
+fn write_pinned(cargo_toml: &DocumentMut, dir: &Path) -> Result<()> {
+    let shutting_down = SHUTTING_DOWN.lock().unwrap();
+    if *shutting_down {
+        return Err(Cancelled.into());
+    }
+    write_atomic(dir.join("Cargo.toml"), cargo_toml.to_string())?;
+    Ok(())
+}
+
+/// Keeps other runs out of the project while this one edits its manifest.
+///
+/// Otherwise a second run could snapshot our pins as the original manifest and restore them.
+struct ProjectLock;
+
+impl ProjectLock {
+    /// Take the lock, waiting for the current holder with `wait`, taking it from them with
+    /// `force` and failing otherwise.
+    ///
+    /// Locks of processes that no longer exist are taken over.
+    fn acquire(wait: bool, force: bool) -> Result<Self> {
+        fs::create_dir_all(&paths().root)?;
+        let path = &paths().lock();
+        let mut waiting = false;
+        loop {
+            let started = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)?
+                .as_secs();
+            let created = fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(path);
+            match created {
+                Ok(mut file) => {
+                    writeln!(file, "{} {started}", std::process::id())?;
+                    return Ok(ProjectLock);
+                }
+                Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => {}
+                Err(err) => return Err(err.into()),
+            }
+
+            let holder = fs::read_to_string(path).unwrap_or_default();
+            let mut parts = holder.split_whitespace();
+            let pid = parts.next().and_then(|pid| pid.parse::<u32>().ok());
+            let started = parts.next().and_then(|started| started.parse::<u64>().ok());
+            let (Some(pid), Some(started)) = (pid, started) else {
+                // Still being written, or garbage from a crash.
+                thread::sleep(Duration::from_millis(100));
+                if fs::read_to_string(path).is_ok_and(|again| again == holder) {
+                    let _ = fs::remove_file(path);
+                }
+                continue;
+            };
+            if !process_alive(pid) {
+                out!(
+                    "{} removing the lock of pid {pid}, which is gone",
+                    "warning:".yellow()
+                );
+                let _ = fs::remove_file(path);
+                continue;
+            }
+
+            let since = format!("{:02}:{:02} UTC", started / 3600 % 24, started / 60 % 60);
+            if force {
+                out!(
+                    "{} taking over the lock of pid {pid} (started {since}), both runs may now edit the manifest",
+                    "warning:".yellow()
+                );
+                // The other run sees the lock isnt its own anymore and leaves it on exit.
+                let now = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)?
+                    .as_secs();
+                write_atomic(path, format!("{} {now}\n", std::process::id()))?;
+                return Ok(ProjectLock);
+            }
+            if !wait {
+                return Err(anyhow!(
+                    "another cargo-bounds run (pid {pid}, started {since}) is active here, use --wait to wait for it or --force to start anyway"
+                ));
+            }
+            if !waiting {
+                out!("Waiting for another cargo-bounds run (pid {pid}, started {since}) to finish",);
+                waiting = true;
+            }
+            thread::sleep(Duration::from_secs(1));
+        }
+    }
+
+    /// Remove the lock, also used by the Ctrl-C handler, which exits before destructors run.
+    fn release() {
+        let path = &paths().lock();
+        let ours = fs::read_to_string(path).is_ok_and(|holder| {
+            holder.split_whitespace().next() == Some(&std::process::id().to_string())
+        });
+        if ours {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+impl Drop for ProjectLock {
+    fn drop(&mut self) {
+        ProjectLock::release();
+    }
+}
+
+/// Whether a process with this pid is running, assumed true where we cant tell.
+fn process_alive(pid: u32) -> bool {
+    if cfg!(unix) {
+        Command::new("kill")
+            .args(["-0", &pid.to_string()])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success())
+    } else {
+        true
+    }
+}
+
+/// Run from `dir`, keeping the paths given on the command line pointing where they did.
+fn enter_dir(dir: &Path, cli: &mut Cli, dirs: [&mut Option<PathBuf>; 2]) -> Result<()> {
+    for path in dirs.into_iter().flatten() {
+        *path = std::path::absolute(&*path)?;
+    }
+    cli.make_paths_absolute()?;
+    std::env::set_current_dir(dir)?;
+    Ok(())
+}
+
+#[inline]
+fn main_impl(state: &State, cli: Cli) -> Result<()> {
+    *KNOWLEDGE.lock().unwrap() = Some(Knowledge::load(state)?);
+    match cli {
+        Cli::Test(mut test) => {
+            prepare_test(&mut test)?;
+            let explore = test.explore;
+            let annotate = test.github_annotations();
+            let artifacts = test.artifacts.clone();
+            let badge = test.badge.clone().map(|path| (path, test.badge_message));
+            let reports = test.report.clone();
+            let save_baseline = test.save_baseline.clone();
+            // Loaded before the run, so a wrong path doesnt waste it.
+            let baseline = test
+                .baseline_file
+                .as_deref()
+                .map(SavedResults::load)
+                .transpose()?;
+            let mut configuration = format!("{} {}", test.check_context(), test.granularity());
+            if let Some(per_major) = test.per_major {
+                configuration = format!("{configuration} per-major {per_major}");
+            }
+            let hypothetical = test.bound.is_some();
+            let log_dir = std::path::absolute(test.log_dir())?;
+            let res = test_packages(state, test)?;
+            if let Some(dir) = &artifacts {
+                write_artifacts(dir, &res, &log_dir)?;
+            }
+            if let Some((path, message)) = &badge {
+                write_badge(path, *message, &res)?;
+            }
+            for report in &reports {
+                write_report(report, &res)?;
+            }
+            if let Some(path) = &save_baseline {
+                write_atomic(path, serde_json::to_string_pretty(&res.run_results())?)?;
+                out!(
+                    "Saved the results as a baseline to {}",
+                    path.display().blue()
+                );
+            }
+            // A hypothetical bound would throw off the trend and what `fix` tightens.
+            if !hypothetical {
+                RunRecord::append(configuration, &res)?;
+            }
+            if !res.results.is_empty() {
+                out!("{}", res.table(true).trim_end());
+                if let Some(note) = res.hypothetical_note() {
+                    out!("{}", note.yellow());
+                }
+                let slowest = res.slowest();
+                if slowest.len() > 1 {
+                    out!("Slowest checks:");
+                    for check in &slowest {
+                        out!(
+                            "  {} {} {}",
+                            check.dep.blue(),
+                            check.version.yellow(),
+                            format!("{:.1}s", check.duration_ms as f64 / 1000.0).bright_black()
+                        );
+                    }
+                }
+                out!(
+                    "{}",
+                    format!("Took {:.1}s", res.duration.as_secs_f32()).bright_black()
+                );
+            }
+            if res.extremes_only {
+                out!("{}", EXTREMES_ONLY_NOTE.bright_black());
+            }
+            if annotate {
+                res.print_annotations(&state.cargo_toml)?;
+            }
+            res.print_granularity();
+            res.print_failing_variants();
+            res.print_toolchains();
+            res.print_targets();
+            res.print_indeterminate();
+            res.print_retried();
+            if explore {
+                res.print_transitions();
+                Ok(())
+            } else if let Some(baseline) = baseline {
+                let diff = ResultsDiff::between(&baseline, &SavedResults::of(&res)?);
+                if res.failed_deps() != 0 {
+                    out!("{}", res.print());
+                }
+                diff.print();
+                diff.check()
+            } else if res.failed_deps() != 0 {
+                Err(anyhow!("{}", res.print()))
+            } else {
+                Ok(())
+            }
+        }
+        Cli::Minimize(config) => minimize(state, config),
+        Cli::Update(config) => update(state, config),
+        Cli::Bisect(config) => bisect(state, config),
+        Cli::ApplyFixes { .. }
+        | Cli::Fix(_)
+        | Cli::Watch(_)
+        | Cli::Trend(_)
+        | Cli::ExplainSkip(_)
+        | Cli::Matrix(_)
+        | Cli::Lint(_)
+        | Cli::CheckCrate(_)
+        | Cli::Restore
+        | Cli::Diff { .. }
+        | Cli::Doctor
+        | Cli::Completions { .. }
+        | Cli::Manpage => {
+            unreachable!("handled before the manifest is stored")
+        }
+    }
+}
+
+/// Print a GitHub Actions workflow command like `::error file=Cargo.toml,line=12::message`.
+fn annotate(level: &str, file: &str, line: &str, message: &str) {
+    let escape = |text: &str| {
+        text.replace('%', "%25")
+            .replace('\r', "%0D")
+            .replace('\n', "%0A")
+    };
+    let file = escape(file).replace(':', "%3A").replace(',', "%2C");
+    out!("::{level} file={file}{line}::{}", escape(message));
+}
+
+/// The manifest as GitHub knows it, relative to the checkout in `GITHUB_WORKSPACE`.
+fn manifest_for_annotations() -> Result<String> {
+    let manifest = std::env::current_dir()?.join("Cargo.toml");
+    let relative = std::env::var_os("GITHUB_WORKSPACE")
+        .and_then(|workspace| manifest.strip_prefix(workspace).ok().map(Path::to_owned))
+        .unwrap_or_else(|| PathBuf::from("Cargo.toml"));
+    Ok(relative.to_string_lossy().replace('\\', "/"))
+}
+
+/// The line of each dependency in `manifest`, the line of its version when it has one.
+///
+/// The deps are the ones the last [`declared_deps`] found.
+fn dep_lines(manifest: &str) -> HashMap<String, usize> {
+    let Ok(document) = toml_edit::ImDocument::parse(manifest) else {
+        return HashMap::new();
+    };
+    let declared = DECLARED_DEPS.lock().unwrap().clone();
+    declared
+        .into_iter()
+        .filter_map(|dep| {
+            let (name, parent) = dep.tables[0].split_last()?;
+            let mut deps = document.as_item();
+            for key in parent {
+                deps = deps.get(key)?;
+            }
+            let deps = deps.as_table_like()?;
+            let item = deps.get(name)?;
+            let version = match item.as_table_like() {
+                Some(table) => table.get("version").and_then(|version| version.span()),
+                None => item.span(),
+            };
+            let span = version.or_else(|| deps.key(name)?.span())?;
+            let line = manifest[..span.start].matches('\n').count() + 1;
+            Some((dep.name, line))
+        })
+        .collect()
+}
+
+/// Resolve the implied flags of `test` and warn about the ones that are ignored.
+fn prepare_test(test: &mut TestConfig) -> Result<()> {
+    if test.patch {
+        test.minor = true;
+    }
+    if test.keep_manifest {
+        KEEP_MANIFEST.store(true, Ordering::SeqCst);
+    }
+    if test.minimal_versions.is_some() {
+        require_nightly("--minimal-versions")?;
+    }
+    if let (Some(dep), Some(bound)) = (&test.dep, &test.bound) {
+        parse_bound(dep, bound)?;
+    }
+    announce_check(test);
+    Ok(())
+}
+
+/// Say how versions are checked, warning about the [`CheckArgs`] that dont apply.
+fn announce_check(config: &TestConfig) {
+    if config.check.command.is_some() && config.check.mode != Mode::Check {
+        out!(
+            "{} --command overrides --mode {}",
+            "warning:".yellow(),
+            config.check.mode.subcommand()
+        );
+    }
+    if config.check.command.is_some() && !config.all_features() {
+        out!(
+            "{} feature flags only apply to the built-in command, not --command",
+            "warning:".yellow()
+        );
+    } else if config.check.command.is_none() {
+        out!("Checking with {}", config.describe_features().yellow());
+    }
+}
+
+/// Returned when `watch` abandons a run because the sources changed.
+#[derive(Debug)]
+struct Cancelled;
+
+impl Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the run was cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// Rerun the versions that failed last time whenever the sources or the manifest change.
+///
+/// Deps without failures get the edges of their bound checked. A change during a run kills the
+/// running check and starts over, runs never overlap.
+fn watch(mut config: TestConfig) -> Result<()> {
+    prepare_test(&mut config)?;
+
+    let (events_tx, events) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(move |event| {
+        let _ = events_tx.send(event);
+    })?;
+    // The manifest is watched through its directory so editors replacing the file are noticed.
+    watcher.watch(Path::new("."), RecursiveMode::NonRecursive)?;
+    watcher.watch(Path::new("src"), RecursiveMode::Recursive)?;
+
+    let root = std::env::current_dir()?;
+    let (rerun_tx, reruns) = mpsc::channel();
+    thread::spawn(move || {
+        while let Ok(event) = events.recv() {
+            let (mut sources, manifest) = changed_files(&root, event);
+            if !sources && !manifest {
+                continue;
+            }
+            while let Ok(event) = events.recv_timeout(WATCH_DEBOUNCE) {
+                sources |= changed_files(&root, event).0;
+            }
+            // The pins of a run touch the manifest too, only an edited manifest counts.
+            if !sources && (WATCH_RUNNING.load(Ordering::SeqCst) || !manifest_edited()) {
+                continue;
+            }
+            if WATCH_RUNNING.load(Ordering::SeqCst) {
+                CANCELLED.store(true, Ordering::SeqCst);
+                for child in RUNNING_CHECKS.lock().unwrap().iter_mut() {
+                    stop_check(child);
+                }
+            }
+            if rerun_tx.send(()).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut failing = HashMap::new();
+    loop {
+        CANCELLED.store(false, Ordering::SeqCst);
+        WATCH_RUNNING.store(true, Ordering::SeqCst);
+        let res = {
+            let state = State::store()?;
+            *KNOWLEDGE.lock().unwrap() = Some(Knowledge::load(&state)?);
+            let mut run = config.clone();
+            run.focus = Some(failing.clone());
+            let res = sanity_test(&state, run);
+            finish_dep_bar();
+            // Stopped to look into a failure, so the manifest stays pinned for it.
+            if res.is_err() && run_keeps_manifest() {
+                return res.map(drop);
+            }
+            state.restore()?;
+            res
+        };
+        WATCH_RUNNING.store(false, Ordering::SeqCst);
+
+        match res {
+            Ok(summary) => {
+                failing = summary.failing();
+                summary.print_toolchains();
+                summary.print_targets();
+                summary.print_indeterminate();
+                summary.print_retried();
+                if summary.failed_deps() == 0 {
+                    out!("{}", "All bounds hold".green());
+                } else {
+                    out!("{}", summary.print());
+                }
+            }
+            Err(err) if err.is::<Cancelled>() => {
+                out!("{}", "Change detected, starting over".bright_black());
+            }
+            Err(err) => out!("{} {err:#}", "error:".red()),
+        }
+        out!("{}", "Waiting for changes…".bright_black());
+
+        reruns.recv()?;
+        while reruns.try_recv().is_ok() {}
+    }
+}
+
+/// Whether a file event touched the sources and whether it touched the manifest.
+fn changed_files(root: &Path, event: notify::Result<notify::Event>) -> (bool, bool) {
+    let Ok(event) = event else {
+        return (false, false);
+    };
+    if event.kind.is_access() {
+        return (false, false);
+    }
+    let mut sources = false;
+    let mut manifest = false;
+    for path in &event.paths {
+        let path = path.strip_prefix(root).unwrap_or(path);
+        let mut parts = path
+            .components()
+            .filter(|part| *part != std::path::Component::CurDir);
+        match parts.next().map(|part| part.as_os_str()) {
+            Some(first) if first == "src" => sources = true,
+            Some(first) if first == "Cargo.toml" => manifest = true,
+            _ => {}
+        }
+    }
+    (sources, manifest)
+}
+
+/// Whether the manifest differs from the one the last run started from.
+fn manifest_edited() -> bool {
+    let snapshot = RESTORE_ON_EXIT
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|state| state.cargo_toml.clone());
+    fs::read_to_string("Cargo.toml").ok().as_deref() != snapshot.as_deref()
+}
+
+fn minimize(state: &State, config: MinimizeConfig) -> Result<()> {
+    let cargo_toml = state.cargo_toml.parse::<DocumentMut>()?;
+    let deps = declared_deps(&cargo_toml)?;
+    if deps.is_empty() {
+        out!("{}", "No dependencies".bright_red());
+        return Ok(());
+    }
+
+    let metadata = Metadata::load(&cargo_toml)?;
+    let mut check = config.search.test_config();
+    check.add_metadata_env(&metadata);
+    announce_check(&check);
+    let deps = select_deps(deps, config.dep.as_deref())?;
+    if config.verify_minimal {
+        require_nightly("--verify-minimal")?;
+    }
+    prefetch_versions(&cargo_toml, &deps);
+    baseline_check(&check)?;
+
+    let mut fixes = Vec::new();
+    start_run_bar(deps.len());
+    if config.jobs > 1 && deps.len() > 1 {
+        fixes = minimize_parallel(&deps, &config, &check, &metadata)
+            .inspect_err(|_| finish_run_bar())?;
+    } else {
+        for declared in &deps {
+            let dep = declared.name.as_str();
+            run_bar_next_dep(dep);
+            let check = check.for_dep(dep, &metadata);
+            let fix = minimize_dep(&state.cargo_toml, declared, &config, &check)
+                .inspect_err(|_| finish_run_bar())?;
+            settle_run_bar();
+            fixes.extend(fix);
+            print_reused(dep);
+        }
+    }
+    finish_run_bar();
+    if config.dep.is_none() && !config.skip_combined && fixes.len() > 1 {
+        verify_combined(state, &fixes, &check)?;
+    }
+
+    if let Some(output) = &config.output {
+        match output.format {
+            OutputFormat::FixesJson => {
+                let fixes = Fixes { fixes };
+                write_atomic(&output.path, serde_json::to_string_pretty(&fixes)?)?;
+            }
+        }
+    }
+    check_no_match()?;
+    let unverified = UNVERIFIED_DEPS.lock().unwrap();
+    match unverified.as_slice() {
+        [] => Ok(()),
+        deps => Err(anyhow!(
+            "No requirement suggested for {}, not every 0.x series in the range was verified",
+            deps.join(", ")
+        )),
+    }
+}
+
+/// Fail when the bound of a dep matched no published version, see [`NO_MATCH_DEPS`].
+fn check_no_match() -> Result<()> {
+    let deps = NO_MATCH_DEPS.lock().unwrap();
+    match deps.as_slice() {
+        [] => Ok(()),
+        [dep] => Err(anyhow!("The bound of {dep} matches no published version")),
+        deps => Err(anyhow!(
+            "The bounds of {} match no published version",
+            deps.join(", ")
+        )),
+    }
+}
+
+/// Raise the upper end of each bound as far as it still builds, see [`update_dep`].
+fn update(state: &State, config: UpdateConfig) -> Result<()> {
+    let cargo_toml = state.cargo_toml.parse::<DocumentMut>()?;
+    let deps = declared_deps(&cargo_toml)?;
+    if deps.is_empty() {
+        out!("{}", "No dependencies".bright_red());
+        return Ok(());
+    }
+
+    let metadata = Metadata::load(&cargo_toml)?;
+    let mut check = config.search.test_config();
+    check.add_metadata_env(&metadata);
+    announce_check(&check);
+    let deps = select_deps(deps, config.dep.as_deref())?;
+    prefetch_versions(&cargo_toml, &deps);
+    baseline_check(&check)?;
+
+    let mut fixes = Vec::new();
+    start_run_bar(deps.len());
+    for declared in &deps {
+        let dep = declared.name.as_str();
+        run_bar_next_dep(dep);
+        let check = check.for_dep(dep, &metadata);
+        let fix = update_dep(state, declared, &check).inspect_err(|_| finish_run_bar())?;
+        settle_run_bar();
+        fixes.extend(fix);
+        print_reused(dep);
+    }
+    finish_run_bar();
+
+    if fixes.is_empty() {
+        if NO_MATCH_DEPS.lock().unwrap().is_empty() {
+            out!("{}", "No bound can be raised".green());
+        }
+    } else if config.apply {
+        *PENDING_FIXES.lock().unwrap() = fixes;
+    } else {
+        out!(
+            "{}",
+            "Run with --apply to write the raised bounds to the manifest".bright_black()
+        );
+    }
+    check_no_match()
+}
+
+/// Search the versions above the bound of `dep` for the newest one that still builds.
+///
+/// Returns the edit raising the upper end of the requirement, `None` when it cant be raised.
+fn update_dep(state: &State, declared: &DeclaredDep, config: &TestConfig) -> Result<Option<Fix>> {
+    let dep = declared.name.as_str();
+    if declared.inherited {
+        print_inherited(dep);
+        return Ok(None);
+    }
+    if print_patched(dep) {
+        return Ok(None);
+    }
+    let mut cargo_toml = state.cargo_toml.parse::<DocumentMut>()?;
+    let Some((old_req, toml_path)) = prepare_declarations(&mut cargo_toml, declared)? else {
+        out!("{} {}", "No version info found for".yellow(), dep.green());
+        return Ok(None);
+    };
+    let bound = parse_bound(dep, &old_req)?;
+
+    out!("{} - {}", dep.blue(), bound.yellow());
+    let Some(published) = published_versions(&cargo_toml, dep)? else {
+        return Ok(None);
+    };
+    let toolchain = config.rust_version.toolchain();
+    let mut versions = published
+        .iter()
+        .filter(|release| !release.yanked && release.version.pre.is_empty())
+        .filter(|release| {
+            toolchain.is_none_or(|toolchain| release.needs_newer_rust(toolchain).is_none())
+        })
+        .map(|release| release.version.clone())
+        .collect::<Vec<_>>();
+    versions.sort();
+
+    let Some(current_max) = versions.iter().rfind(|version| bound.matches(version)) else {
+        print_no_match(dep, &bound, &published, toolchain);
+        NO_MATCH_DEPS.lock().unwrap().push(dep.to_owned());
+        return Ok(None);
+    };
+    let above = versions
+        .iter()
+        .filter(|version| *version > current_max)
+        .count();
+    if above == 0 {
+        out!(
+            "  {} {}",
+            "Already allows the newest release".green(),
+            current_max.green()
+        );
+        return Ok(None);
+    }
+    let current_max = current_max.clone();
+    // A binary search from the current max to the newest release.
+    grow_run_bar((above + 1).ilog2() as usize + 2);
+
+    let mut failing = Vec::new();
+    let found = raise_bound(
+        &old_req,
+        &versions,
+        |version| test_version(&mut cargo_toml, dep, version.clone(), config),
+        &mut MinimizeProgress::new(dep, &mut failing),
+    )?
+    .expect("the bound matches a version");
+    if failing.contains(&current_max) {
+        out!(
+            "  {} {} fails already, fix the current bound first",
+            "error:".red(),
+            current_max.red()
+        );
+        return Ok(None);
+    }
+    if found.max == current_max {
+        out!("  {}", "No newer version builds".yellow());
+        return Ok(None);
+    }
+    out!(
+        "  Raising {} to {}",
+        old_req.yellow(),
+        found.requirement.green()
+    );
+    failing.sort();
+    failing.dedup();
+    Ok(Some(Fix {
+        dep: dep.to_owned(),
+        manifest: std::env::current_dir()?.join("Cargo.toml"),
+        toml_path,
+        old: old_req,
+        new: found.requirement,
+        evidence: Evidence {
+            verified_min: found.min,
+            verified_max: found.max,
+            failing,
+            minimal_versions_min: None,
+        },
+    }))
+}
+
+/// Tighten each requirement to leave out the versions that failed, see [`tighten_requirement`].
+///
+/// The results come from the last recorded `test` run, or from a saved one with `--from`.
+fn fix(config: &FixConfig) -> Result<()> {
+    let cargo_toml = fs::read_to_string("Cargo.toml")?.parse::<DocumentMut>()?;
+    let results = match &config.from {
+        Some(path) => SavedResults::load(path)?
+            .deps
+            .into_iter()
+            .map(|saved| {
+                let with = |result| {
+                    saved
+                        .versions
+                        .iter()
+                        .filter(|tested| tested.result == result)
+                        .map(|tested| tested.version.clone())
+                        .collect::<Vec<_>>()
+                };
+                DepRecord {
+                    passing: with(TestResult::Sucess),
+                    failing: with(TestResult::Fail),
+                    dep: saved.dep,
+                    package: saved.package,
+                }
+            })
+            .collect::<Vec<_>>(),
+        None => {
+            let history = fs::read_to_string(paths().history()).unwrap_or_default();
+            let latest = history
+                .lines()
+                .rev()
+                .find_map(|line| serde_json::from_str::<RunRecord>(line).ok())
+                .ok_or(anyhow!(
+                    "No runs recorded yet, run `cargo bounds test` first"
+                ))?;
+            latest.deps
+        }
+    };
+
+    let deps = select_deps(declared_deps(&cargo_toml)?, config.dep.as_deref())?;
+    let mut fixes = Vec::new();
+    for declared in &deps {
+        let dep = declared.name.as_str();
+        // The other members have their own manifests.
+        let Some(record) = results.iter().find(|record| {
+            record.dep == dep && record.package.is_none() && !record.failing.is_empty()
+        }) else {
+            continue;
+        };
+        if declared.inherited {
+            print_inherited(dep);
+            continue;
+        }
+        let mut document = cargo_toml.clone();
+        let Some((old_req, toml_path)) = prepare_declarations(&mut document, declared)? else {
+            continue;
+        };
+        let mut versions = fetch_published(&cargo_toml, dep)?
+            .into_iter()
+            .filter(|release| !release.yanked && release.version.pre.is_empty())
+            .map(|release| release.version)
+            .collect::<Vec<_>>();
+        versions.sort();
+        let Some(Tightened {
+            requirement,
+            min,
+            max,
+            holes,
+        }) = tighten_requirement(&old_req, &record.passing, &record.failing, &versions)
+        else {
+            continue;
+        };
+
+        out!("{} - {}", dep.blue(), old_req.yellow());
+        if !holes.is_empty() {
+            let (fail, them, own) = match holes.len() {
+                1 => ("fails", "it", "its"),
+                _ => ("fail", "them", "their"),
+            };
+            let holes = holes.iter().map(ToString::to_string).collect::<Vec<_>>();
+            out!(
+                "  {} {} {fail} between passing versions, a requirement cant leave {them} out on {own} own, so it only keeps the passing versions above {them}",
+                "note:".bright_black(),
+                holes.join(", ").red()
+            );
+        }
+        let failing = record
+            .failing
+            .iter()
+            .filter(|version| !(min <= **version && **version <= max))
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        out!(
+            "  Tightening {} to {}, leaving out {}",
+            old_req.yellow(),
+            requirement.green(),
+            failing.join(", ").red()
+        );
+        let kept = record
+            .passing
+            .iter()
+            .filter(|version| min <= **version && **version <= max);
+        fixes.push(Fix {
+            dep: dep.to_owned(),
+            manifest: std::env::current_dir()?.join("Cargo.toml"),
+            toml_path,
+            old: old_req,
+            new: requirement,
+            evidence: Evidence {
+                verified_min: kept.clone().min().unwrap_or(&min).clone(),
+                verified_max: kept.max().unwrap_or(&max).clone(),
+                failing: record.failing.clone(),
+                minimal_versions_min: None,
+            },
+        });
+    }
+
+    if fixes.is_empty() {
+        out!("{}", "No requirement allows a failing version".green());
+    } else if config.apply {
+        apply_all(fixes)?;
+    } else {
+        out!(
+            "{}",
+            "Run with --apply to write the tightened requirements to the manifest".bright_black()
+        );
+    }
+    Ok(())
+}
+
+/// Download `name` from crates.io and copy it somewhere it can be tested, returning where.
+///
+/// Cargo does the download, by resolving a throwaway project that depends on it. The copy is
+/// kept in the temp directory, so checking the same version again reuses its builds.
+fn fetch_crate(name: &str, version: Option<&semver::Version>) -> Result<PathBuf> {
+    let root = std::env::temp_dir().join("cargo-bounds-check-crate");
+    let consumer = root.join(format!("consumer-{name}"));
+    fs::create_dir_all(consumer.join("src"))?;
+    let requirement = version.map_or("*".to_owned(), |version| format!("={version}"));
+    fs::write(
+        consumer.join("Cargo.toml"),
+        format!(
+            "[package]\nname = \"cargo-bounds-consumer\"\nversion = \"0.0.0\"\nedition = \"2021\"\npublish = false\n\n[dependencies]\n{name} = \"{requirement}\"\n\n[workspace]\n"
+        ),
+    )?;
+    fs::write(consumer.join("src/lib.rs"), "")?;
+    // A lockfile from an earlier fetch would hold on to an older newest version.
+    let _ = fs::remove_file(consumer.join("Cargo.lock"));
+
+    let spinner = Spinner::new(format!("Fetching {}", name.blue()));
+    let output = Command::new("cargo")
+        .args(["metadata", "--format-version", "1", "--manifest-path"])
+        .arg(consumer.join("Cargo.toml"))
+        .output()?;
+    spinner.finish_and_clear();
+    if !output.status.success() {
+        return Err(anyhow!(
+            "Couldnt fetch {name} {requirement} from crates.io: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    let metadata = serde_json::from_slice::<CargoMetadata>(&output.stdout)?;
+    let package = metadata
+        .packages
+        .into_iter()
+        .find(|package| package.name == name)
+        .ok_or(anyhow!("cargo didnt download {name}"))?;
+    let source = package
+        .manifest_path
+        .parent()
+        .ok_or(anyhow!("{name} has no source directory"))?;
+
+    let dir = root.join(format!("{name}-{}", package.version));
+    if !dir.join("Cargo.toml").exists() {
+        copy_dir(source, &dir)?;
+        // Keep it out of any workspace the temp directory happens to be in.
+        let manifest = dir.join("Cargo.toml");
+        let mut cargo_toml = fs::read_to_string(&manifest)?.parse::<DocumentMut>()?;
+        if !cargo_toml.contains_key("workspace") {
+            cargo_toml.insert("workspace", toml_edit::table());
+            write_atomic(&manifest, cargo_toml.to_string())?;
+        }
+    }
+    out!(
+        "Checking {} {} in {}",
+        name.blue(),
+        package.version.yellow(),
+        dir.display().bright_black()
+    );
+    Ok(dir)
+}
+
+/// Minimize `deps` with `--jobs` threads, each checking in its own [`Worker`] copy of the project.
+///
+/// What each dependency prints is held back until it is done and then shown in manifest order,
+/// only the progress lines of the running searches are live.
+fn minimize_parallel(
+    deps: &[DeclaredDep],
+    options: &MinimizeConfig,
+    check: &TestConfig,
+    metadata: &Metadata,
+) -> Result<Vec<Fix>> {
+    let jobs = usize::from(options.jobs).min(deps.len());
+    let spinner = Spinner::new(format!("Copying the project for {jobs} jobs"));
+    let workers = (0..jobs).map(Worker::create).collect::<Result<Vec<_>>>()?;
+    spinner.finish_and_clear();
+
+    let next = AtomicUsize::new(0);
+    let (done_tx, done) = mpsc::channel();
+    thread::scope(|scope| {
+        for worker in workers {
+            let done_tx = done_tx.clone();
+            let next = &next;
+            scope.spawn(move || {
+                let manifest = fs::read_to_string(worker.dir.join("Cargo.toml"));
+                let config = TestConfig {
+                    worker: Some(worker),
+                    ..check.clone()
+                };
+                CAPTURED.set(Some(Vec::new()));
+                loop {
+                    let index = next.fetch_add(1, Ordering::SeqCst);
+                    let Some(declared) = deps.get(index) else {
+                        break;
+                    };
+                    if CANCELLED.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    let dep = declared.name.as_str();
+                    run_bar_next_dep(dep);
+                    let fix = match &manifest {
+                        Ok(manifest) => minimize_dep(
+                            manifest,
+                            declared,
+                            options,
+                            &config.for_dep(dep, metadata),
+                        ),
+                        Err(err) => Err(anyhow!("Couldnt read the manifest of the copy: {err}")),
+                    };
+                    if fix.is_err() {
+                        // The run fails anyway, so the other jobs dont need to finish.
+                        CANCELLED.store(true, Ordering::SeqCst);
+                        for child in RUNNING_CHECKS.lock().unwrap().iter_mut() {
+                            stop_check(child);
+                        }
+                    }
+                    print_reused(dep);
+                    let lines = CAPTURED.replace(Some(Vec::new())).unwrap_or_default();
+                    if done_tx.send((index, fix, lines)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(done_tx);
+
+        let mut finished = BTreeMap::new();
+        let mut fixes = Vec::new();
+        let mut error: Option<anyhow::Error> = None;
+        let mut shown = 0;
+        for (index, fix, lines) in done {
+            finished.insert(index, (fix, lines));
+            // Everything before it has to be shown first, a job stopped early leaves a gap.
+            while let Some((fix, lines)) = finished.remove(&shown) {
+                shown += 1;
+                for (stream, line) in lines {
+                    print_to(stream, line);
+                }
+                match fix {
+                    Ok(fix) => fixes.extend(fix),
+                    // A job stopped because of another one's error says less than that error.
+                    Err(err) if error.as_ref().is_none_or(|error| error.is::<Cancelled>()) => {
+                        error = Some(err);
+                    }
+                    Err(_) => {}
+                }
+            }
+        }
+        for (fix, lines) in finished.into_values() {
+            for (stream, line) in lines {
+                print_to(stream, line);
+            }
+            if let Err(err) = fix
+                && error.as_ref().is_none_or(|error| error.is::<Cancelled>())
+            {
+                error = Some(err);
+            }
+        }
+        match error {
+            Some(error) => Err(error),
+            None => Ok(fixes),
+        }
+    })
+}
+
+/// Copy the sources of the workspace `root` in `from` to `to`, without what was built, the git
+/// history or the directories in `skip`.
+///
+/// Relative `path`s that point outside of the workspace are made absolute in the manifests, so
+/// they still resolve from the copy.
+fn copy_project(from: &Path, to: &Path, root: &Path, skip: &[PathBuf]) -> Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let path = entry.path();
+        let target = to.join(entry.file_name());
+        if path.is_dir() {
+            if entry.file_name() == "target" || entry.file_name() == ".git" || skip.contains(&path)
+            {
+                continue;
+            }
+            copy_project(&path, &target, root, skip)?;
+        } else if entry.file_name() == "Cargo.toml"
+            && let Ok(mut manifest) = fs::read_to_string(&path)?.parse::<DocumentMut>()
+        {
+            absolutize_paths(manifest.as_table_mut(), from, root);
+            fs::write(&target, manifest.to_string())?;
+        } else {
+            fs::copy(&path, &target)?;
+        }
+    }
+    Ok(())
+}
+
+/// Make every `path` in `table` that leaves the workspace `root` absolute, `dir` being where the
+/// manifest is.
+fn absolutize_paths(table: &mut dyn toml_edit::TableLike, dir: &Path, root: &Path) {
+    for (key, item) in table.iter_mut() {
+        if key.get() == "path"
+            && let Some(path) = item.as_str()
+        {
+            let mut resolved = PathBuf::new();
+            for component in dir.join(path).components() {
+                match component {
+                    std::path::Component::ParentDir => {
+                        resolved.pop();
+                    }
+                    std::path::Component::CurDir => {}
+                    component => resolved.push(component),
+                }
+            }
+            if !resolved.starts_with(root) {
+                *item = toml_edit::value(resolved.to_string_lossy().as_ref());
+            }
+        } else if let Some(table) = item.as_table_like_mut() {
+            absolutize_paths(table, dir, root);
+        } else if let Some(tables) = item.as_array_of_tables_mut() {
+            for table in tables.iter_mut() {
+                absolutize_paths(table, dir, root);
+            }
+        }
+    }
+}
+
+fn copy_dir(from: &Path, to: &Path) -> Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+/// Binary search the published versions between `--good` and `--bad` for where the result flips.
+///
+/// Both ends are checked first, so a wrong guess about them doesnt send the search astray.
+fn bisect(state: &State, mut config: BisectConfig) -> Result<()> {
+    prepare_test(&mut config.test)?;
+    let dep = config
+        .test
+        .dep
+        .clone()
+        .ok_or(anyhow!("bisect needs the dependency to search, pass --dep"))?;
+    let mut cargo_toml = state.cargo_toml.parse::<DocumentMut>()?;
+    let metadata = Metadata::load(&cargo_toml)?;
+    config.test.add_metadata_env(&metadata);
+    let declared = select_deps(declared_deps(&cargo_toml)?, Some(&dep))?;
+    if declared[0].inherited {
+        return Err(anyhow!(
+            "{dep} inherits its requirement from the workspace, it cant be pinned from here"
+        ));
+    }
+    prepare_declarations(&mut cargo_toml, &declared[0])?;
+    let test = config.test.for_dep(&dep, &metadata);
+
+    let Some(published) = published_versions(&cargo_toml, &dep)? else {
+        return Ok(());
+    };
+    let (good, bad) = (&config.good, &config.bad);
+    if good == bad {
+        return Err(anyhow!("--good and --bad are the same version"));
+    }
+    let (oldest, newest) = (good.min(bad), good.max(bad));
+    for end in [good, bad] {
+        if !published.iter().any(|release| release.version == *end) {
+            return Err(anyhow!("{dep} {end} isnt published"));
+        }
+    }
+    let mut versions = published
+        .iter()
+        .map(|release| &release.version)
+        .filter(|version| oldest <= *version && *version <= newest)
+        .filter(|version| {
+            let release = published
+                .iter()
+                .find(|release| release.version == **version);
+            // The ends were picked on purpose, so they stay even when yanked or a prerelease.
+            *version == good
+                || *version == bad
+                || release.is_some_and(|release| !release.yanked && version.pre.is_empty())
+        })
+        .cloned()
+        .collect::<Vec<_>>();
+    versions.sort();
+    versions.dedup();
+
+    let good_index = versions.iter().position(|version| version == good).unwrap();
+    let bad_index = versions.iter().position(|version| version == bad).unwrap();
+    out!(
+        "{} - bisecting {} versions between {} and {}",
+        dep.blue(),
+        versions.len(),
+        good.green(),
+        bad.red()
+    );
+    start_run_bar(1);
+    grow_run_bar(versions.len().ilog2() as usize + 2);
+    let mut check = |version: &semver::Version| -> Result<TestResult> {
+        let res = test_version(&mut cargo_toml, &dep, version.clone(), &test)?.or_fail();
+        advance_run_bar();
+        Ok(res)
+    };
+    let res = (|| {
+        if check(good)? != TestResult::Sucess {
+            return Err(anyhow!("{dep} {good} was given as --good but fails"));
+        }
+        if check(bad)? != TestResult::Fail {
+            return Err(anyhow!("{dep} {bad} was given as --bad but works"));
+        }
+        // `good` stays on a working and `bad` on a failing version.
+        let (mut good, mut bad) = (good_index, bad_index);
+        while good.abs_diff(bad) > 1 {
+            let middle = (good + bad) / 2;
+            match check(&versions[middle])? {
+                TestResult::Sucess => good = middle,
+                TestResult::Fail | TestResult::Indeterminate => bad = middle,
+            }
+        }
+        Ok((versions[good].clone(), versions[bad].clone()))
+    })();
+    finish_run_bar();
+    let (last_good, first_bad) = res?;
+
+    let link = |version: &semver::Version| format!("https://crates.io/crates/{dep}/{version}");
+    if good < bad {
+        out!(
+            "{} broke in {}, {} is the last version that works",
+            dep.blue(),
+            first_bad.red(),
+            last_good.green()
+        );
+        out!("  {}", link(&first_bad).bright_black());
+    } else {
+        out!(
+            "{} was fixed in {}, {} is the last version that fails",
+            dep.blue(),
+            last_good.green(),
+            first_bad.red()
+        );
+        out!("  {}", link(&last_good).bright_black());
+    }
+    Ok(())
+}
+
+/// Check the found bounds of all deps together, first all at their maximum then all at their
+/// minimum.
+///
+/// Each dep was minimized with the others at their current bound, so their minimums can still
+/// conflict through shared dependencies. If they do, bisect for the dep whose minimum has to go up.
+fn verify_combined(state: &State, fixes: &[Fix], config: &TestConfig) -> Result<()> {
+    out!("Checking the found bounds together");
+    let maximums = fixes
+        .iter()
+        .map(|fix| &fix.evidence.verified_max)
+        .collect::<Vec<_>>();
+    let minimums = fixes
+        .iter()
+        .map(|fix| &fix.evidence.verified_min)
+        .collect::<Vec<_>>();
+
+    let maximums_pass = check_pinned(state, fixes, &maximums, "all at their maximum", config)?
+        == TestResult::Sucess;
+    if !maximums_pass {
+        out!("  {}", "The maximums dont build together".red());
+    }
+    if check_pinned(state, fixes, &minimums, "all at their minimum", config)? == TestResult::Sucess
+    {
+        return Ok(());
+    }
+    if !maximums_pass {
+        out!("  {}", "The minimums dont build together either".red());
+        return Ok(());
+    }
+
+    // With the first `low` deps at their minimum it builds, with the first `high` it doesnt.
+    let (mut low, mut high) = (0, fixes.len());
+    while high - low > 1 {
+        let mid = (low + high) / 2;
+        let pins = minimums[..mid]
+            .iter()
+            .chain(&maximums[mid..])
+            .copied()
+            .collect::<Vec<_>>();
+        let label = format!("first {mid} at their minimum");
+        match check_pinned(state, fixes, &pins, &label, config)? {
+            TestResult::Sucess => low = mid,
+            TestResult::Fail | TestResult::Indeterminate => high = mid,
+        }
+    }
+    let culprit = &fixes[high - 1];
+    out!(
+        "  {} {} {} {}",
+        "The minimum of".red(),
+        culprit.dep.blue(),
+        culprit.evidence.verified_min.yellow(),
+        "doesnt build with the minimums of the deps before it, it has to be raised".red()
+    );
+    Ok(())
+}
+
+/// Pin every dep of `fixes` to its version in `versions` at once and run the check.
+fn check_pinned(
+    state: &State,
+    fixes: &[Fix],
+    versions: &[&semver::Version],
+    label: &str,
+    config: &TestConfig,
+) -> Result<TestResult> {
+    let mut cargo_toml = state.cargo_toml.parse::<DocumentMut>()?;
+    for (fix, version) in fixes.iter().zip(versions) {
+        let mut item = cargo_toml.as_item_mut();
+        for key in &fix.toml_path {
+            item = &mut item[key.as_str()];
+        }
+        *item = toml_edit::value(format!("={version}"));
+    }
+    write_pinned(&cargo_toml, config.project_dir())?;
+
+    let command = check_command(config, None)?;
+    let log_name = format!("combined-{}.log", label.replace(' ', "-"));
+    let msg = label.blue().to_string();
+    Ok(run_check(msg.clone(), &msg, &log_name, command, config)?.result)
+}
+
+/// Check the project with its manifest untouched, so a project that doesnt build isnt blamed on
+/// every version.
+///
+/// This also builds the deps that stay the same between versions, which the checks after it reuse.
+fn baseline_check(config: &TestConfig) -> Result<()> {
+    if config.skip_baseline {
+        return Ok(());
+    }
+    if config
+        .check
+        .command
+        .as_ref()
+        .is_some_and(|command| command.contains("{dep}") || command.contains("{version}"))
+    {
+        out!(
+            "{}",
+            "The command depends on the version being checked, skipping the baseline"
+                .bright_black()
+        );
+        return Ok(());
+    }
+    for config in config.per_toolchain_and_target() {
+        let mut msg = format!("{} with the current dependencies", "baseline".blue());
+        let mut log_name = "baseline".to_owned();
+        if let Some(toolchain) = &config.on_toolchain {
+            msg = format!("{msg} {}", format!("[+{toolchain}]").bright_black());
+            log_name = format!("{log_name}-{toolchain}");
+        }
+        if let Some(target) = &config.on_target {
+            msg = format!("{msg} {}", format!("[{target}]").bright_black());
+            log_name = format!("{log_name}-{target}");
+        }
+        let mut on = config
+            .active_toolchain()
+            .map(|toolchain| format!(" on +{toolchain}"))
+            .unwrap_or_default();
+        if let Some(target) = config.active_target() {
+            on = format!("{on} for {target}");
+        }
+        let log_name = format!("{log_name}.log");
+        let command = check_command(&config, None)?;
+        let check = run_check(
+            msg,
+            &"baseline".blue().to_string(),
+            &log_name,
+            command,
+            &config,
+        )?;
+        if check.result != TestResult::Sucess && CHECKING_PUBLISHED.load(Ordering::Relaxed) {
+            return Err(anyhow!(
+                "the published crate doesnt build here with its current dependencies{on}. It may need features, a target or a toolchain that cant be known from its manifest, try --features, --no-all-features, --target or --toolchain. The output is in {}",
+                config.log_dir().join(log_name).display()
+            ));
+        }
+        if check.result != TestResult::Sucess {
+            return Err(anyhow!(
+                "the project doesnt build with its current dependencies{on}, fix that first (or pass --skip-baseline). The output is in {}",
+                config.log_dir().join(log_name).display()
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Run [`sanity_test`] for each `--package`, in its own directory, or for the current one.
+///
+/// Deps shared through `[workspace.dependencies]` are only tested with the first package that
+/// uses them.
+fn test_packages(state: &State, mut config: TestConfig) -> Result<TestSummary> {
+    if config.package.is_empty() {
+        return sanity_test(state, config);
+    }
+    let members = workspace_members()?;
+    let mut selected = Vec::new();
+    for package in &config.package {
+        let Some((_, manifest)) = members.iter().find(|(name, _)| name == package) else {
+            let names = members.iter().map(|(name, _)| name.as_str());
+            let hint = match closest(package, names.clone()) {
+                Some(name) => format!(", did you mean `{name}`?"),
+                None => format!(", the members are {}", names.collect::<Vec<_>>().join(", ")),
+            };
+            return Err(anyhow!("`{package}` isnt a member of the workspace{hint}"));
+        };
+        let dir = manifest
+            .parent()
+            .ok_or(anyhow!("{} has no directory", manifest.display()))?;
+        selected.push((package.clone(), dir.to_owned()));
+    }
+
+    config.make_paths_absolute()?;
+    let root = std::env::current_dir()?;
+    let log_dir = std::path::absolute(config.log_dir())?;
+    let start = Instant::now();
+    let mut summary = TestSummary::default();
+    for (package, dir) in selected {
+        out!("{} {}", "Package".bright_black(), package.blue());
+        std::env::set_current_dir(&dir)?;
+        let member = State::store()?;
+        // The logs of each member get a directory of their own, since they have the same names.
+        let mut member_config = config.clone();
+        member_config.log_dir = Some(log_dir.join(&package));
+        let res = sanity_test(&member, member_config);
+        if res.is_err() && run_keeps_manifest() {
+            std::env::set_current_dir(&root)?;
+            return res.map(|_| summary);
+        }
+        member.restore()?;
+        state.restore_on_exit()?;
+        std::env::set_current_dir(&root)?;
+        let res = res?;
+        for declared in DECLARED_DEPS.lock().unwrap().iter() {
+            if declared.inherited {
+                config
+                    .tested_shared
+                    .entry(declared.name.clone())
+                    .or_insert_with(|| package.clone());
+            }
+        }
+        summary.merge(res, &package);
+    }
+    summary.duration = start.elapsed();
+    Ok(summary)
+}
+
+/// The packages of the workspace, with their manifest.
+fn workspace_members() -> Result<Vec<(String, PathBuf)>> {
+    let output = Command::new("cargo")
+        .args(["metadata", "--no-deps", "--format-version", "1"])
+        .output()?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "`cargo metadata` failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    let metadata = serde_json::from_slice::<CargoMetadata>(&output.stdout)?;
+    Ok(metadata
+        .packages
+        .into_iter()
+        .map(|package| (package.name, package.manifest_path))
+        .collect())
+}
+
+/// The name closest to `typo`, when one is close enough to be what was meant.
+fn closest<'a>(typo: &str, names: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    names
+        .map(|name| (edit_distance(typo, name), name))
+        .filter(|(distance, name)| *distance <= (name.len() / 3).max(1))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, name)| name)
+}
+
+/// How many characters have to be inserted, removed or replaced to turn `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b) in b.iter().enumerate() {
+            let replace = previous[j] + usize::from(a != *b);
+            current.push(replace.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+fn sanity_test(state: &State, mut config: TestConfig) -> Result<TestSummary> {
+    let cargo_toml = state.cargo_toml.parse::<DocumentMut>()?;
+    let deps = declared_deps(&cargo_toml)?;
+    if deps.is_empty() {
+        out!("{}", "No dependencies".bright_red());
+        return Ok(TestSummary::default());
+    }
+
+    let metadata = Metadata::load(&cargo_toml)?;
+    config.add_metadata_env(&metadata);
+
+    let mut summary = TestSummary {
+        extremes_only: config.extremes_only,
+        configuration: RunConfiguration::of(&config)?,
+        toolchains: match config.toolchain.len() {
+            0 | 1 => Vec::new(),
+            _ => config.toolchain.clone(),
+        },
+        targets: match config.target.len() {
+            0 | 1 => Vec::new(),
+            _ => config.target.clone(),
+        },
+        ..TestSummary::default()
+    };
+    let mut deps = select_deps(deps, config.dep.as_deref())?;
+    deps.retain(|dep| {
+        let Some(package) = config
+            .tested_shared
+            .get(&dep.name)
+            .filter(|_| dep.inherited)
+        else {
+            return true;
+        };
+        out!(
+            "{} {} {}",
+            "Skipping".bright_black(),
+            dep.name.green(),
+            format!("it comes from the workspace and was tested with {package}").bright_black()
+        );
+        false
+    });
+    if let Some(git_ref) = &config.since_ref {
+        let changed = changed_since(git_ref, &cargo_toml, &deps)?;
+        deps.retain(|dep| changed.contains(&dep.name));
+        if deps.is_empty() {
+            out!(
+                "No dependency was added or changed since {}",
+                git_ref.yellow()
+            );
+            return Ok(summary);
+        }
+        out!(
+            "Testing the {} deps that changed since {}",
+            deps.len(),
+            git_ref.yellow()
+        );
+    }
+    let run_start = Instant::now();
+    prefetch_versions(&cargo_toml, &deps);
+    let planned = plan_versions(&cargo_toml, &deps, &config, &metadata);
+    print_plan(&planned, &config);
+    baseline_check(&config)?;
+    start_run_bar(deps.len());
+    plan_run_bar(planned);
+    for declared in &deps {
+        let dep = declared.name.as_str();
+        run_bar_next_dep(dep);
+        if config.fail_fast == Some(FailFast::All) && summary.failed_deps() != 0 {
+            summary.add_unreached(dep, declared.requirement(&cargo_toml));
+            continue;
+        }
+        let start = Instant::now();
+        let mut outcome = sanity_test_dep(state, declared, &config, &metadata)
+            .inspect_err(|_| finish_run_bar())?;
+        outcome.duration = start.elapsed();
+        if config.strict_outdated && outcome.outdated.is_some() {
+            outcome.fails += 1;
+        }
+        finish_dep_bar();
+        settle_run_bar();
+        print_reused(dep);
+        summary.add(
+            dep,
+            declared.requirement(&cargo_toml),
+            config.bound.clone(),
+            outcome,
+        );
+    }
+    if summary.unreached_deps() != 0 {
+        out!(
+            "{}",
+            format!(
+                "Stopped at the first failure, {} deps not tested",
+                summary.unreached_deps()
+            )
+            .bright_black()
+        );
+    }
+    finish_run_bar();
+    summary.duration = run_start.elapsed();
+    Ok(summary)
+}
+
+/// The deps whose requirement differs from the one in the manifest at `git_ref`, or that it
+/// doesnt have.
+fn changed_since(
+    git_ref: &str,
+    cargo_toml: &DocumentMut,
+    deps: &[DeclaredDep],
+) -> Result<Vec<String>> {
+    // `./` makes git look the manifest up relative to the current directory.
+    let output = Command::new("git")
+        .args(["show", &format!("{git_ref}:./Cargo.toml")])
+        .output()
+        .map_err(|err| anyhow!("Couldnt run git for --since-ref ({err})"))?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "Couldnt read Cargo.toml at {git_ref}: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    let old = String::from_utf8(output.stdout)?
+        .parse::<DocumentMut>()
+        .map_err(|err| anyhow!("Cargo.toml at {git_ref} isnt valid TOML: {err}"))?;
+    Ok(deps
+        .iter()
+        .filter(|dep| {
+            let before = item_at(&old, &dep.tables[0]);
+            before.is_none_or(|before| declared_bound(before) != dep.requirement(cargo_toml))
+        })
+        .map(|dep| dep.name.clone())
+        .collect())
+}
+
+/// Say that `dep` comes from `[workspace.dependencies]`, which cant be pinned from here.
+fn print_inherited(dep: &str) {
+    out!(
+        "{} {} {}",
+        "Skipping".yellow(),
+        dep.green(),
+        "as its requirement is inherited from the workspace".yellow()
+    );
+}
+
+/// The dep called `name`, or all of them when no name is given.
+///
+/// `name` is the name in the manifest, or the package of a renamed dep when only one has it.
+fn select_deps(deps: Vec<DeclaredDep>, name: Option<&str>) -> Result<Vec<DeclaredDep>> {
+    let Some(name) = name else {
+        return Ok(deps);
+    };
+    if let Some(dep) = deps.iter().find(|dep| dep.name == name) {
+        return Ok(vec![dep.clone()]);
+    }
+    let renamed = deps
+        .into_iter()
+        .filter(|dep| dep.package == name)
+        .collect::<Vec<_>>();
+    match renamed.len() {
+        0 => Err(anyhow!("dep {name} not found.")),
+        1 => Ok(renamed),
+        _ => Err(anyhow!(
+            "{name} is declared as {}, pick one of them",
+            renamed
+                .iter()
+                .map(|dep| dep.name.as_str())
+                .collect::<Vec<_>>()
+                .join(" and ")
+        )),
+    }
+}
+
+/// A normal dependency of the package, as `cargo metadata` reports it.
+#[derive(Clone, Debug)]
+struct DeclaredDep {
+    /// The name it has in the manifest, which is the rename when it has one. Everything is
+    /// reported under this name.
+    name: String,
+    /// The name it is published under, which is what is fetched and pinned in the lockfile.
+    package: String,
+    /// Where it is declared, `dependencies` and any `target.<cfg>.dependencies`.
+    tables: Vec<Vec<String>>,
+    /// Set for `workspace = true`, whose requirement lives in the workspace manifest.
+    inherited: bool,
+    /// The entry in `[workspace.dependencies]` of an inherited dep.
+    workspace_entry: Option<toml_edit::Item>,
+}
+
+impl DeclaredDep {
+    /// The requirement of its first declaration, `None` when it has none.
+    fn requirement(&self, cargo_toml: &DocumentMut) -> Option<String> {
+        match &self.workspace_entry {
+            Some(entry) => declared_bound(entry),
+            None => declared_bound(item_at(cargo_toml, &self.tables[0])?),
+        }
+    }
+
+    /// The first declaration in the manifest.
+    fn item<'a>(&self, cargo_toml: &'a DocumentMut) -> Option<&'a toml_edit::Item> {
+        item_at(cargo_toml, &self.tables[0])
+    }
+}
+
+#[derive(Deserialize)]
+struct CargoMetadata {
+    packages: Vec<MetadataPackage>,
+    workspace_root: PathBuf,
+}
+
+#[derive(Deserialize)]
+struct MetadataPackage {
+    name: String,
+    version: semver::Version,
+    manifest_path: PathBuf,
+    dependencies: Vec<MetadataDependency>,
+}
+
+#[derive(Deserialize)]
+struct MetadataDependency {
+    name: String,
+    /// `None` for normal dependencies, `dev` or `build` otherwise.
+    kind: Option<String>,
+    target: Option<String>,
+    rename: Option<String>,
+}
+
+/// The normal dependencies of the package in the current directory, in the order cargo lists
+/// them, `dependencies` and target specific ones alike.
+///
+/// Cargo does the parsing, the manifest is only used to find the declarations to edit. The
+/// result is kept for [`declaration_tables`].
+fn declared_deps(cargo_toml: &DocumentMut) -> Result<Vec<DeclaredDep>> {
+    let output = Command::new("cargo")
+        .args(["metadata", "--no-deps", "--format-version", "1"])
+        .output()?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "`cargo metadata` failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    let metadata = serde_json::from_slice::<CargoMetadata>(&output.stdout)?;
+    let manifest = fs::canonicalize("Cargo.toml")?;
+    // A virtual manifest has no package, and so no dependencies of its own.
+    let Some(package) = metadata.packages.into_iter().find(|package| {
+        fs::canonicalize(&package.manifest_path).is_ok_and(|path| path == manifest)
+    }) else {
+        return Ok(Vec::new());
+    };
+
+    let workspace = fs::read_to_string(metadata.workspace_root.join("Cargo.toml"))
+        .ok()
+        .and_then(|manifest| manifest.parse::<DocumentMut>().ok());
+    let mut deps = Vec::<DeclaredDep>::new();
+    for dependency in package.dependencies {
+        if dependency.kind.is_some() {
+            continue;
+        }
+        let package = dependency.name;
+        let name = dependency.rename.unwrap_or_else(|| package.clone());
+        let mut table = match dependency.target {
+            Some(target) => vec!["target".to_owned(), target, "dependencies".to_owned()],
+            None => vec!["dependencies".to_owned()],
+        };
+        table.push(name.clone());
+        let inherited = item_at(cargo_toml, &table)
+            .and_then(|item| item.get("workspace"))
+            .and_then(|workspace| workspace.as_bool())
+            .unwrap_or(false);
+        let workspace_entry = workspace
+            .as_ref()
+            .filter(|_| inherited)
+            .and_then(|workspace| workspace.get("workspace")?.get("dependencies")?.get(&name))
+            .map(|entry| workspace_entry(entry, &metadata.workspace_root));
+        match deps.iter_mut().find(|dep| dep.name == name) {
+            Some(dep) => dep.tables.push(table),
+            None => deps.push(DeclaredDep {
+                name,
+                package,
+                tables: vec![table],
+                inherited,
+                workspace_entry,
+            }),
+        }
+    }
+    *DECLARED_DEPS.lock().unwrap() = deps.clone();
+    Ok(deps)
+}
+
+/// A `[workspace.dependencies]` entry as an inline table, with its `path` made absolute so it
+/// still points at the same place from a member.
+fn workspace_entry(entry: &toml_edit::Item, root: &Path) -> toml_edit::Item {
+    let mut table = toml_edit::InlineTable::new();
+    if let Some(version) = entry.as_str() {
+        table.insert("version", version.into());
+    } else if let Some(entry) = entry.as_table_like() {
+        for (key, value) in entry.iter() {
+            if let Some(value) = value.as_value() {
+                table.insert(key, value.clone());
+            }
+        }
+    }
+    if let Some(path) = table.get("path").and_then(|path| path.as_str()) {
+        let path = root.join(path).to_string_lossy().into_owned();
+        table.insert("path", path.as_str().into());
+    }
+    table.into()
+}
+
+/// Where `dep` is declared, according to the last [`declared_deps`].
+fn declaration_tables(dep: &str) -> Vec<Vec<String>> {
+    DECLARED_DEPS
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|declared| declared.name == dep)
+        .map(|declared| declared.tables.clone())
+        .unwrap_or_else(|| vec![vec!["dependencies".to_owned(), dep.to_owned()]])
+}
+
+/// The name `dep` is published under, see [`DeclaredDep::package`].
+fn package_name(dep: &str) -> String {
+    DECLARED_DEPS
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|declared| declared.name == dep)
+        .map_or_else(|| dep.to_owned(), |declared| declared.package.clone())
+}
+
+/// Whether another dep is the same package under a different name, like two majors of it.
+fn package_declared_twice(dep: &str) -> bool {
+    let package = package_name(dep);
+    DECLARED_DEPS
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|declared| declared.package == package)
+        .count()
+        > 1
+}
+
+/// The item at `path`, like `["target", "cfg(unix)", "dependencies", "libc"]`.
+fn item_at<'a>(cargo_toml: &'a DocumentMut, path: &[String]) -> Option<&'a toml_edit::Item> {
+    let mut item = cargo_toml.as_item();
+    for key in path {
+        item = item.get(key)?;
+    }
+    Some(item)
+}
+
+fn item_at_mut<'a>(
+    cargo_toml: &'a mut DocumentMut,
+    path: &[String],
+) -> Option<&'a mut toml_edit::Item> {
+    let mut item = cargo_toml.as_item_mut();
+    for key in path {
+        item = item.get_mut(key)?;
+    }
+    Some(item)
+}
+
+/// Turn every declaration of `dep` into a table, so a pin can be written as its `version`.
+///
+/// Returns the requirement and the path of the string holding it in the original manifest,
+/// `None` when it has no requirement.
+fn prepare_declarations(
+    cargo_toml: &mut DocumentMut,
+    dep: &DeclaredDep,
+) -> Result<Option<(String, Vec<String>)>> {
+    let mut found = None;
+    for table in &dep.tables {
+        let item =
+            item_at_mut(cargo_toml, table).ok_or(anyhow!("{} not found", table.join(".")))?;
+        // Written out in full, the version can then be pinned in the member.
+        if let Some(toml_edit::Item::Value(toml_edit::Value::InlineTable(entry))) =
+            &dep.workspace_entry
+        {
+            let mut merged = entry.clone();
+            for (key, value) in item
+                .as_table_like()
+                .into_iter()
+                .flat_map(|item| item.iter())
+            {
+                let Some(value) = value.as_value() else {
+                    continue;
+                };
+                match (key, merged.get_mut("features"), value.as_array()) {
+                    ("workspace", _, _) => {}
+                    // Features of the member come on top of the ones of the workspace.
+                    ("features", Some(toml_edit::Value::Array(features)), Some(extra)) => {
+                        features.extend(extra.iter().cloned());
+                    }
+                    _ => {
+                        merged.insert(key, value.clone());
+                    }
+                }
+            }
+            *item = merged.into();
+        }
+        let mut path = table.clone();
+        let requirement = if let Some(requirement) = item.as_str() {
+            let requirement = requirement.to_owned();
+            let mut new_table = toml_edit::InlineTable::new();
+            new_table.insert("version", requirement.as_str().into());
+            *item = new_table.into();
+            requirement
+        } else {
+            let table = item.as_table_like().ok_or(anyhow!("Unexpected dep type"))?;
+            let Some(requirement) = table.get("version") else {
+                continue;
+            };
+            path.push("version".to_owned());
+            requirement
+                .as_str()
+                .ok_or(anyhow!("Expected str"))?
+                .to_owned()
+        };
+        found.get_or_insert((requirement, path));
+    }
+    Ok(found)
+}
+
+/// Write `requirement` into every declaration of `dep`.
+fn set_requirement(cargo_toml: &mut DocumentMut, dep: &str, requirement: &str) {
+    for table in declaration_tables(dep) {
+        let Some(item) = item_at_mut(cargo_toml, &table) else {
+            continue;
+        };
+        if item.is_str() {
+            *item = toml_edit::value(requirement);
+        } else {
+            item["version"] = requirement.into();
+        }
+    }
+}
+
+/// The version requirement a dep is declared with, `None` for deps without one like git deps.
+fn declared_bound(item: &toml_edit::Item) -> Option<String> {
+    let version = match item.as_table_like() {
+        Some(table) => table.get("version")?,
+        None => item,
+    };
+    version.as_str().map(str::to_owned)
+}
+
+#[derive(Default)]
+struct TestSummary {
+    /// Set when only the edges of each bound were tested.
+    extremes_only: bool,
+    /// The granularity `--adaptive` settled on for each dep.
+    granularity: Vec<(String, Granularity)>,
+    /// The toolchains when `--toolchain` was given more than once.
+    toolchains: Vec<String>,
+    /// The targets when `--target` was given more than once.
+    targets: Vec<String>,
+    /// The results of each dep, which the rest of the summary is worked out from.
+    results: Vec<DepResults>,
+    /// How long the whole run took, including the baseline.
+    duration: Duration,
+    /// How the versions were checked.
+    configuration: RunConfiguration,
+}
+
+/// The result of testing a single dependency.
+struct DepOutcome {
+    fails: usize,
+    /// Set when the granularity was chosen by `--adaptive`.
+    granularity: Option<Granularity>,
+    /// The versions of the sweep, empty for deps that werent swept.
+    tested: Vec<TestedVersion>,
+    /// The versions `--fail-fast` skipped after the first failure.
+    untested: Vec<semver::Version>,
+    /// The latest release, when the bound excludes it.
+    outdated: Option<semver::Version>,
+    /// Set when no published version matches the bound.
+    no_match: Option<NoMatch>,
+    /// How long testing the dep took, from fetching its versions to the last check.
+    duration: Duration,
+}
+
+impl DepOutcome {
+    fn fails(fails: usize) -> Self {
+        DepOutcome {
+            fails,
+            granularity: None,
+            tested: Vec::new(),
+            untested: Vec::new(),
+            outdated: None,
+            no_match: None,
+            duration: Duration::ZERO,
+        }
+    }
+
+    fn tested(tested: Vec<TestedVersion>, granularity: Option<Granularity>) -> Self {
+        DepOutcome {
+            fails: tested
+                .iter()
+                .filter(|tested| tested.result == TestResult::Fail)
+                .count(),
+            granularity,
+            tested,
+            untested: Vec::new(),
+            outdated: None,
+            no_match: None,
+            duration: Duration::ZERO,
+        }
+    }
+
+    fn with_untested(self, untested: Vec<semver::Version>) -> Self {
+        DepOutcome { untested, ..self }
+    }
+
+    fn with_outdated(self, outdated: Option<semver::Version>) -> Self {
+        DepOutcome { outdated, ..self }
+    }
+
+    fn with_no_match(self, no_match: NoMatch) -> Self {
+        DepOutcome {
+            no_match: Some(no_match),
+            ..self
+        }
+    }
+}
+
+impl TestSummary {
+    /// How many deps have a failure.
+    fn failed_deps(&self) -> usize {
+        self.results.iter().filter(|dep| dep.failed != 0).count()
+    }
+
+    /// How many checks failed over all deps.
+    fn failed_versions(&self) -> usize {
+        self.results.iter().map(|dep| dep.failed).sum()
+    }
+
+    /// The versions `--fail-fast` didnt get to.
+    fn untested_versions(&self) -> usize {
+        self.results.iter().map(|dep| dep.untested.len()).sum()
+    }
+
+    /// The deps `--fail-fast=all` didnt get to.
+    fn unreached_deps(&self) -> usize {
+        self.results.iter().filter(|dep| dep.unreached).count()
+    }
+
+    /// The failing versions of each dep that has any.
+    fn failing(&self) -> HashMap<String, Vec<semver::Version>> {
+        let mut failing = HashMap::<String, Vec<semver::Version>>::new();
+        for dep in &self.results {
+            let versions = dep
+                .versions
+                .iter()
+                .filter(|tested| tested.result == TestResult::Fail)
+                .map(|tested| tested.version.clone());
+            failing.entry(dep.dep.clone()).or_default().extend(versions);
+        }
+        failing.retain(|_, versions| !versions.is_empty());
+        failing
+    }
+
+    /// Every tested version, with the dep it belongs to.
+    fn tested(&self) -> impl Iterator<Item = (&str, &VersionResult)> {
+        self.results.iter().flat_map(|dep| {
+            dep.versions
+                .iter()
+                .map(move |tested| (dep.dep.as_str(), tested))
+        })
+    }
+
+    /// The failing versions with what `failed` picks out of them, for those where it isnt empty.
+    fn failing_with<'a>(
+        &'a self,
+        failed: impl Fn(&'a VersionResult) -> &'a [String],
+    ) -> Vec<(&'a str, &'a semver::Version, &'a [String])> {
+        self.tested()
+            .filter(|(_, tested)| tested.result == TestResult::Fail)
+            .map(|(dep, tested)| (dep, &tested.version, failed(tested)))
+            .filter(|(_, _, failed)| !failed.is_empty())
+            .collect()
+    }
+
+    /// The versions that couldnt be confirmed either way for `reason`.
+    fn unconfirmed(&self, reason: Unconfirmed) -> Vec<(&str, &semver::Version)> {
+        self.tested()
+            .filter(|(_, tested)| tested.unconfirmed() == Some(reason))
+            .map(|(dep, tested)| (dep, &tested.version))
+            .collect()
+    }
+
+    fn add(
+        &mut self,
+        dep: &str,
+        bound: Option<String>,
+        hypothetical_bound: Option<String>,
+        outcome: DepOutcome,
+    ) {
+        if let Some(granularity) = outcome.granularity {
+            self.granularity.push((dep.to_owned(), granularity));
+        }
+        self.results.push(DepResults {
+            dep: dep.to_owned(),
+            package: None,
+            bound,
+            hypothetical_bound,
+            failed: outcome.fails,
+            untested: outcome.untested,
+            unreached: false,
+            no_match: outcome.no_match,
+            duration_ms: outcome.duration.as_millis(),
+            versions: outcome
+                .tested
+                .into_iter()
+                .map(|tested| VersionResult {
+                    version: tested.version,
+                    result: tested.result,
+                    duration_ms: tested.duration.as_millis(),
+                    failed_variants: tested.failed_variants,
+                    failed_toolchains: tested.failed_toolchains,
+                    failed_targets: tested.failed_targets,
+                    failure: tested.failure,
+                    retries: tested.retries,
+                    logs: tested.logs,
+                })
+                .collect(),
+        });
+    }
+
+    /// Print a GitHub Actions annotation for every failing dep, pointing at its line in
+    /// `manifest`, and warnings for indeterminate versions and deps without a bound.
+    fn print_annotations(&self, manifest: &str) -> Result<()> {
+        let file = manifest_for_annotations()?;
+        let lines = dep_lines(manifest);
+        let line = |dep: &str| {
+            lines
+                .get(dep)
+                .map_or(String::new(), |line| format!(",line={line}"))
+        };
+        for dep in &self.results {
+            let name = &dep.dep;
+            let Some(bound) = &dep.bound else {
+                let message = format!("{name} has no version requirement, its bound isnt tested");
+                annotate("warning", &file, &line(name), &message);
+                continue;
+            };
+            let failing = dep
+                .versions
+                .iter()
+                .filter(|tested| tested.result == TestResult::Fail)
+                .map(|tested| tested.version.to_string())
+                .collect::<Vec<_>>();
+            if let Some(no_match) = &dep.no_match {
+                let mut message = format!(
+                    "{name} bound {bound:?} matches no published version, {}",
+                    no_match.closest()
+                );
+                for excluded in no_match.excluded() {
+                    message = format!("{message}\n{excluded}");
+                }
+                annotate("error", &file, &line(name), &message);
+            } else if !failing.is_empty() {
+                let message = format!("{name} bound {bound:?} fails at {}", failing.join(", "));
+                annotate("error", &file, &line(name), &message);
+            } else if dep.failed != 0 {
+                let message = format!("{name} bound {bound:?} fails");
+                annotate("error", &file, &line(name), &message);
+            }
+            let indeterminate = dep.unconfirmed(Unconfirmed::Indeterminate);
+            if !indeterminate.is_empty() {
+                let message = format!(
+                    "{name} bound {bound:?} is indeterminate at {}, cargo used a different version",
+                    indeterminate.join(", ")
+                );
+                annotate("warning", &file, &line(name), &message);
+            }
+            let untestable = dep.unconfirmed(Unconfirmed::Untestable);
+            if !untestable.is_empty() {
+                let message = format!(
+                    "{name} bound {bound:?} is untestable at {}, cargo couldnt resolve the dependency graph",
+                    untestable.join(", ")
+                );
+                annotate("warning", &file, &line(name), &message);
+            }
+            let killed = dep.unconfirmed(Unconfirmed::Killed);
+            if !killed.is_empty() {
+                let message = format!(
+                    "{name} bound {bound:?} is unknown at {}, the check was killed by a signal",
+                    killed.join(", ")
+                );
+                annotate("warning", &file, &line(name), &message);
+            }
+        }
+        Ok(())
+    }
+
+    /// Add the results of the `--package` called `package`.
+    fn merge(&mut self, other: TestSummary, package: &str) {
+        let TestSummary {
+            extremes_only,
+            granularity,
+            toolchains,
+            targets,
+            results,
+            duration: _,
+            configuration,
+        } = other;
+        self.extremes_only |= extremes_only;
+        self.granularity.extend(granularity);
+        self.results
+            .extend(results.into_iter().map(|dep| DepResults {
+                package: Some(package.to_owned()),
+                ..dep
+            }));
+        // The same for every package, a package without deps just doesnt fill them in.
+        if self.toolchains.is_empty() {
+            self.toolchains = toolchains;
+        }
+        if self.targets.is_empty() {
+            self.targets = targets;
+        }
+        if self.configuration.command.is_empty() {
+            self.configuration = configuration;
+        }
+    }
+
+    /// Record a dep that `--fail-fast=all` stopped the run before.
+    fn add_unreached(&mut self, dep: &str, bound: Option<String>) {
+        self.results.push(DepResults {
+            dep: dep.to_owned(),
+            package: None,
+            bound,
+            hypothetical_bound: None,
+            failed: 0,
+            untested: Vec::new(),
+            unreached: true,
+            no_match: None,
+            duration_ms: 0,
+            versions: Vec::new(),
+        });
+    }
+
+    /// Print where each dep goes from failing to passing, used by `--explore`.
+    fn print_transitions(&self) {
+        out!("Transition regions:");
+        for dep in self.results.iter().filter(|dep| !dep.unreached) {
+            let (newest_fail, oldest_pass) = dep.transition();
+            let newest_fail =
+                newest_fail.map_or("none".to_owned(), |version| version.red().to_string());
+            let oldest_pass =
+                oldest_pass.map_or("none".to_owned(), |version| version.green().to_string());
+            out!(
+                "  {} - newest failing {newest_fail}, oldest passing {oldest_pass}",
+                dep.dep.blue()
+            );
+        }
+    }
+
+    /// Print what failed on each toolchain, and the versions that only fail on some of them.
+    ///
+    /// Those build with a newer compiler, so they most likely need a newer Rust rather than
+    /// breaking the API.
+    fn print_toolchains(&self) {
+        print_breakdown(
+            "Per toolchain:",
+            "Failing only on some toolchains, likely a too old compiler rather than a break:",
+            &self.toolchains,
+            &self.failing_with(|tested| &tested.failed_toolchains),
+            "+",
+        );
+    }
+
+    /// Print what failed for each target, and the versions that only fail for some of them.
+    fn print_targets(&self) {
+        print_breakdown(
+            "Per target:",
+            "Failing only for some targets:",
+            &self.targets,
+            &self.failing_with(|tested| &tested.failed_targets),
+            "",
+        );
+    }
+
+    /// Print the versions that couldnt be confirmed either way.
+    fn print_indeterminate(&self) {
+        for (reason, title) in [
+            (
+                Unconfirmed::Indeterminate,
+                "Indeterminate, cargo used a different version than the pin:",
+            ),
+            (
+                Unconfirmed::Untestable,
+                "Untestable, cargo couldnt resolve the dependency graph with the pin:",
+            ),
+            (
+                Unconfirmed::Killed,
+                "Unknown, the check was killed by a signal (out of memory?):",
+            ),
+        ] {
+            let versions = self.unconfirmed(reason);
+            if versions.is_empty() {
+                continue;
+            }
+            out!("{title}");
+            for (dep, version) in versions {
+                out!("  {} {}", dep.blue(), version.yellow());
+            }
+        }
+    }
+
+    /// Print the versions that only passed when checked again, they may be flaky.
+    fn print_retried(&self) {
+        let retried = self
+            .tested()
+            .filter(|(_, tested)| tested.result == TestResult::Sucess && tested.retries != 0)
+            .collect::<Vec<_>>();
+        if retried.is_empty() {
+            return;
+        }
+        out!("Passed only after a retry:");
+        for (dep, tested) in retried {
+            let plural = if tested.retries == 1 {
+                "retry"
+            } else {
+                "retries"
+            };
+            out!(
+                "  {} {} {}",
+                dep.blue(),
+                tested.version.yellow(),
+                format!("({} {plural})", tested.retries).bright_black()
+            );
+        }
+    }
+
+    /// Print which feature configurations each failing version failed with, for `--each-feature`.
+    fn print_failing_variants(&self) {
+        let failing = self.failing_with(|tested| &tested.failed_variants);
+        if failing.is_empty() {
+            return;
+        }
+        out!("Failing feature configurations:");
+        for (dep, version, variants) in failing {
+            out!(
+                "  {} {} - {}",
+                dep.blue(),
+                version.red(),
+                variants.join(", ").yellow()
+            );
+        }
+    }
+
+    fn print_granularity(&self) {
+        if self.granularity.is_empty() {
+            return;
+        }
+        out!("Adaptive granularity:");
+        for (dep, granularity) in &self.granularity {
+            out!("  {} - {}", dep.blue(), granularity.yellow());
+        }
+    }
+
+    /// The closing table, a row per dep with its bound, how many versions were tested and
+    /// failed, and the failing versions.
+    ///
+    /// After `--fail-fast` stopped early, a column says how many versions werent tested.
+    fn table(&self, colored: bool) -> String {
+        let stopped_early = self.untested_versions() != 0 || self.unreached_deps() != 0;
+        let mut header = vec![
+            "dep",
+            "bound",
+            "tested",
+            "failed",
+            "time",
+            "failing versions",
+        ];
+        if stopped_early {
+            header.insert(3, "not tested");
+        }
+        let packages = self.results.iter().any(|dep| dep.package.is_some());
+        if packages {
+            header.insert(0, "package");
+        }
+        let rows = self
+            .results
+            .iter()
+            .map(|dep| {
+                let failing = dep
+                    .versions
+                    .iter()
+                    .filter(|tested| tested.result == TestResult::Fail)
+                    .map(|tested| match tested.failure {
+                        Some(FailureKind::ResolutionConflict) => {
+                            format!("{} (conflict)", tested.version)
+                        }
+                        _ => tested.version.to_string(),
+                    })
+                    .collect::<Vec<_>>();
+                let mut shown = failing
+                    .iter()
+                    .take(TABLE_FAILING_SHOWN)
+                    .cloned()
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                if failing.len() > TABLE_FAILING_SHOWN {
+                    shown.push_str(&format!(" +{} more", failing.len() - TABLE_FAILING_SHOWN));
+                }
+                let mut row = vec![
+                    dep.dep.clone(),
+                    dep.bound.clone().unwrap_or_else(|| "-".to_owned()),
+                    dep.versions.len().to_string(),
+                    dep.failed.to_string(),
+                    match dep.unreached {
+                        true => "-".to_owned(),
+                        false => format!("{:.1}s", dep.duration_ms as f64 / 1000.0),
+                    },
+                    shown,
+                ];
+                if stopped_early {
+                    let untested = match dep.unreached {
+                        true => "all".to_owned(),
+                        false => dep.untested.len().to_string(),
+                    };
+                    row.insert(3, untested);
+                }
+                if packages {
+                    row.insert(0, dep.package.clone().unwrap_or_default());
+                }
+                row
+            })
+            .collect::<Vec<_>>();
+        let header = header.into_iter().map(str::to_owned).collect::<Vec<_>>();
+        let mut widths = vec![0; header.len()];
+        for row in std::iter::once(&header).chain(&rows) {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+
+        let mut table = String::new();
+        for (index, row) in std::iter::once(&header).chain(&rows).enumerate() {
+            let failed = header
+                .iter()
+                .position(|column| column == "failed")
+                .is_some_and(|column| row[column] != "0");
+            let cells = row
+                .iter()
+                .zip(&widths)
+                .zip(&header)
+                .map(|((cell, width), column)| {
+                    let cell = format!("{cell:width$}");
+                    if !colored {
+                        return cell;
+                    }
+                    match column.as_str() {
+                        _ if index == 0 => cell.paint(Style::new().bold()).to_string(),
+                        "dep" => cell.blue().to_string(),
+                        "bound" => cell.yellow().to_string(),
+                        "not tested" | "time" => cell.bright_black().to_string(),
+                        "failed" | "failing versions" if failed => cell.red().to_string(),
+                        "failed" => cell.green().to_string(),
+                        _ => cell,
+                    }
+                });
+            let line = cells.collect::<Vec<_>>().join("  ");
+            table.push_str(line.trim_end());
+            table.push('\n');
+        }
+        table
+    }
+
+    /// The summary of the run without colors, the table and a total.
+    fn plain_text(&self) -> String {
+        let mut text = self.table(false);
+        if let Some(note) = self.hypothetical_note() {
+            text.push_str(&note);
+            text.push('\n');
+        }
+        if self.extremes_only {
+            text.push_str(EXTREMES_ONLY_NOTE);
+            text.push('\n');
+        }
+        for dep in &self.results {
+            for (reason, label) in [
+                (Unconfirmed::Indeterminate, "indeterminate"),
+                (Unconfirmed::Untestable, "untestable"),
+                (Unconfirmed::Killed, "killed"),
+            ] {
+                let versions = dep.unconfirmed(reason);
+                if !versions.is_empty() {
+                    text.push_str(&format!("{}: {label} {}\n", dep.dep, versions.join(", ")));
+                }
+            }
+            let retried = dep
+                .versions
+                .iter()
+                .filter(|tested| tested.result == TestResult::Sucess && tested.retries != 0)
+                .map(|tested| tested.version.to_string())
+                .collect::<Vec<_>>();
+            if !retried.is_empty() {
+                text.push_str(&format!(
+                    "{}: passed after a retry {}\n",
+                    dep.dep,
+                    retried.join(", ")
+                ));
+            }
+            for tested in &dep.versions {
+                if !tested.failed_toolchains.is_empty() {
+                    text.push_str(&format!(
+                        "{} {}: failed on +{}\n",
+                        dep.dep,
+                        tested.version,
+                        tested.failed_toolchains.join(", +")
+                    ));
+                }
+                if !tested.failed_targets.is_empty() {
+                    text.push_str(&format!(
+                        "{} {}: failed for {}\n",
+                        dep.dep,
+                        tested.version,
+                        tested.failed_targets.join(", ")
+                    ));
+                }
+            }
+        }
+        text.push_str(&format!(
+            "{} deps have failing versions in their bounds. ({} versions failed in total){}\n",
+            self.failed_deps(),
+            self.failed_versions(),
+            self.not_tested()
+        ));
+        let slowest = self.slowest();
+        if slowest.len() > 1 {
+            text.push_str("Slowest checks:\n");
+            for check in &slowest {
+                text.push_str(&format!(
+                    "  {} {} {:.1}s\n",
+                    check.dep,
+                    check.version,
+                    check.duration_ms as f64 / 1000.0
+                ));
+            }
+        }
+        text.push_str(&format!("Took {:.1}s\n", self.duration.as_secs_f32()));
+        text
+    }
+
+    /// The checks that took longest, newest first on ties.
+    fn slowest(&self) -> Vec<SlowCheck> {
+        let mut checks = self
+            .results
+            .iter()
+            .flat_map(|dep| {
+                dep.versions.iter().map(|tested| SlowCheck {
+                    dep: dep.name(),
+                    version: tested.version.clone(),
+                    duration_ms: tested.duration_ms,
+                })
+            })
+            // Results reused from earlier runs didnt take any time.
+            .filter(|check| check.duration_ms != 0)
+            .collect::<Vec<_>>();
+        checks.sort_by(|a, b| {
+            b.duration_ms
+                .cmp(&a.duration_ms)
+                .then_with(|| b.version.cmp(&a.version))
+        });
+        checks.truncate(SLOWEST_SHOWN);
+        checks
+    }
+
+    /// Which dep `--bound` swept a hypothetical requirement of, so the results arent mistaken
+    /// for the declared one.
+    fn hypothetical_note(&self) -> Option<String> {
+        self.results.iter().find_map(|dep| {
+            let hypothetical = dep.hypothetical_bound.as_ref()?;
+            Some(format!(
+                "Tested the hypothetical bound {hypothetical} of {}, Cargo.toml still declares {}",
+                dep.name(),
+                dep.bound.as_deref().unwrap_or("nothing")
+            ))
+        })
+    }
+
+    /// What `--fail-fast` left out, so the counts dont read as everything else passing.
+    fn not_tested(&self) -> String {
+        match (self.untested_versions(), self.unreached_deps()) {
+            (0, 0) => String::new(),
+            (versions, 0) => format!(" {versions} versions not tested after failing fast."),
+            (versions, deps) => {
+                format!(" {versions} versions and {deps} deps not tested after failing fast.")
+            }
+        }
+    }
+
+    fn print(self) -> String {
+        format!(
+            "{} deps have failing versions in their bounds. ({} versions failed in total){}",
+            self.failed_deps().red(),
+            self.failed_versions().yellow(),
+            self.not_tested().bright_black()
+        )
+    }
+}
+
+fn sanity_test_dep(
+    state: &State,
+    declared: &DeclaredDep,
+    config: &TestConfig,
+    metadata: &Metadata,
+) -> Result<DepOutcome> {
+    let dep = declared.name.as_str();
+    let mut cargo_toml = state.cargo_toml.parse::<DocumentMut>()?;
+    let optional = declared
+        .item(&cargo_toml)
+        .and_then(|item| item.get("optional"))
+        .and_then(|optional| optional.as_bool())
+        .unwrap_or(false);
+
+    if declared.inherited && declared.workspace_entry.is_none() {
+        print_inherited(dep);
+        return Ok(DepOutcome::fails(0));
+    }
+    if print_patched(dep) {
+        return Ok(DepOutcome::fails(0));
+    }
+    let Some((requirement, _)) = prepare_declarations(&mut cargo_toml, declared)? else {
+        out!("{} {}", "No version info found for".yellow(), dep.green());
+        return Ok(DepOutcome::fails(0));
+    };
+    let bound = match &config.bound {
+        Some(hypothetical) => parse_bound(dep, hypothetical)?,
+        None => parse_bound(dep, &requirement)?,
+    };
+
+    let label = format!("{} {}", dep.blue(), bound.yellow());
+    if display_mode() != DisplayMode::Compact {
+        match &config.bound {
+            Some(_) => out!(
+                "{} - {} {}",
+                dep.blue(),
+                bound.yellow(),
+                format!("(hypothetical, declared {requirement})").bright_black()
+            ),
+            None => out!("{} - {}", dep.blue(), bound.yellow()),
+        }
+    }
+    let mut config = config.for_dep(dep, metadata);
+    if optional && config.check.command.is_none() && !config.all_features() {
+        let features = enabling_features(&cargo_toml, dep);
+        if features.is_empty() {
+            out!(
+                "  {} {} is optional but no feature enables it, its bound cant be tested",
+                "warning:".yellow(),
+                dep.blue()
+            );
+            return Ok(DepOutcome::fails(0));
+        }
+        for feature in features {
+            if !config.check.features.contains(&feature) {
+                config.check.features.push(feature);
+            }
+        }
+    }
+    let config = &config;
+
+    if metadata.policy(dep) == DepPolicy::Exact {
+        start_dep_bar(label, 1);
+        return validate_pin(&mut cargo_toml, dep, &bound, config).map(DepOutcome::fails);
+    }
+
+    if bound.comparators.is_empty() {
+        out!(
+            "  {} {} is `*`, testing it as every published version",
+            "warning:".yellow(),
+            dep.blue()
+        );
+    }
+    let Some(published) = published_versions(&cargo_toml, dep)? else {
+        return Ok(DepOutcome::fails(0));
+    };
+    let candidates = Candidates::pick(&cargo_toml, dep, &bound, &published, config)?;
+    let versions = &candidates.in_bound;
+    let too_new = candidates.removed_by("rust-version");
+    if versions.is_empty() && !too_new.is_empty() {
+        out!(
+            "  {} every version of {} in {} needs a newer Rust, {}",
+            "error:".red(),
+            dep.blue(),
+            bound.yellow(),
+            too_new[0].1
+        );
+        return Ok(DepOutcome::fails(1));
+    }
+    if versions.is_empty() {
+        let no_match = print_no_match(dep, &bound, &published, config.rust_version.toolchain());
+        return Ok(DepOutcome::fails(1).with_no_match(no_match));
+    }
+    print_skipped(&too_new);
+    let outdated = outdated_release(&bound, &candidates.stable);
+    if let Some(latest) = &outdated
+        && !config.no_outdated_hint
+    {
+        let level = match config.strict_outdated {
+            true => "error:".red().to_string(),
+            false => "note:".bright_black().to_string(),
+        };
+        out!(
+            "  {level} {} excludes {}, the latest release",
+            bound.yellow(),
+            latest.yellow()
+        );
+    }
+    let selected = &candidates.selected;
+
+    if config.focus.is_some() {
+        start_dep_bar(label, selected.len());
+        let mut results = Vec::new();
+        let untested = test_selected(
+            &mut cargo_toml,
+            dep,
+            versions,
+            selected,
+            versions,
+            config,
+            &mut results,
+        )?;
+        return Ok(DepOutcome::tested(results, None)
+            .with_untested(untested)
+            .with_outdated(outdated));
+    }
+
+    let unresolvable = candidates.removed_by("preflight");
+    print_skipped(&unresolvable);
+    let sampled_out = candidates.removed_by("per-major");
+    if config.print_skiped {
+        print_skipped(&sampled_out);
+    }
+    let sampled_out = sampled_out
+        .into_iter()
+        .map(|(version, _)| version)
+        .collect::<Vec<_>>();
+    start_dep_bar(label, selected.len());
+    let mut results = Vec::new();
+    // When adapting the skipped versions are only known after refining.
+    let hide_skipped = if config.adaptive {
+        &versions[..]
+    } else {
+        &sampled_out[..]
+    };
+    let untested = test_selected(
+        &mut cargo_toml,
+        dep,
+        versions,
+        selected,
+        hide_skipped,
+        config,
+        &mut results,
+    )?;
+    let failed = results
+        .iter()
+        .any(|tested| tested.result == TestResult::Fail);
+    // Refining after a failure would only add more versions `--fail-fast` skips.
+    if !config.adaptive || (config.fail_fast.is_some() && failed) {
+        return Ok(DepOutcome::tested(results, None)
+            .with_untested(untested)
+            .with_outdated(outdated));
+    }
+
+    let spent = results
+        .iter()
+        .map(|tested| tested.duration)
+        .sum::<Duration>();
+    let per_check = spent / results.len().max(1) as u32;
+    let budget = Duration::from_secs(config.adaptive_budget).saturating_sub(spent);
+    let granularity = refine_granularity(versions, selected, per_check, budget, config.sample);
+    let refined = select_versions(versions, granularity, config.sample);
+    let mut extra = refined
+        .iter()
+        .filter(|version| {
+            !selected.contains(version)
+                && !unresolvable.iter().any(|(skipped, _)| skipped == *version)
+        })
+        .cloned()
+        .collect::<Vec<_>>();
+    if config.preflight {
+        let unresolvable = find_unresolvable(&cargo_toml, dep, &extra)?;
+        print_skipped(&unresolvable);
+        extra.retain(|version| !unresolvable.iter().any(|(skipped, _)| skipped == version));
+    }
+    if !extra.is_empty() {
+        extend_dep_bar(extra.len());
+        out!(
+            "  {} {} ({} more versions, ~{:.1}s per check)",
+            "Refining to".bright_black(),
+            granularity.yellow(),
+            extra.len(),
+            per_check.as_secs_f32()
+        );
+    }
+    let untested = test_selected(
+        &mut cargo_toml,
+        dep,
+        versions,
+        &extra,
+        selected,
+        config,
+        &mut results,
+    )?;
+
+    Ok(DepOutcome::tested(results, Some(granularity))
+        .with_untested(untested)
+        .with_outdated(outdated))
+}
+
+/// Find the versions of `dep` in `candidates` that cant resolve together with the locked
+/// versions of the other direct dependencies, with the conflict that rules each out.
+///
+/// This only looks at the published requirements, one level deep: what the other deps require
+/// of `dep`, and the crates both sides require.
+fn find_unresolvable(
+    cargo_toml: &DocumentMut,
+    dep: &str,
+    candidates: &[semver::Version],
+) -> Result<Vec<(semver::Version, String)>> {
+    let locked = locked_versions()?;
+    if locked.is_empty() {
+        out!(
+            "  {} no Cargo.lock to compare requirements against",
+            "preflight:".bright_black()
+        );
+        return Ok(Vec::new());
+    }
+    let spinner = Spinner::new(format!("Comparing requirements for {}", dep.blue()));
+
+    let mut others = Vec::new();
+    let direct = DECLARED_DEPS
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|dep| (dep.name.clone(), dep.package.clone()))
+        .collect::<Vec<_>>();
+    // Requirements are only looked up on crates.io.
+    if dep_registry(cargo_toml, dep).is_some() {
+        spinner.finish_and_clear();
+        return Ok(Vec::new());
+    }
+    // The lockfile and the registry know deps by their package.
+    let dep = &package_name(dep);
+    let direct = direct
+        .into_iter()
+        .filter(|(other, _)| dep_registry(cargo_toml, other).is_none())
+        .map(|(_, package)| package)
+        .collect::<Vec<_>>();
+    for other in direct.iter().filter(|other| *other != dep) {
+        for version in locked.get(other).into_iter().flatten() {
+            let requirements = normal_requirements(other, version)?;
+            others.push((other, version, requirements));
+        }
+    }
+
+    let mut unresolvable = Vec::new();
+    for candidate in candidates.iter() {
+        let pinned = semver::VersionReq::parse(&format!("={candidate}"))?;
+        let requirements = normal_requirements(dep, candidate)?;
+        let conflict = others
+            .iter()
+            .find_map(|(other, version, other_requirements)| {
+                other_requirements.iter().find_map(|(name, req)| {
+                    if name == dep {
+                        return requirements_conflict(&pinned, req)
+                            .then(|| format!("{other} {version} requires {dep} {req}"));
+                    }
+                    requirements
+                        .iter()
+                        .find(|(shared, ours)| shared == name && requirements_conflict(ours, req))
+                        .map(|(_, ours)| {
+                            format!(
+                                "it requires {name} {ours} but {other} {version} requires {req}"
+                            )
+                        })
+                })
+            });
+        if let Some(reason) = conflict {
+            unresolvable.push((candidate.clone(), reason));
+        }
+    }
+    spinner.finish_and_clear();
+    Ok(unresolvable)
+}
+
+/// Print versions that a selection stage left out, with why.
+fn print_skipped(skipped: &[(semver::Version, String)]) {
+    for (version, reason) in skipped {
+        out!(
+            "  {} {}",
+            version.bright_black(),
+            format!("skipped, {reason}").bright_black()
+        );
+    }
+}
+
+/// The versions of each package in `Cargo.lock`, empty when there is no lockfile.
+fn locked_versions() -> Result<HashMap<String, Vec<semver::Version>>> {
+    let Ok(lockfile) = fs::read_to_string(workspace_root().join("Cargo.lock")) else {
+        return Ok(HashMap::new());
+    };
+    let lockfile = lockfile.parse::<DocumentMut>()?;
+    let mut locked = HashMap::<String, Vec<semver::Version>>::new();
+    let packages = lockfile
+        .get("package")
+        .and_then(|packages| packages.as_array_of_tables());
+    for package in packages.into_iter().flatten() {
+        let name = package.get("name").and_then(|name| name.as_str());
+        let version = package
+            .get("version")
+            .and_then(|version| version.as_str())
+            .and_then(|version| semver::Version::parse(version).ok());
+        if let (Some(name), Some(version)) = (name, version) {
+            locked.entry(name.to_owned()).or_default().push(version);
+        }
+    }
+    Ok(locked)
+}
+
+/// The requirements a published version always has, leaving out optional, target specific and
+/// dev dependencies.
+fn normal_requirements(
+    name: &str,
+    version: &semver::Version,
+) -> Result<Vec<(String, semver::VersionReq)>> {
+    registry_fetch(&format!("{name}-{version}.deps"), Endpoint::Api, |client| {
+        let dependencies = client.crate_dependencies(name, &version.to_string())?;
+        Ok(dependencies
+            .into_iter()
+            .filter(|dependency| {
+                dependency.kind == "normal" && !dependency.optional && dependency.target.is_none()
+            })
+            .filter_map(|dependency| {
+                let req = semver::VersionReq::parse(&dependency.req).ok()?;
+                Some((dependency.crate_id, req))
+            })
+            .collect())
+    })
+    .map_err(|err| {
+        anyhow!("couldnt get the requirements of {name} {version} from crates.io: {err}")
+    })
+}
+
+/// Whether cargo can never satisfy both requirements at once.
+///
+/// Cargo picks one version per semver-compatible series, so that is the case when both only
+/// allow versions of the same series, but no version in common. Requirements spanning several
+/// series never count as conflicting.
+fn requirements_conflict(a: &semver::VersionReq, b: &semver::VersionReq) -> bool {
+    let (Some(a), Some(b)) = (Interval::of(a), Interval::of(b)) else {
+        return false;
+    };
+    a.series().is_some() && a.series() == b.series() && a.intersect(&b).is_none()
+}
+
+/// The versions `low..high` a requirement allows, ignoring pre-releases.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Interval {
+    low: semver::Version,
+    /// Exclusive, `None` for no upper bound.
+    high: Option<semver::Version>,
+}
+
+impl Interval {
+    const ALL: Interval = Interval {
+        low: semver::Version::new(0, 0, 0),
+        high: None,
+    };
+
+    /// The versions `req` allows, `None` if it allows none.
+    fn of(req: &semver::VersionReq) -> Option<Interval> {
+        req.comparators
+            .iter()
+            .try_fold(Interval::ALL, |interval, comparator| {
+                interval.intersect(&Interval::of_comparator(comparator))
+            })
+    }
+
+    fn of_comparator(comparator: &semver::Comparator) -> Interval {
+        use semver::{Op, Version};
+
+        let major = comparator.major;
+        // `1.2` names every `1.2.x`, these are the first version named and the first one after.
+        let first = Version::new(
+            major,
+            comparator.minor.unwrap_or(0),
+            comparator.patch.unwrap_or(0),
+        );
+        let after = match (comparator.minor, comparator.patch) {
+            (None, _) => Version::new(major + 1, 0, 0),
+            (Some(minor), None) => Version::new(major, minor + 1, 0),
+            (Some(minor), Some(patch)) => Version::new(major, minor, patch + 1),
+        };
+        let (low, high) = match comparator.op {
+            Op::Exact | Op::Wildcard => (first, Some(after)),
+            Op::Greater => (after, None),
+            Op::GreaterEq => (first, None),
+            Op::Less => (Version::new(0, 0, 0), Some(first)),
+            Op::LessEq => (Version::new(0, 0, 0), Some(after)),
+            Op::Tilde => {
+                let high = match comparator.minor {
+                    None => Version::new(major + 1, 0, 0),
+                    Some(minor) => Version::new(major, minor + 1, 0),
+                };
+                (first, Some(high))
+            }
+            Op::Caret => {
+                let high = match (major, comparator.minor, comparator.patch) {
+                    (0, None, _) => Version::new(1, 0, 0),
+                    (0, Some(0), None) => Version::new(0, 1, 0),
+                    (0, Some(0), Some(patch)) => Version::new(0, 0, patch + 1),
+                    (0, Some(minor), _) => Version::new(0, minor + 1, 0),
+                    (major, _, _) => Version::new(major + 1, 0, 0),
+                };
+                (first, Some(high))
+            }
+            _ => return Interval::ALL,
+        };
+        Interval { low, high }
+    }
+
+    fn intersect(&self, other: &Interval) -> Option<Interval> {
+        let low = (&self.low).max(&other.low).clone();
+        let high = match (&self.high, &other.high) {
+            (Some(a), Some(b)) => Some(a.min(b).clone()),
+            (high, None) | (None, high) => high.clone(),
+        };
+        if high.as_ref().is_some_and(|high| *high <= low) {
+            return None;
+        }
+        Some(Interval { low, high })
+    }
+
+    /// The semver-compatible series all of the interval is in, if it's within one.
+    fn series(&self) -> Option<semver::Version> {
+        let low = &self.low;
+        let (start, end) = match (low.major, low.minor) {
+            (0, 0) => (
+                semver::Version::new(0, 0, low.patch),
+                semver::Version::new(0, 0, low.patch + 1),
+            ),
+            (0, minor) => (
+                semver::Version::new(0, minor, 0),
+                semver::Version::new(0, minor + 1, 0),
+            ),
+            (major, _) => (
+                semver::Version::new(major, 0, 0),
+                semver::Version::new(major + 1, 0, 0),
+            ),
+        };
+        self.high
+            .as_ref()
+            .is_some_and(|high| *high <= end)
+            .then_some(start)
+    }
+}
+
+/// One step of narrowing the published versions of a dep down to the ones `test` checks.
+struct Stage {
+    name: &'static str,
+    /// The versions this step left out, with why.
+    removed: Vec<(semver::Version, String)>,
+}
+
+impl Stage {
+    /// Remove the versions `reason` gives a reason to leave out.
+    fn filter(
+        name: &'static str,
+        versions: &mut Vec<semver::Version>,
+        reason: impl Fn(&semver::Version) -> Option<String>,
+    ) -> Stage {
+        let mut removed = Vec::new();
+        versions.retain(|version| match reason(version) {
+            Some(reason) => {
+                removed.push((version.clone(), reason));
+                false
+            }
+            None => true,
+        });
+        Stage { name, removed }
+    }
+}
+
+/// The versions of a dep that `test` checks, and the stages that picked them.
+///
+/// `explain-skip` runs the same stages to say why a version was left out.
+struct Candidates {
+    /// The published versions that are stable and not yanked, oldest first.
+    stable: Vec<semver::Version>,
+    /// The stable versions in the bound the toolchain can build, oldest first.
+    in_bound: Vec<semver::Version>,
+    /// The versions to test, oldest first. With `--adaptive` more may be added later.
+    selected: Vec<semver::Version>,
+    stages: Vec<Stage>,
+}
+
+impl Candidates {
+    fn pick(
+        cargo_toml: &DocumentMut,
+        dep: &str,
+        bound: &semver::VersionReq,
+        published: &[PublishedVersion],
+        config: &TestConfig,
+    ) -> Result<Candidates> {
+        let mut versions = published
+            .iter()
+            .map(|release| release.version.clone())
+            .collect::<Vec<_>>();
+        versions.sort();
+        let mut stages = Vec::new();
+
+        stages.push(Stage::filter("yanked", &mut versions, |version| {
+            published
+                .iter()
+                .any(|release| release.yanked && release.version == *version)
+                .then(|| "it is yanked".to_owned())
+        }));
+        stages.push(Stage::filter("prerelease", &mut versions, |version| {
+            (!version.pre.is_empty()).then(|| "it is a prerelease".to_owned())
+        }));
+        let stable = versions.clone();
+        stages.push(Stage::filter("bound", &mut versions, |version| {
+            (!bound.matches(version)).then(|| format!("it is outside {bound}"))
+        }));
+        if let Some(toolchain) = config.rust_version.toolchain() {
+            stages.push(Stage::filter("rust-version", &mut versions, |version| {
+                let release = published
+                    .iter()
+                    .find(|release| release.version == *version)?;
+                let needed = release.needs_newer_rust(toolchain)?;
+                Some(format!(
+                    "it needs Rust {needed}, the toolchain is {toolchain}"
+                ))
+            }));
+        }
+        let in_bound = versions.clone();
+        if in_bound.is_empty() {
+            return Ok(Candidates {
+                stable,
+                in_bound,
+                selected: versions,
+                stages,
+            });
+        }
+
+        // Watch reruns what failed last time instead of sampling the bound.
+        if let Some(focus) = &config.focus {
+            let failing = focus
+                .get(dep)
+                .filter(|failing| failing.iter().any(|version| in_bound.contains(version)));
+            stages.push(Stage::filter("focus", &mut versions, |version| {
+                let kept = match failing {
+                    Some(failing) => failing.contains(version),
+                    None => version == &in_bound[0] || version == &in_bound[in_bound.len() - 1],
+                };
+                (!kept).then(|| "watch only reruns failures and the edges".to_owned())
+            }));
+            return Ok(Candidates {
+                stable,
+                in_bound,
+                selected: versions,
+                stages,
+            });
+        }
+
+        let granularity = if config.adaptive {
+            Granularity::Major
+        } else {
+            config.granularity()
+        };
+        if config.extremes_only {
+            let (oldest, newest) = (&in_bound[0], &in_bound[in_bound.len() - 1]);
+            stages.push(Stage::filter("extremes", &mut versions, |version| {
+                (version != oldest && version != newest)
+                    .then(|| "--extremes-only tests the oldest and newest version".to_owned())
+            }));
+        } else {
+            let picked = select_versions(&in_bound, granularity, config.sample);
+            stages.push(Stage::filter("granularity", &mut versions, |version| {
+                if picked.contains(version) {
+                    return None;
+                }
+                let instead = picked
+                    .iter()
+                    .filter(|picked| granularity.same_series(picked, version))
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>();
+                if instead.is_empty() {
+                    return None;
+                }
+                Some(format!(
+                    "{granularity} granularity tests {} for its series",
+                    instead.join(" and ")
+                ))
+            }));
+            if let Some(per_major) = config.per_major {
+                let spread = spread_minors(&versions, per_major as usize);
+                stages.push(Stage::filter("per-major", &mut versions, |version| {
+                    (!spread.contains(version)).then(|| {
+                        format!(
+                            "--per-major tests {per_major} minors of {}.x, spread evenly",
+                            version.major
+                        )
+                    })
+                }));
+            }
+        }
+
+        let wildcard = bound.comparators.is_empty() && !config.extremes_only;
+        let max_versions = config
+            .max_versions
+            .or(wildcard.then_some(WILDCARD_MAX_VERSIONS));
+        if let Some(max_versions) = max_versions {
+            let mut thinned = versions.clone();
+            thin_out(&mut thinned, max_versions as usize);
+            stages.push(Stage::filter("max versions", &mut versions, |version| {
+                (!thinned.contains(version))
+                    .then(|| format!("only {max_versions} versions are tested, spread evenly"))
+            }));
+        }
+
+        if config.preflight {
+            let unresolvable = find_unresolvable(cargo_toml, dep, &versions)?;
+            stages.push(Stage::filter("preflight", &mut versions, |version| {
+                unresolvable
+                    .iter()
+                    .find(|(skipped, _)| skipped == version)
+                    .map(|(_, reason)| reason.clone())
+            }));
+        }
+
+        // A patched version may not be published yet, and is what the user wants to see.
+        let mut in_bound = in_bound;
+        for patch in &config.patch_version {
+            if patch.dep == dep && bound.matches(&patch.version) {
+                for list in [&mut in_bound, &mut versions] {
+                    if !list.contains(&patch.version) {
+                        list.push(patch.version.clone());
+                        list.sort();
+                    }
+                }
+            }
+        }
+
+        Ok(Candidates {
+            stable,
+            in_bound,
+            selected: versions,
+            stages,
+        })
+    }
+
+    /// The versions the stage called `name` left out.
+    fn removed_by(&self, name: &str) -> Vec<(semver::Version, String)> {
+        self.stages
+            .iter()
+            .filter(|stage| stage.name == name)
+            .flat_map(|stage| stage.removed.iter().cloned())
+            .collect()
+    }
+}
+
+/// Print the verdict of every selection stage for one version.
+fn explain_skip(config: &ExplainConfig) -> Result<()> {
+    let _ = CARGO_VERSION.set(probe_cargo(None)?);
+    let dep = &config.name;
+    let version = &config.version;
+    let cargo_toml = fs::read_to_string("Cargo.toml")?.parse::<DocumentMut>()?;
+    let declared = select_deps(declared_deps(&cargo_toml)?, Some(dep))?.remove(0);
+    let dep = &declared.name;
+    let bound = declared
+        .requirement(&cargo_toml)
+        .ok_or(anyhow!("{dep} has no version requirement"))?;
+    let bound = parse_bound(dep, &bound)?;
+    out!("{} {} in {}", dep.blue(), version.yellow(), bound.yellow());
+
+    if Metadata::load(&cargo_toml)?.policy(dep) == DepPolicy::Exact {
+        let verdict = match exact_pin(&bound) {
+            Some(pin) if pin == *version => "would be TESTED".green().to_string(),
+            _ => "would be SKIPPED, only the exact pin is checked"
+                .red()
+                .to_string(),
+        };
+        out!("  {:<14} {}", "policy", "exact".bright_black());
+        out!("{verdict}");
+        return Ok(());
+    }
+
+    let published = fetch_published(&cargo_toml, dep)?;
+    if !published.iter().any(|release| release.version == *version) {
+        out!("  {:<14} {}", "published", "no".red());
+        out!("{}", "would be SKIPPED, it isnt published".red());
+        return Ok(());
+    }
+    out!("  {:<14} {}", "published", "yes".green());
+
+    let mut test = config.test.clone();
+    if test.patch {
+        test.minor = true;
+    }
+    let candidates = Candidates::pick(&cargo_toml, dep, &bound, &published, &test)?;
+    for stage in &candidates.stages {
+        let Some((_, reason)) = stage.removed.iter().find(|(removed, _)| removed == version) else {
+            out!("  {:<14} {}", stage.name, "kept".green());
+            continue;
+        };
+        out!("  {:<14} {}", stage.name, reason.red());
+        out!("{}", format!("would be SKIPPED by {}", stage.name).red());
+        if stage.name == "granularity" && test.adaptive {
+            out!(
+                "{}",
+                "--adaptive may still test it when the checks are fast enough".bright_black()
+            );
+        }
+        return Ok(());
+    }
+    out!("{}", "would be TESTED".green());
+    Ok(())
+}
+
+/// An entry of the `matrix` include list.
+#[derive(Serialize)]
+struct MatrixEntry {
+    dep: String,
+    version: semver::Version,
+}
+
+/// Print the oldest and newest version of each bound as a CI matrix include list.
+///
+/// Deps with an exact pin only get their pin, deps without a version requirement are left out.
+fn matrix(config: &MatrixConfig) -> Result<()> {
+    let _ = CARGO_VERSION.set(probe_cargo(None)?);
+    let cargo_toml = fs::read_to_string("Cargo.toml")?.parse::<DocumentMut>()?;
+    let metadata = Metadata::load(&cargo_toml)?;
+    let deps = declared_deps(&cargo_toml)?;
+    if deps.is_empty() {
+        return Err(anyhow!("No dependencies"));
+    }
+    for dep in &config.dep {
+        if !deps.iter().any(|declared| declared.name == *dep) {
+            return Err(anyhow!("dep {dep} not found."));
+        }
+    }
+
+    let mut entries = Vec::new();
+    for declared in &deps {
+        let dep = declared.name.as_str();
+        if !config.dep.is_empty() && !config.dep.iter().any(|name| name == dep) {
+            continue;
+        }
+        let Some(bound) = declared.requirement(&cargo_toml) else {
+            continue;
+        };
+        let bound = parse_bound(dep, &bound)?;
+        if metadata.policy(dep) == DepPolicy::Exact
+            && let Some(pin) = exact_pin(&bound)
+        {
+            entries.push(MatrixEntry {
+                dep: dep.to_owned(),
+                version: pin,
+            });
+            continue;
+        }
+
+        let Some(published) = published_versions(&cargo_toml, dep)? else {
+            continue;
+        };
+        let toolchain = config.rust_version.toolchain();
+        let mut in_bound = published
+            .iter()
+            .filter(|release| !release.yanked && release.version.pre.is_empty())
+            .filter(|release| bound.matches(&release.version))
+            .filter(|release| {
+                toolchain.is_none_or(|toolchain| release.needs_newer_rust(toolchain).is_none())
+            })
+            .map(|release| release.version.clone())
+            .collect::<Vec<_>>();
+        in_bound.sort();
+        let versions = match (in_bound.first(), in_bound.last()) {
+            (Some(_), Some(_)) if config.minor => {
+                select_versions(&in_bound, Granularity::Minor, Sample::First)
+            }
+            (Some(oldest), Some(newest)) if oldest == newest => vec![oldest.clone()],
+            (Some(oldest), Some(newest)) => vec![oldest.clone(), newest.clone()],
+            _ => return Err(anyhow!("{dep} {bound} matches no published version")),
+        };
+        entries.extend(versions.into_iter().map(|version| MatrixEntry {
+            dep: dep.to_owned(),
+            version,
+        }));
+    }
+
+    match config.format {
+        MatrixFormat::Json => println!("{}", serde_json::to_string(&entries)?),
+        MatrixFormat::Yaml => {
+            for entry in &entries {
+                println!("- dep: {}\n  version: \"{}\"", entry.dep, entry.version);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Check every requirement against the [`LintRule`]s, failing when a denied one is found.
+///
+/// `--allow`, `--warn` and `--deny` win over the `lints` table of the metadata, in that order.
+fn lint(config: &LintConfig) -> Result<()> {
+    let manifest = fs::read_to_string("Cargo.toml")?;
+    let cargo_toml = manifest.parse::<DocumentMut>()?;
+    let metadata = Metadata::load(&cargo_toml)?;
+    let deps = declared_deps(&cargo_toml)?;
+    if deps.is_empty() {
+        return Err(anyhow!("No dependencies"));
+    }
+    let mut levels = metadata.lints.clone();
+    for (rules, level) in [
+        (&config.allow, LintLevel::Allow),
+        (&config.warn, LintLevel::Warn),
+        (&config.deny, LintLevel::Deny),
+    ] {
+        for rule in rules {
+            levels.insert(*rule, level);
+        }
+    }
+    let level = |rule: LintRule| levels.get(&rule).copied().unwrap_or(rule.default_level());
+    let lines = dep_lines(&manifest);
+
+    let mut denied = 0;
+    let mut found = 0;
+    for declared in &deps {
+        let dep = declared.name.as_str();
+        let Some(requirement) = declared.requirement(&cargo_toml) else {
+            continue;
+        };
+        let bound = parse_bound(dep, &requirement)?;
+        let mut findings = Vec::new();
+        if exact_pin(&bound).is_some() && metadata.policy(dep) != DepPolicy::Exact {
+            findings.push((
+                LintRule::ExactPin,
+                "is an exact pin, users cant pick any other version".to_owned(),
+            ));
+        }
+        if bound.comparators.is_empty() {
+            findings.push((
+                LintRule::Wildcard,
+                "allows every release, including ones it cant build with".to_owned(),
+            ));
+        }
+        if let [comparator] = bound.comparators.as_slice()
+            && comparator.major == 0
+            && comparator.minor.is_none()
+        {
+            findings.push((
+                LintRule::BareZero,
+                "allows every 0.x release, but each 0.x minor is a breaking release".to_owned(),
+            ));
+        }
+        let registry_rules = [LintRule::NoMatch, LintRule::YankedMinimum];
+        if registry_rules
+            .iter()
+            .any(|rule| level(*rule) != LintLevel::Allow)
+            && let Some(published) = published_versions(&cargo_toml, dep)?
+        {
+            let oldest = published
+                .iter()
+                .filter(|release| bound.matches(&release.version))
+                .min_by(|a, b| a.version.cmp(&b.version));
+            let usable = published
+                .iter()
+                .filter(|release| !release.yanked && bound.matches(&release.version))
+                .min_by(|a, b| a.version.cmp(&b.version));
+            match (oldest, usable) {
+                (_, None) => findings.push((
+                    LintRule::NoMatch,
+                    "matches no published version that isnt yanked".to_owned(),
+                )),
+                (Some(oldest), Some(usable)) if oldest.yanked => findings.push((
+                    LintRule::YankedMinimum,
+                    format!(
+                        "allows {} which was yanked, the oldest usable version is {}",
+                        oldest.version, usable.version
+                    ),
+                )),
+                _ => {}
+            }
+        }
+
+        let line = lines
+            .get(dep)
+            .map(|line| format!("Cargo.toml:{line}"))
+            .unwrap_or_else(|| "Cargo.toml".to_owned());
+        for (rule, message) in findings {
+            let label = match level(rule) {
+                LintLevel::Allow => continue,
+                LintLevel::Warn => format!("warning[{}]:", rule.name()).yellow().to_string(),
+                LintLevel::Deny => {
+                    denied += 1;
+                    format!("error[{}]:", rule.name()).red().to_string()
+                }
+            };
+            found += 1;
+            out!("{label} {} {} {message}", dep.blue(), requirement.yellow());
+            out!("  {} {}", "-->".bright_black(), line.bright_black());
+        }
+    }
+
+    if denied != 0 {
+        return Err(anyhow!("{denied} of {found} findings are denied"));
+    }
+    if found == 0 {
+        out!("{}", "No findings".green());
+    }
+    Ok(())
+}
+
+/// How one of the checks of `doctor` went.
+#[derive(Clone, Copy, PartialEq)]
+enum Health {
+    Ok,
+    Warn,
+    Fail,
+}
+
+/// Check the environment a run depends on, failing when something it cant do without is missing.
+///
+/// Each problem comes with a hint on how to fix it.
+fn doctor() -> Result<()> {
+    let mut checks = 0;
+    let mut failed = 0;
+    let mut report = |health: Health, message: String, hint: Option<&str>| {
+        checks += 1;
+        let label = match health {
+            Health::Ok => "ok".green().to_string(),
+            Health::Warn => "warning:".yellow().to_string(),
+            Health::Fail => {
+                failed += 1;
+                "error:".red().to_string()
+            }
+        };
+        out!("{label} {message}");
+        if let Some(hint) = hint {
+            out!("  {} {}", "-->".bright_black(), hint.bright_black());
+        }
+    };
+
+    match probe_cargo(None) {
+        Ok(version) => report(Health::Ok, format!("cargo {version}"), None),
+        Err(err) => report(
+            Health::Fail,
+            err.to_string(),
+            Some("install Rust from https://rustup.rs"),
+        ),
+    }
+    match Command::new("rustup").args(["toolchain", "list"]).output() {
+        Ok(output) if output.status.success() => {
+            let toolchains = String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .filter_map(|line| line.split_whitespace().next())
+                .map(str::to_owned)
+                .collect::<Vec<_>>();
+            report(
+                Health::Ok,
+                format!("toolchains {}", toolchains.join(", ")),
+                None,
+            );
+            if !toolchains
+                .iter()
+                .any(|toolchain| toolchain.starts_with("nightly"))
+            {
+                report(
+                    Health::Warn,
+                    "no nightly toolchain, which `--minimal-versions` and `minimize --verify-minimal` need".to_owned(),
+                    Some("install one with `rustup toolchain install nightly`"),
+                );
+            }
+        }
+        _ => report(
+            Health::Warn,
+            "rustup wasnt found, so `--toolchain` and `--target` cant be checked before the run"
+                .to_owned(),
+            None,
+        ),
+    }
+
+    let offline = std::env::var("CARGO_NET_OFFLINE").is_ok_and(|offline| offline == "true")
+        || cargo_configs()?.iter().any(|(_, config)| {
+            config
+                .get("net")
+                .and_then(|net| net.get("offline"))
+                .and_then(|offline| offline.as_bool())
+                == Some(true)
+        });
+    if offline {
+        report(
+            Health::Warn,
+            "cargo is set to work offline, so only versions it already downloaded can be checked"
+                .to_owned(),
+            Some("unset CARGO_NET_OFFLINE or `net.offline` in .cargo/config.toml"),
+        );
+    }
+    match fetch_sparse(&format!("{CRATES_IO_INDEX}/config.json"), None) {
+        Ok(_) => report(Health::Ok, format!("reached {CRATES_IO_INDEX}"), None),
+        Err(err) => report(
+            Health::Fail,
+            format!("couldnt reach {CRATES_IO_INDEX} ({err})"),
+            Some(
+                "the versions of each dependency come from there, check the network and HTTPS_PROXY",
+            ),
+        ),
+    }
+    let manifest = fs::read_to_string("Cargo.toml")?.parse::<DocumentMut>()?;
+    let mut registries = declared_deps(&manifest)?
+        .iter()
+        .filter_map(|declared| dep_registry(&manifest, &declared.name))
+        .collect::<Vec<_>>();
+    registries.sort();
+    registries.dedup();
+    for registry in registries {
+        let index = match registry_index(&registry) {
+            Ok(index) => index,
+            Err(err) => {
+                report(Health::Fail, format!("registry {registry} has {err}"), None);
+                continue;
+            }
+        };
+        let Some(url) = index.strip_prefix("sparse+") else {
+            report(
+                Health::Ok,
+                format!(
+                    "registry {registry} uses the git index {index}, which is fetched when a run reaches it"
+                ),
+                None,
+            );
+            continue;
+        };
+        let config = format!("{}/config.json", url.trim_end_matches('/'));
+        match fetch_sparse(&config, registry_token(&registry)) {
+            Ok(_) => report(
+                Health::Ok,
+                format!("reached registry {registry} at {url}"),
+                None,
+            ),
+            Err(err) => report(
+                Health::Fail,
+                format!("couldnt reach registry {registry} at {url} ({err})"),
+                Some(
+                    "private registries need a token in CARGO_REGISTRIES_<NAME>_TOKEN or ~/.cargo/credentials.toml",
+                ),
+            ),
+        }
+    }
+
+    let writable = |dir: &Path| -> Result<()> {
+        fs::create_dir_all(dir)?;
+        let probe = dir.join(format!(".doctor-{}", std::process::id()));
+        fs::write(&probe, "")?;
+        fs::remove_file(&probe)?;
+        Ok(())
+    };
+    let project = std::env::current_dir()?;
+    let state = std::path::absolute(&paths().root)?;
+    for (dir, hint) in [
+        (
+            project.as_path(),
+            "the manifest and lockfile are edited here for every check",
+        ),
+        (&state, "keep the state elsewhere with --state-dir"),
+        (
+            &paths().target(),
+            "build elsewhere with --target-dir or CARGO_TARGET_DIR",
+        ),
+    ] {
+        match writable(dir) {
+            Ok(()) => report(Health::Ok, format!("can write to {}", dir.display()), None),
+            Err(err) => report(
+                Health::Fail,
+                format!("cant write to {} ({err})", dir.display()),
+                Some(hint),
+            ),
+        }
+    }
+
+    let workspace = fs::read_to_string(workspace_root().join("Cargo.toml"))?;
+    let patched = patched_crates(&workspace.parse::<DocumentMut>()?);
+    if !patched.is_empty() {
+        let names = patched
+            .iter()
+            .map(|(name, section)| format!("{} ({section})", name.blue()))
+            .collect::<Vec<_>>()
+            .join(", ");
+        report(
+            Health::Warn,
+            format!("the workspace overrides {names}, which are skipped"),
+            Some("pass --ignore-patches to test them without the overrides"),
+        );
+    }
+    for (path, config) in cargo_configs()? {
+        if config.contains_key("patch") {
+            report(
+                Health::Warn,
+                format!(
+                    "{} has a [patch] section, which every check builds with",
+                    path.display()
+                ),
+                Some(
+                    "move it out of the way while testing, --ignore-patches only covers the manifest",
+                ),
+            );
+        }
+        let sources = config
+            .get("source")
+            .and_then(|sources| sources.as_table_like());
+        for (source, entry) in sources.into_iter().flat_map(|sources| sources.iter()) {
+            if let Some(replacement) = entry.get("replace-with").and_then(|with| with.as_str()) {
+                report(
+                    Health::Warn,
+                    format!(
+                        "{} replaces the {source} source with {replacement}, so the checks build what it serves",
+                        path.display()
+                    ),
+                    Some(
+                        "versions missing from the replacement fail to resolve and are reported as failing",
+                    ),
+                );
+            }
+        }
+    }
+
+    if paths().snapshot().exists() {
+        report(
+            Health::Fail,
+            "an earlier run left the manifest pinned".to_owned(),
+            Some("put it back with `cargo bounds restore`"),
+        );
+    }
+    if let Ok(holder) = fs::read_to_string(paths().lock()) {
+        match holder
+            .split_whitespace()
+            .next()
+            .and_then(|pid| pid.parse::<u32>().ok())
+        {
+            Some(pid) if process_alive(pid) => report(
+                Health::Warn,
+                format!("another cargo-bounds run (pid {pid}) holds the lock"),
+                Some("runs started now fail, unless they --wait for it"),
+            ),
+            _ => report(
+                Health::Warn,
+                format!("the lock at {} was left behind", paths().lock().display()),
+                Some("the next run removes it"),
+            ),
+        }
+    }
+
+    if failed != 0 {
+        return Err(anyhow!("{failed} of {checks} checks failed"));
+    }
+    out!("{}", "Ready to run".green());
+    Ok(())
+}
+
+/// Print the failing versions of each of `names`, then the ones that dont fail for all of them.
+///
+/// `failing` holds the names each version failed for, shown with `prefix` in front.
+fn print_breakdown(
+    title: &str,
+    partial_title: &str,
+    names: &[String],
+    failing: &[(&str, &semver::Version, &[String])],
+    prefix: &str,
+) {
+    if names.is_empty() {
+        return;
+    }
+    out!("{title}");
+    for name in names {
+        let failed = failing
+            .iter()
+            .filter(|(_, _, failed)| failed.contains(name))
+            .map(|(dep, version, _)| format!("{} {}", dep.blue(), version.red()))
+            .collect::<Vec<_>>();
+        let name = format!("{prefix}{name}");
+        if failed.is_empty() {
+            out!("  {} {}", name.yellow(), "all passed".green());
+        } else {
+            out!("  {} {}", name.yellow(), failed.join(", "));
+        }
+    }
+    let partial = failing
+        .iter()
+        .filter(|(_, _, failed)| failed.len() < names.len())
+        .collect::<Vec<_>>();
+    if partial.is_empty() {
+        return;
+    }
+    out!("{partial_title}");
+    for (dep, version, failed) in partial {
+        out!(
+            "  {} {} - {prefix}{}",
+            dep.blue(),
+            version.red(),
+            failed.join(&format!(", {prefix}")).yellow()
+        );
+    }
+}
+
+/// A version that was tested.
+struct TestedVersion {
+    version: semver::Version,
+    result: TestResult,
+    duration: Duration,
+    /// The `--each-feature` configurations that failed.
+    failed_variants: Vec<String>,
+    /// The toolchains it failed on, when there were several.
+    failed_toolchains: Vec<String>,
+    /// The targets it failed for, when there were several.
+    failed_targets: Vec<String>,
+    /// Why it failed or is indeterminate, when this run found out.
+    failure: Option<FailureKind>,
+    /// How often it was tried again after a network error.
+    retries: u32,
+    /// The logs of its checks that were kept.
+    logs: Vec<PathBuf>,
+}
+
+/// Test the versions in `to_test`, adding their results to `results`.
+///
+/// Versions that are in neither `to_test` nor `done` are printed as skipped with `--print-skiped`.
+/// Returns the versions of `to_test` that `--fail-fast` left untested.
+/// When exploring the newest versions go first, and testing stops after `--max-failures`.
+fn test_selected(
+    cargo_toml: &mut DocumentMut,
+    dep: &str,
+    versions: &[semver::Version],
+    to_test: &[semver::Version],
+    done: &[semver::Version],
+    config: &TestConfig,
+    results: &mut Vec<TestedVersion>,
+) -> Result<Vec<semver::Version>> {
+    let mut versions = versions.iter().collect::<Vec<_>>();
+    // The versions were picked from the whole bound already, so the order doesnt change which.
+    if config.explore || config.reverse {
+        versions.reverse();
+    }
+
+    let mut untested = Vec::new();
+    for version in versions {
+        if !untested.is_empty() {
+            if to_test.contains(version) {
+                untested.push(version.clone());
+            }
+            continue;
+        }
+        if !to_test.contains(version) {
+            if config.print_skiped && !done.contains(version) {
+                out!("  {}", version.bright_black());
+            }
+            continue;
+        }
+
+        let fails = results
+            .iter()
+            .filter(|tested| tested.result == TestResult::Fail)
+            .count();
+        if config.explore && config.max_failures.is_some_and(|max| fails >= max) {
+            out!(
+                "  {}",
+                format!("Stopping after {fails} failures").bright_black()
+            );
+            break;
+        }
+        if config.fail_fast.is_some() && fails > 0 {
+            untested.push(version.clone());
+            continue;
+        }
+
+        let start = Instant::now();
+        let (result, failed) = test_variants(cargo_toml, dep, version, config)?;
+        let mut failed_variants = Vec::new();
+        let mut failed_toolchains = Vec::new();
+        let mut failed_targets = Vec::new();
+        for variant in failed {
+            if let Some(name) = variant.variant
+                && !failed_variants.contains(&name)
+            {
+                failed_variants.push(name);
+            }
+            if let Some(toolchain) = variant.on_toolchain
+                && !failed_toolchains.contains(&toolchain)
+            {
+                failed_toolchains.push(toolchain);
+            }
+            if let Some(target) = variant.on_target
+                && !failed_targets.contains(&target)
+            {
+                failed_targets.push(target);
+            }
+        }
+        results.push(TestedVersion {
+            version: version.clone(),
+            result,
+            duration: start.elapsed(),
+            failed_variants,
+            failed_toolchains,
+            failed_targets,
+            failure: (result != TestResult::Sucess)
+                .then(|| check_note(dep, version).failure)
+                .flatten(),
+            retries: check_note(dep, version).retries,
+            logs: check_note(dep, version).logs,
+        });
+    }
+    if !untested.is_empty() {
+        out!(
+            "  {}",
+            format!(
+                "Stopping at the first failure, {} versions not tested",
+                untested.len()
+            )
+            .bright_black()
+        );
+    }
+    Ok(untested)
+}
+
+/// Find the features that enable an optional dependency.
+///
+/// These are the features listing `dep:foo` or `foo/feature`, or the implicit `foo` feature
+/// when no feature uses `dep:foo`.
+fn enabling_features(cargo_toml: &DocumentMut, dep: &str) -> Vec<String> {
+    let Some(features) = cargo_toml
+        .get("features")
+        .and_then(|features| features.as_table_like())
+    else {
+        return vec![dep.to_owned()];
+    };
+
+    let explicit = format!("dep:{dep}");
+    let enables_dep = format!("{dep}/");
+    let mut implicit = true;
+    let mut enabling = Vec::new();
+    for (feature, enables) in features.iter() {
+        let Some(enables) = enables.as_array() else {
+            continue;
+        };
+        let mut enables_it = false;
+        for enabled in enables.iter().filter_map(|enabled| enabled.as_str()) {
+            if enabled == explicit {
+                implicit = false;
+            }
+            enables_it |= enabled == explicit || enabled.starts_with(&enables_dep);
+        }
+        if enables_it {
+            enabling.push(feature.to_owned());
+        }
+    }
+
+    if implicit && !features.contains_key(dep) {
+        enabling.push(dep.to_owned());
+    }
+    enabling
+}
+
+/// Validate a dependency with the `exact` policy.
+///
+/// Checks that the pinned version is published, not yanked and still builds.
+/// A newer release in the same compatibility bucket is reported but doesnt count as a failure.
+fn validate_pin(
+    cargo_toml: &mut DocumentMut,
+    dep: &str,
+    bound: &semver::VersionReq,
+    config: &TestConfig,
+) -> Result<usize> {
+    let Some(pinned) = exact_pin(bound) else {
+        out!(
+            "  {} {}",
+            "Policy is exact, but the requirement isnt an exact pin:".red(),
+            bound.yellow()
+        );
+        return Ok(1);
+    };
+
+    let Some(published) = published_versions(cargo_toml, dep)? else {
+        return Ok(0);
+    };
+    let Some(release) = published.iter().find(|release| release.version == pinned) else {
+        out!("  {} {}", pinned.red(), "is not published".red());
+        return Ok(1);
+    };
+    if release.yanked {
+        out!("  {} {}", pinned.red(), "is yanked".red());
+        return Ok(1);
+    }
+
+    let compatible = semver::VersionReq::parse(&format!("^{pinned}"))?;
+    let newest = published
+        .iter()
+        .filter(|release| !release.yanked && release.version.pre.is_empty())
+        .map(|release| &release.version)
+        .filter(|version| compatible.matches(version))
+        .max();
+    if let Some(newest) = newest
+        && *newest > pinned
+    {
+        out!(
+            "  {} {} {}",
+            "Newer compatible release available:".yellow(),
+            newest.green(),
+            format!("(pinned {pinned})").bright_black()
+        );
+    }
+
+    match test_variants(cargo_toml, dep, &pinned, config)?.0 {
+        TestResult::Fail | TestResult::Indeterminate => Ok(1),
+        TestResult::Sucess => Ok(0),
+    }
+}
+
+/// Error out unless a nightly toolchain is installed, `flag` is the option that needs it.
+fn require_nightly(flag: &str) -> Result<()> {
+    let nightly = Command::new("cargo")
+        .args(["+nightly", "--version"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success());
+    if !nightly {
+        return Err(anyhow!(
+            "{flag} resolves with `cargo +nightly`, but no nightly toolchain was found. Install one with `rustup toolchain install nightly`"
+        ));
+    }
+    Ok(())
+}
+
+/// Minimize a dependency, returning the edit that would apply the found bound.
+///
+/// Returns `None` when no published version matches the current bound.
+fn minimize_dep(
+    manifest: &str,
+    declared: &DeclaredDep,
+    options: &MinimizeConfig,
+    config: &TestConfig,
+) -> Result<Option<Fix>> {
+    let dep = declared.name.as_str();
+    if declared.inherited {
+        print_inherited(dep);
+        return Ok(None);
+    }
+    if print_patched(dep) {
+        return Ok(None);
+    }
+    let mut cargo_toml = manifest.parse::<DocumentMut>()?;
+    let (old_req, toml_path) =
+        prepare_declarations(&mut cargo_toml, declared)?.ok_or(anyhow!("Expected version key"))?;
+    let bound = parse_bound(dep, &old_req)?;
+
+    out!("{} - {}", dep.blue(), bound.yellow());
+    if bound.comparators.is_empty() {
+        out!(
+            "  {} `*` allows every published version, searching all of them",
+            "warning:".yellow()
+        );
+    }
+    let mut failing = Vec::new();
+
+    let Some(published) = published_versions(&cargo_toml, dep)? else {
+        return Ok(None);
+    };
+    let mut too_new = Vec::new();
+    let mut versions = Vec::new();
+    let mut skipped = Vec::new();
+    for release in &published {
+        if release.yanked {
+            skipped.push((release.version.clone(), "yanked".to_owned()));
+            continue;
+        }
+        if !release.version.pre.is_empty() {
+            skipped.push((release.version.clone(), "prerelease".to_owned()));
+            continue;
+        }
+        match config
+            .rust_version
+            .toolchain()
+            .and_then(|toolchain| release.needs_newer_rust(toolchain))
+        {
+            Some(needed) => too_new.push((release.version.clone(), needed)),
+            None => versions.push(release.version.clone()),
+        }
+    }
+    versions.sort();
+    too_new.sort();
+    if options.print_skiped {
+        skipped.extend(
+            too_new
+                .iter()
+                .map(|(version, needed)| (version.clone(), format!("needs Rust {needed}"))),
+        );
+        skipped.sort();
+        print_skipped(&skipped);
+    }
+
+    let mut current_supported = versions.clone();
+    current_supported.retain(|version| bound.matches(version));
+    if current_supported.is_empty() {
+        print_no_match(dep, &bound, &published, config.rust_version.toolchain());
+        NO_MATCH_DEPS.lock().unwrap().push(dep.to_owned());
+        return Ok(None);
+    }
+
+    // The limits only keep the search out, the current bound is where it starts either way.
+    let floor = VersionLimit::find(&options.floor, dep)
+        .map(|floor| floor.min(&current_supported[0]).clone())
+        .filter(|floor| *floor > versions[0]);
+    let ceiling = VersionLimit::find(&options.ceiling, dep)
+        .map(|ceiling| {
+            ceiling
+                .max(&current_supported[current_supported.len() - 1])
+                .clone()
+        })
+        .filter(|ceiling| *ceiling < versions[versions.len() - 1]);
+    if let Some(floor) = &floor {
+        versions.retain(|version| version >= floor);
+    }
+    if let Some(ceiling) = &ceiling {
+        versions.retain(|version| version <= ceiling);
+    }
+
+    let min_index = versions.partition_point(|version| *version < current_supported[0]);
+    let max_index = versions.partition_point(|version| Some(version) < current_supported.last());
+    // With one side the sanity check only covers what was searched.
+    let explored = if options.lower_only {
+        &versions[..=min_index]
+    } else if options.upper_only {
+        &versions[max_index..]
+    } else {
+        &current_supported[..]
+    };
+    grow_run_bar(minimize_estimate(
+        min_index + 1,
+        versions.len() - max_index,
+        explored,
+        options,
+    ));
+
+    let mut verified;
+    let found = {
+        let check =
+            |version: &semver::Version| test_version(&mut cargo_toml, dep, version.clone(), config);
+        let progress = &mut MinimizeProgress::new(dep, &mut failing);
+        let found = if options.lower_only {
+            lower_bound(&old_req, &versions, check, progress)?
+        } else if options.upper_only {
+            raise_bound(&old_req, &versions, check, progress)?
+        } else {
+            minimize_bound(&old_req, &versions, check, progress)?
+        };
+        verified = std::mem::take(&mut progress.passing);
+        found
+    };
+    let Some(found) = found else {
+        unreachable!("the bound matches a version");
+    };
+    let SuggestedBound {
+        min: min_version,
+        max: max_version,
+        requirement: new_req,
+    } = found;
+    // On a newer compiler the maximum could be higher.
+    if max_version == versions[versions.len() - 1]
+        && ceiling.is_none()
+        && let Some((next, needed)) = too_new.iter().find(|(version, _)| *version > max_version)
+    {
+        out!(
+            "  {} the max is limited by rust-version, not by a breaking change: {} needs Rust {needed}",
+            "note:".bright_black(),
+            next.yellow()
+        );
+    }
+
+    if floor.is_some() && min_version == versions[0] {
+        out!(
+            "  {} the min stopped at {} because of --floor, older versions might work too",
+            "note:".bright_black(),
+            min_version.yellow()
+        );
+    }
+    if ceiling.is_some() && max_version == versions[versions.len() - 1] {
+        out!(
+            "  {} the max stopped at {} because of --ceiling, newer versions might work too",
+            "note:".bright_black(),
+            max_version.yellow()
+        );
+    }
+
+    let bound = semver::VersionReq::parse(&new_req)?;
+    let in_range = versions
+        .iter()
+        .filter(|version| **version >= min_version && **version <= max_version)
+        .cloned()
+        .collect::<Vec<_>>();
+    let series = breaking_series(&in_range);
+    let spans = match series.len() {
+        1 => String::new(),
+        count => format!(" (spans {count} breaking series)")
+            .bright_black()
+            .to_string(),
+    };
+    out!(
+        "  Found range {}, suggesting {}{spans}",
+        format!(">={min_version}, <={max_version}").yellow(),
+        new_req.green()
+    );
+    let mut fix = Fix {
+        dep: dep.to_owned(),
+        manifest: std::env::current_dir()?.join("Cargo.toml"),
+        toml_path,
+        old: old_req,
+        new: new_req,
+        evidence: Evidence {
+            verified_min: min_version.clone(),
+            verified_max: max_version.clone(),
+            failing: Vec::new(),
+            minimal_versions_min: None,
+        },
+    };
+    if options.verify_minimal {
+        fix.evidence.minimal_versions_min = verify_minimal(
+            &mut cargo_toml,
+            dep,
+            &versions,
+            (&min_version, &max_version),
+            &fix.old,
+            config,
+        )?;
+    }
+    if options.skip_sanity {
+        failing.sort();
+        failing.dedup();
+        fix.evidence.failing = failing;
+        if options.zerover_strict && !all_series_verified(dep, &series, &verified) {
+            return Ok(None);
+        }
+        return Ok(Some(fix));
+    }
+    // The side that wasnt searched keeps its bound, so it isnt checked either.
+    let (old_min, old_max) = (
+        &current_supported[0],
+        &current_supported[current_supported.len() - 1],
+    );
+    let (sanity_min, sanity_max) = if options.lower_only {
+        (&min_version, old_min.max(&min_version))
+    } else if options.upper_only {
+        (old_max.min(&max_version), &max_version)
+    } else {
+        (&min_version, &max_version)
+    };
+    let checked = match options.lower_only || options.upper_only {
+        true => format!(">={sanity_min}, <={sanity_max}")
+            .green()
+            .to_string(),
+        false => bound.green().to_string(),
+    };
+    let granularity = options.sanity_granularity();
+    if granularity == Granularity::Patch {
+        out!("  Checking every version of {checked}");
+    } else {
+        out!("  Doing sanity check of {checked}");
+    }
+    let sanity_start = Instant::now();
+    let mut started = false;
+    let mut last_checked = None::<&semver::Version>;
+    for version in &versions {
+        if version == sanity_min {
+            started = true;
+        }
+
+        if started {
+            // Per minor by default, which is one per breaking series for `0.x` and several for
+            // the rest.
+            if last_checked.is_some_and(|last| granularity.same_series(last, version)) {
+                if options.print_skiped {
+                    out!("  {}", version.bright_black());
+                }
+                continue;
+            }
+
+            let res = test_version(&mut cargo_toml, dep, version.clone(), config)?.or_fail();
+            advance_run_bar();
+            match res {
+                TestResult::Fail => failing.push(version.clone()),
+                TestResult::Sucess => verified.push(version.clone()),
+                TestResult::Indeterminate => {}
+            }
+            last_checked = Some(version);
+            if version == sanity_max {
+                break;
+            }
+        }
+    }
+    out!(
+        "  {}",
+        format!(
+            "Checked the range in {:.1}s",
+            sanity_start.elapsed().as_secs_f32()
+        )
+        .bright_black()
+    );
+    failing.sort();
+    failing.dedup();
+    let holes = failing
+        .iter()
+        .filter(|version| **version > min_version && **version < max_version)
+        .map(|version| version.to_string())
+        .collect::<Vec<_>>();
+    if !holes.is_empty() {
+        out!(
+            "  {} {}",
+            "Failing versions inside the range:".red(),
+            holes.join(", ").red()
+        );
+    }
+    fix.evidence.failing = failing;
+    if options.zerover_strict && !all_series_verified(dep, &series, &verified) {
+        return Ok(None);
+    }
+    Ok(Some(fix))
+}
+
+/// Whether a version of every `0.x` series in a range spanning several passed, printing the
+/// ones that didnt for `--zerover-strict`.
+fn all_series_verified(
+    dep: &str,
+    series: &[&semver::Version],
+    verified: &[semver::Version],
+) -> bool {
+    if series.len() < 2 {
+        return true;
+    }
+    let unverified = series
+        .iter()
+        .filter(|first| first.major == 0)
+        .filter(|first| {
+            !verified
+                .iter()
+                .any(|version| Granularity::Major.same_series(first, version))
+        })
+        .map(|first| format!("0.{}", first.minor))
+        .collect::<Vec<_>>();
+    if unverified.is_empty() {
+        return true;
+    }
+    out!(
+        "  {} the range spans {} breaking series and {} {} verified, not suggesting it",
+        "error:".red(),
+        series.len(),
+        unverified.join(", ").yellow(),
+        if unverified.len() == 1 {
+            "wasnt"
+        } else {
+            "werent"
+        }
+    );
+    UNVERIFIED_DEPS.lock().unwrap().push(dep.to_owned());
+    false
+}
+
+/// Check the found minimum again with `-Zdirect-minimal-versions` and raise it until both
+/// resolutions build, returning the minimum that does.
+///
+/// Returns `None` when not even `max` builds under minimal versions.
+fn verify_minimal(
+    cargo_toml: &mut DocumentMut,
+    dep: &str,
+    versions: &[semver::Version],
+    (min, max): (&semver::Version, &semver::Version),
+    old_req: &str,
+    config: &TestConfig,
+) -> Result<Option<semver::Version>> {
+    let minimal = TestConfig {
+        minimal_versions: Some(MinimalVersions::Direct),
+        ..config.clone()
+    };
+    let flag = MinimalVersions::Direct.flag();
+    out!("  Checking {} with {flag}", min.yellow());
+    let low = versions.partition_point(|version| version < min);
+    let high = versions.partition_point(|version| version <= max);
+    let range = &versions[low..high];
+    grow_run_bar(range.len().max(1).ilog2() as usize + 2);
+
+    let mut check = |version: &semver::Version| -> Result<TestResult> {
+        let res = test_version(cargo_toml, dep, version.clone(), &minimal)?.or_fail();
+        if res == TestResult::Fail {
+            return Ok(res);
+        }
+        // The normal resolution is only known to build at the found min and above.
+        test_version(cargo_toml, dep, version.clone(), config)
+    };
+    let res = check(min)?;
+    advance_run_bar();
+    if res == TestResult::Sucess {
+        out!("  {} also builds with {flag}", min.green());
+        return Ok(Some(min.clone()));
+    }
+    let builds_at_max = range.len() > 1 && check(max)? == TestResult::Sucess;
+    advance_run_bar();
+    if !builds_at_max {
+        out!(
+            "  {} doesnt build with {flag} with any version up to {}",
+            dep.red(),
+            max.red()
+        );
+        return Ok(None);
+    }
+    let mut failing = Vec::new();
+    let mut progress = MinimizeProgress::new(dep, &mut failing);
+    let stricter = find_boundary(range, TestResult::Sucess, &mut check, &mut progress)?;
+    progress.clear_search();
+    let requirement = restyle_requirement(old_req, &stricter, max, versions);
+    out!(
+        "  Builds from {} normally, from {} under {flag}, which would be {}",
+        min.green(),
+        stricter.yellow(),
+        requirement.yellow()
+    );
+    Ok(Some(stricter))
+}
+
+/// Roughly how many checks minimizing a dep takes, a binary search through each range that is
+/// searched plus the sanity pass over `in_bound`.
+fn minimize_estimate(
+    below: usize,
+    above: usize,
+    in_bound: &[semver::Version],
+    options: &MinimizeConfig,
+) -> usize {
+    let search = |len: usize| len.max(1).ilog2() as usize + 2;
+    let searches = if options.lower_only {
+        search(below)
+    } else if options.upper_only {
+        search(above)
+    } else {
+        search(below) + search(above)
+    };
+    let sanity = if options.skip_sanity {
+        0
+    } else {
+        let granularity = options.sanity_granularity();
+        let mut checked = in_bound.iter().collect::<Vec<_>>();
+        checked.dedup_by(|version, last| granularity.same_series(last, version));
+        checked.len()
+    };
+    searches + sanity
+}
+
+/// Shows the searches of [`minimize_bound`] and [`raise_bound`] and collects the failing versions.
+struct MinimizeProgress<'a> {
+    dep: &'a str,
+    failing: &'a mut Vec<semver::Version>,
+    /// The versions that passed.
+    passing: Vec<semver::Version>,
+    /// The line showing what is left to search, only drawn with a terminal.
+    search: Option<ProgressBar>,
+    /// When the current search started.
+    started: Instant,
+}
+
+impl<'a> MinimizeProgress<'a> {
+    fn new(dep: &'a str, failing: &'a mut Vec<semver::Version>) -> Self {
+        MinimizeProgress {
+            dep,
+            failing,
+            passing: Vec::new(),
+            search: None,
+            started: Instant::now(),
+        }
+    }
+
+    fn clear_search(&mut self) {
+        if let Some(search) = self.search.take() {
+            search.finish_and_clear();
+        }
+    }
+}
+
+impl Drop for MinimizeProgress<'_> {
+    fn drop(&mut self) {
+        self.clear_search();
+    }
+}
+
+impl Progress for MinimizeProgress<'_> {
+    fn narrowed(&mut self, low: &semver::Version, high: &semver::Version, remaining: usize) {
+        if display_mode() == DisplayMode::Plain {
+            return;
+        }
+        let search = self.search.get_or_insert_with(|| {
+            add_bar(
+                ProgressBar::new_spinner()
+                    .with_style(ProgressStyle::with_template("  {msg}").unwrap()),
+            )
+        });
+        search.set_message(
+            format!(
+                "searching {remaining} versions of {} between {low} and {high}",
+                self.dep
+            )
+            .bright_black()
+            .to_string(),
+        );
+    }
+
+    fn searching(&mut self, edge: Edge, from: &semver::Version) {
+        self.started = Instant::now();
+        match edge {
+            Edge::Min => out!("  Minimizing {}", from.yellow()),
+            Edge::Max => out!("  Maximizing {}", from.yellow()),
+        }
+    }
+
+    fn checked(&mut self, version: &semver::Version, result: TestResult) {
+        advance_run_bar();
+        match result {
+            TestResult::Fail => self.failing.push(version.clone()),
+            TestResult::Sucess => self.passing.push(version.clone()),
+            TestResult::Indeterminate => {}
+        }
+    }
+
+    fn not_monotonic(&mut self) {
+        self.clear_search();
+        out!(
+            "  {} results arent monotonic, checking every version instead",
+            "warning:".yellow()
+        );
+    }
+
+    fn found(&mut self, edge: Edge, version: &semver::Version) {
+        self.clear_search();
+        let took = format!("({:.1}s)", self.started.elapsed().as_secs_f32());
+        match edge {
+            Edge::Min => out!("  Found min {} {}", version.green(), took.bright_black()),
+            Edge::Max => out!("  Found max {} {}", version.green(), took.bright_black()),
+        }
+    }
+}
+
+/// The newest of the `stable` versions, when it is newer than everything `bound` allows.
+fn outdated_release(
+    bound: &semver::VersionReq,
+    stable: &[semver::Version],
+) -> Option<semver::Version> {
+    let latest = stable.iter().max()?;
+    let newest_allowed = stable
+        .iter()
+        .filter(|version| bound.matches(version))
+        .max()?;
+    (latest > newest_allowed).then(|| latest.clone())
+}
+
+/// What is published around a bound that matches none of the versions that get tested.
+#[derive(Clone, Debug, Serialize)]
+struct NoMatch {
+    /// The newest stable release below the bound.
+    below: Option<semver::Version>,
+    /// The oldest stable release above the bound.
+    above: Option<semver::Version>,
+    /// Releases in the bound that are left out because they are yanked.
+    yanked: Vec<semver::Version>,
+    /// Releases in the bound that are left out because they are prereleases.
+    prerelease: Vec<semver::Version>,
+    /// Releases in the bound that are left out because they need a newer Rust.
+    needs_newer_rust: Vec<semver::Version>,
+}
+
+impl NoMatch {
+    fn find(
+        bound: &semver::VersionReq,
+        published: &[PublishedVersion],
+        toolchain: Option<&semver::Version>,
+    ) -> Self {
+        let mut stable = Vec::new();
+        let mut no_match = NoMatch {
+            below: None,
+            above: None,
+            yanked: Vec::new(),
+            prerelease: Vec::new(),
+            needs_newer_rust: Vec::new(),
+        };
+        for release in published {
+            let version = release.version.clone();
+            if release.yanked {
+                if bound.matches(&version) {
+                    no_match.yanked.push(version);
+                }
+            } else if !version.pre.is_empty() {
+                // A bound only matches prereleases it names, so check by hand what it would allow.
+                let release = semver::Version {
+                    pre: semver::Prerelease::EMPTY,
+                    ..version.clone()
+                };
+                if bound.matches(&version) || bound.matches(&release) {
+                    no_match.prerelease.push(version);
+                }
+            } else if bound.matches(&version) {
+                if toolchain.is_some_and(|toolchain| release.needs_newer_rust(toolchain).is_some())
+                {
+                    no_match.needs_newer_rust.push(version);
+                }
+            } else {
+                stable.push(version);
+            }
+        }
+        let (below, above) = closest_outside(bound, &stable);
+        no_match.below = below.cloned();
+        no_match.above = above.cloned();
+        no_match.yanked.sort();
+        no_match.prerelease.sort();
+        no_match.needs_newer_rust.sort();
+        no_match
+    }
+
+    /// The closest versions, like `closest published versions: 2.2.9 (below), 3.0.0 (above)`.
+    fn closest(&self) -> String {
+        let closest = [(&self.below, "below"), (&self.above, "above")]
+            .into_iter()
+            .filter_map(|(version, side)| Some(format!("{} ({side})", version.as_ref()?)))
+            .collect::<Vec<_>>();
+        match closest.len() {
+            0 => "no stable version is published".to_owned(),
+            1 => format!("closest published version: {}", closest[0]),
+            _ => format!("closest published versions: {}", closest.join(", ")),
+        }
+    }
+
+    /// Why the versions in the bound arent tested, a line each.
+    fn excluded(&self) -> Vec<String> {
+        let reasons = [
+            (&self.yanked, "is yanked", "are yanked"),
+            (
+                &self.prerelease,
+                "is a prerelease, which isnt tested",
+                "are prereleases, which arent tested",
+            ),
+            (
+                &self.needs_newer_rust,
+                "needs a newer Rust, see --ignore-rust-version",
+                "need a newer Rust, see --ignore-rust-version",
+            ),
+        ];
+        let mut lines = Vec::new();
+        for (versions, one, many) in reasons {
+            let reason = match versions.len() {
+                0 => continue,
+                1 => one,
+                _ => many,
+            };
+            let versions = versions
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ");
+            lines.push(format!("{versions} in the bound {reason}"));
+        }
+        lines
+    }
+}
+
+/// Explain that no published version of `dep` matches its bound, and what is around it.
+///
+/// Versions that need a newer Rust than `toolchain` count as not matching.
+fn print_no_match(
+    dep: &str,
+    bound: &semver::VersionReq,
+    published: &[PublishedVersion],
+    toolchain: Option<&semver::Version>,
+) -> NoMatch {
+    let no_match = NoMatch::find(bound, published, toolchain);
+    out!(
+        "  {} {} {} matches no published version, {}",
+        "error:".red(),
+        dep.blue(),
+        bound.yellow(),
+        no_match.closest()
+    );
+    for excluded in no_match.excluded() {
+        out!("    {}", excluded.bright_black());
+    }
+    no_match
+}
+
+/// Test a version with every config of [`TestConfig::variants`].
+///
+/// It fails if any of them fails, the failing configs are returned with the result.
+fn test_variants(
+    cargo_toml: &mut DocumentMut,
+    dep: &str,
+    version: &semver::Version,
+    config: &TestConfig,
+) -> Result<(TestResult, Vec<TestConfig>)> {
+    let mut result = TestResult::Sucess;
+    let mut failed_variants = Vec::new();
+    for variant in config.variants(cargo_toml) {
+        match test_version(cargo_toml, dep, version.clone(), &variant)? {
+            TestResult::Fail => {
+                result = TestResult::Fail;
+                failed_variants.push(variant);
+            }
+            TestResult::Indeterminate if result == TestResult::Sucess => {
+                result = TestResult::Indeterminate;
+            }
+            _ => {}
+        }
+    }
+    advance_dep_bar(result);
+    Ok((result, failed_variants))
+}
+
+fn test_version(
+    cargo_toml: &mut DocumentMut,
+    dep: &str,
+    version: semver::Version,
+    config: &TestConfig,
+) -> Result<TestResult> {
+    if CANCELLED.load(Ordering::SeqCst) {
+        return Err(Cancelled.into());
+    }
+    run_bar_checking(dep, &version);
+    let dir = config.project_dir();
+    let context = config.check_context();
+    // Results with a local checkout patched in say nothing about the published version.
+    let patch = config.patch_for(dep, &version);
+    if let Some(known) = KNOWLEDGE
+        .lock()
+        .unwrap()
+        .as_mut()
+        .filter(|_| patch.is_none())
+        .and_then(|knowledge| knowledge.lookup(dep, &context, &version))
+    {
+        out!(
+            "{} {} {}",
+            config.label(dep, &version),
+            known.colored(),
+            "(reused)".bright_black()
+        );
+        return Ok(known);
+    }
+
+    match (patch, config.pin_strategy) {
+        // Only this check sees the patch, the next pin writes the manifest without it.
+        (Some(path), _) => {
+            let mut pinned = cargo_toml.clone();
+            set_requirement(&mut pinned, dep, &format!("={version}"));
+            let mut source = toml_edit::InlineTable::new();
+            source.insert("path", path.to_string_lossy().as_ref().into());
+            let patches = pinned.entry("patch").or_insert(toml_edit::table());
+            if let Some(patches) = patches.as_table_mut() {
+                patches.set_implicit(true);
+            }
+            patches["crates-io"].or_insert(toml_edit::table())[dep] = source.into();
+            write_pinned(&pinned, dir)?;
+        }
+        (None, PinStrategy::Manifest) => {
+            set_requirement(cargo_toml, dep, &format!("={version}"));
+            write_pinned(cargo_toml, dir)?;
+        }
+        (None, PinStrategy::Lockfile) => {
+            // Undo the manifest pin of an earlier version outside the requirement.
+            if fs::read_to_string(dir.join("Cargo.toml"))? != cargo_toml.to_string() {
+                write_pinned(cargo_toml, dir)?;
+            }
+            if !pin_in_lockfile(dep, &version, dir)? {
+                let mut pinned = cargo_toml.clone();
+                set_requirement(&mut pinned, dep, &format!("={version}"));
+                write_pinned(&pinned, dir)?;
+            }
+        }
+    }
+
+    if let Some(minimal) = config.minimal_versions
+        && let Some(error) = resolve_minimal(minimal, dir)?
+    {
+        out!(
+            "{} {} {}",
+            config.label(dep, &version),
+            TestResult::Fail.colored(),
+            format!("(no minimal resolution: {error})").bright_black()
+        );
+        record_failure(dep, &version, Some(FailureKind::ResolutionConflict));
+        if config.conflicts == Conflicts::Untestable {
+            print_untestable(&version);
+            return Ok(TestResult::Indeterminate);
+        }
+        if patch.is_none()
+            && let Some(knowledge) = KNOWLEDGE.lock().unwrap().as_mut()
+        {
+            knowledge.record(dep, &context, version, TestResult::Fail)?;
+        }
+        return Ok(TestResult::Fail);
+    }
+
+    let mut check = run_test(dep, &version, config)?;
+
+    // A pin to a new version has to rebuild at least our own crate,
+    // if nothing was compiled the check didnt actually see this version.
+    let previous = LAST_CHECKED
+        .lock()
+        .unwrap()
+        .insert(dir.to_owned(), (dep.to_owned(), version.clone()));
+    let changed = previous.is_none_or(|previous| previous != (dep.to_owned(), version.clone()));
+    if check.compiled == Some(0) && changed {
+        out!(
+            "  {} nothing was compiled for {}, checking again without the cache",
+            "suspicious:".yellow(),
+            version.yellow()
+        );
+        // Cleaning resolves the workspace as well, see [`resolved_version`].
+        let shutting_down = SHUTTING_DOWN.lock().unwrap();
+        if *shutting_down {
+            return Err(Cancelled.into());
+        }
+        let _ = Command::new("cargo")
+            .args([
+                "clean",
+                "--package",
+                &format!("{}@{version}", package_name(dep)),
+            ])
+            .current_dir(dir)
+            .env("CARGO_TARGET_DIR", config.target_dir())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+        drop(shutting_down);
+        check = run_test(dep, &version, config)?;
+    }
+
+    let mut tries = 0;
+    while check.failure == Some(FailureKind::Network) && tries < config.check.retries {
+        tries += 1;
+        out!(
+            "  {} ({tries}/{})…",
+            "retrying".yellow(),
+            config.check.retries
+        );
+        record_retry(dep, &version);
+        check = run_test(dep, &version, config)?;
+    }
+
+    // Cargo can unify the pin away, or a `[patch]` can replace it.
+    if let Some((resolved, local)) = resolved_version(dep, dir) {
+        if resolved != version {
+            out!(
+                "  {} {} resolved to {} instead of {}, the result says nothing about {}",
+                "indeterminate:".yellow(),
+                dep.blue(),
+                resolved.yellow(),
+                version.yellow(),
+                version.yellow()
+            );
+            return Ok(TestResult::Indeterminate);
+        }
+        if let Some(path) = patch
+            && !local
+        {
+            out!(
+                "  {} the checkout in {} wasnt used, is its version {}?",
+                "indeterminate:".yellow(),
+                path.display(),
+                version.yellow()
+            );
+            return Ok(TestResult::Indeterminate);
+        }
+    }
+
+    record_failure(dep, &version, check.failure);
+    if check.failure == Some(FailureKind::ResolutionConflict)
+        && config.conflicts == Conflicts::Untestable
+    {
+        print_untestable(&version);
+        return Ok(TestResult::Indeterminate);
+    }
+
+    if patch.is_none()
+        && let Some(knowledge) = KNOWLEDGE.lock().unwrap().as_mut()
+    {
+        knowledge.record(dep, &context, version.clone(), check.result)?;
+    }
+    if check.result == TestResult::Fail && config.keep_manifest_on_failure {
+        KEEP_MANIFEST.store(true, Ordering::SeqCst);
+        return Err(anyhow!(
+            "Stopped at the first failure, {dep} {version} (--keep-manifest-on-failure)"
+        ));
+    }
+    Ok(check.result)
+}
+
+/// Say that a version `--conflicts untestable` doesnt count as a failure.
+fn print_untestable(version: &semver::Version) {
+    out!(
+        "  {} cargo couldnt resolve the dependency graph with {} pinned, it doesnt count as a failure",
+        "untestable:".yellow(),
+        version.yellow()
+    );
+}
+
+/// The version of `dep` the current package resolved to according to `cargo metadata`, and
+/// whether it came from a local path instead of a registry.
+///
+/// `None` when that cant be told, e.g. when cargo metadata fails.
+fn resolved_version(dep: &str, dir: &Path) -> Option<(semver::Version, bool)> {
+    // Resolving writes the lockfile, which mustnt happen after a shutdown restored it.
+    let shutting_down = SHUTTING_DOWN.lock().unwrap();
+    if *shutting_down {
+        return None;
+    }
+    let output = Command::new("cargo")
+        .args(["metadata", "--format-version", "1"])
+        .current_dir(dir)
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    drop(shutting_down);
+    if !output.status.success() {
+        return None;
+    }
+    let metadata = serde_json::from_slice::<serde_json::Value>(&output.stdout).ok()?;
+    let root = metadata["resolve"]["root"].as_str()?;
+    let node = metadata["resolve"]["nodes"]
+        .as_array()?
+        .iter()
+        .find(|node| node["id"] == root)?;
+    let packages = metadata["packages"].as_array()?;
+    let lib_name = dep.replace('-', "_");
+    let package_name = package_name(dep);
+    let deps = node["deps"].as_array()?;
+    // The crate name is the rename when there is one, which tells two majors of a package
+    // apart. A library named differently than its package only matches by package name.
+    let dependency = deps
+        .iter()
+        .find(|dependency| dependency["name"] == lib_name.as_str())
+        .or_else(|| {
+            deps.iter().find(|dependency| {
+                packages.iter().any(|package| {
+                    package["id"] == dependency["pkg"] && package["name"] == package_name.as_str()
+                })
+            })
+        })?;
+    let package = packages
+        .iter()
+        .find(|package| package["id"] == dependency["pkg"])?;
+    let version = semver::Version::parse(package["version"].as_str()?).ok()?;
+    Some((version, package["source"].is_null()))
+}
+
+/// The outcome of running the check command once.
+struct Check {
+    result: TestResult,
+    /// How many crates cargo compiled or checked, if the output looked like cargo's.
+    compiled: Option<usize>,
+    /// Set when it failed.
+    failure: Option<FailureKind>,
+}
+
+fn run_test(dep: &str, version: &semver::Version, config: &TestConfig) -> Result<Check> {
+    let mut log_name = match &config.variant {
+        Some(variant) => format!("{dep}-{version}-{variant}"),
+        None => format!("{dep}-{version}"),
+    };
+    if let Some(toolchain) = &config.on_toolchain {
+        log_name = format!("{log_name}-{toolchain}");
+    }
+    if let Some(target) = &config.on_target {
+        log_name = format!("{log_name}-{target}");
+    }
+    if config.patch_for(dep, version).is_some() {
+        log_name.push_str("-patched");
+    }
+    let log_name = format!("{log_name}.log");
+    let command = check_command(config, Some((dep, version)))?;
+    let subject = format!("{} {}", dep.blue(), version.red());
+    let check = run_check(
+        config.label(dep, version),
+        &subject,
+        &log_name,
+        command,
+        config,
+    )?;
+    if check.result != TestResult::Sucess || config.keep_all_logs {
+        record_log(dep, version, config.log_dir().join(log_name));
+    }
+    Ok(check)
+}
+
+/// The command that checks the crate, `pin` is the dependency version being tested if any.
+fn check_command(config: &TestConfig, pin: Option<(&str, &semver::Version)>) -> Result<Command> {
+    let mut command;
+    if let Some(custom_command) = &config.check.command {
+        let custom_command = match pin {
+            Some((dep, version)) => expand_placeholders(custom_command, dep, version),
+            None => custom_command.clone(),
+        };
+        command = custom_check_command(&custom_command, config.check.shell.as_deref())?;
+        if let Some(toolchain) = config.active_toolchain() {
+            command.env("RUSTUP_TOOLCHAIN", toolchain);
+        }
+        if let Some(target) = config.active_target() {
+            command.env("CARGO_BOUNDS_TARGET", target);
+        }
+    } else {
+        command = Command::new("cargo");
+        if let Some(toolchain) = config.active_toolchain() {
+            command.arg(format!("+{toolchain}"));
+        }
+        command.arg(config.check.mode.subcommand());
+        if config.all_features() && cargo_supports(CargoFeature::AllFeatures) {
+            command.arg("--all-features");
+        }
+        if config.check.no_default_features {
+            command.arg("--no-default-features");
+        }
+        if !config.check.features.is_empty() {
+            command
+                .arg("--features")
+                .arg(config.check.features.join(","));
+        }
+        if let Some(target) = config.active_target() {
+            command.args(["--target", target]);
+        }
+        command.arg("--color");
+        command.arg(if color_enabled() { "always" } else { "never" });
+        // Passed as they are, no shell gets to split or unquote them.
+        command.args(&config.cargo_args);
+        if config.check.mode == Mode::Clippy && config.check.deny_warnings {
+            command.args(["--", "-D", "warnings"]);
+        }
+    }
+    for (key, value) in &config.check.env {
+        match pin {
+            Some((dep, version)) => command.env(key, expand_placeholders(value, dep, version)),
+            None => command.env(key, value),
+        };
+    }
+    if let Some((dep, version)) = pin {
+        command.env("CARGO_BOUNDS_DEP", dep);
+        command.env("CARGO_BOUNDS_VERSION", version.to_string());
+    }
+    command.current_dir(config.project_dir());
+    command.env("CARGO_TARGET_DIR", config.target_dir());
+    Ok(command)
+}
+
+/// Run a check command, showing its progress as `msg`.
+///
+/// `subject` names what failed in the compact display, and the log is written as `log_name`.
+fn run_check(
+    msg: String,
+    subject: &str,
+    log_name: &str,
+    mut command: Command,
+    config: &TestConfig,
+) -> Result<Check> {
+    let log_file = &config.log_dir().join(log_name);
+    let mut spinner = if config.check.verbose {
+        // On the same stream as the output it heads.
+        print_output(format!(
+            "{} {msg} {}",
+            "-----".bright_black(),
+            "-----".bright_black()
+        ));
+        None
+    } else {
+        Some(Spinner::new(msg.clone()))
+    };
+
+    // Started under the shutdown lock, so a shutdown either sees this check or prevents it.
+    let shutting_down = SHUTTING_DOWN.lock().unwrap();
+    if *shutting_down {
+        if let Some(spinner) = spinner {
+            spinner.finish_with_message("cancelled".bright_black().to_string());
+        }
+        return Err(Cancelled.into());
+    }
+    // In its own process group, so stopping it reaches the rustc processes cargo starts.
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut command, 0);
+    let mut child = command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let stderr = child.stderr.take().unwrap();
+    let mut stdout = child.stdout.take().unwrap();
+    let id = child.id();
+    let mut running = RUNNING_CHECKS.lock().unwrap();
+    if CANCELLED.load(Ordering::SeqCst) {
+        stop_check(&mut child);
+    }
+    running.push(child);
+    drop(running);
+    drop(shutting_down);
+    let stdout = thread::spawn(move || {
+        let mut output = String::new();
+        let _ = stdout.read_to_string(&mut output);
+        output
+    });
+
+    let mut clock = CheckClock::new();
+    let mut output = Vec::new();
+    for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+        let blocked = clock.observe(&line);
+        if spinner.is_none() {
+            print_output(line.clone());
+        }
+        output.push(line);
+        if let Some(spinner) = &mut spinner {
+            spinner.set_message(if blocked {
+                "waiting for build lock…".into()
+            } else {
+                String::new()
+            });
+            spinner.show_output(&output);
+        }
+    }
+
+    let status = {
+        let mut running = RUNNING_CHECKS.lock().unwrap();
+        let index = running
+            .iter()
+            .position(|child| child.id() == id)
+            .expect("the check is stored while it runs");
+        running.swap_remove(index)
+    }
+    .wait()?;
+    let killed = killed_by(&status);
+    let res = match (status.success(), killed) {
+        (true, _) => TestResult::Sucess,
+        (false, Some(_)) => TestResult::Indeterminate,
+        (false, None) => TestResult::Fail,
+    };
+    clock.finish();
+    let stdout = stdout.join().unwrap_or_default();
+    if CANCELLED.load(Ordering::SeqCst) {
+        if let Some(spinner) = spinner {
+            spinner.finish_with_message("cancelled".bright_black().to_string());
+        }
+        return Err(Cancelled.into());
+    }
+
+    let failure = match killed {
+        Some(_) => Some(FailureKind::Killed),
+        None => (res == TestResult::Fail).then(|| classify_failure(&output)),
+    };
+    let mut res_text = format!("{} {}", res.colored(), clock.summary().bright_black());
+    if let Some(signal) = killed {
+        let killed = match signal {
+            9 => "(killed by SIGKILL, out of memory?)".to_owned(),
+            signal => format!("(killed by signal {signal})"),
+        };
+        res_text = format!("{res_text} {}", killed.bright_black());
+    } else if let Some(failure) = failure {
+        res_text = format!("{res_text} {}", format!("({failure})").bright_black());
+    }
+    if res != TestResult::Sucess || config.keep_all_logs {
+        write_log(log_file, &command, &clock, &output, &stdout)?;
+        res_text = format!("{res_text} {}", log_file.display().bright_black());
+    }
+    match spinner {
+        Some(spinner) => {
+            spinner.finish_with_message(res_text);
+            if res != TestResult::Sucess {
+                let tail = output.len().saturating_sub(FAILURE_TAIL_LINES);
+                if DEP_BAR.lock().unwrap().is_some() {
+                    out!("{subject} {}", res.colored());
+                }
+                for line in &output[tail..] {
+                    out!("{line}");
+                }
+            }
+        }
+        None => out!("{msg} {res_text}"),
+    }
+    Ok(Check {
+        result: res,
+        compiled: compiled_units(&output),
+        failure,
+    })
+}
+
+/// The signal that killed a check, `None` when it exited by itself.
+fn killed_by(status: &std::process::ExitStatus) -> Option<i32> {
+    #[cfg(unix)]
+    {
+        std::os::unix::process::ExitStatusExt::signal(status)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = status;
+        None
+    }
+}
+
+/// Count the crates cargo reports compiling or checking.
+///
+/// Returns `None` when the output doesnt look like it came from cargo.
+fn compiled_units(output: &[String]) -> Option<usize> {
+    let mut finished = false;
+    let mut compiled = 0;
+    for line in output {
+        let line = strip_ansi(line);
+        let line = line.trim_start();
+        if line.starts_with("Compiling ") || line.starts_with("Checking ") {
+            compiled += 1;
+        } else if line.starts_with("Finished ") {
+            finished = true;
+        }
+    }
+    finished.then_some(compiled)
+}
+
+/// Remove the ANSI escape codes cargo adds with `--color always`.
+fn strip_ansi(line: &str) -> String {
+    let mut result = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(char) = chars.next() {
+        if char == '\x1b' {
+            for char in chars.by_ref() {
+                if char.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            result.push(char);
+        }
+    }
+    result
+}
+
+fn color_enabled() -> bool {
+    COLOR.load(Ordering::Relaxed)
+}
+
+/// Colors that are only applied when color is enabled.
+///
+/// This replaces the [`owo_colors::OwoColorize`] methods so every call site follows `--color`.
+trait Paint: Display + Sized {
+    fn paint(&self, style: Style) -> Painted<'_, Self> {
+        Painted { value: self, style }
+    }
+
+    fn red(&self) -> Painted<'_, Self> {
+        self.paint(Style::new().red())
+    }
+
+    fn green(&self) -> Painted<'_, Self> {
+        self.paint(Style::new().green())
+    }
+
+    fn yellow(&self) -> Painted<'_, Self> {
+        self.paint(Style::new().yellow())
+    }
+
+    fn blue(&self) -> Painted<'_, Self> {
+        self.paint(Style::new().blue())
+    }
+
+    fn bright_red(&self) -> Painted<'_, Self> {
+        self.paint(Style::new().bright_red())
+    }
+
+    fn bright_black(&self) -> Painted<'_, Self> {
+        self.paint(Style::new().bright_black())
+    }
+}
+
+impl<T: Display> Paint for T {}
+
+struct Painted<'a, T> {
+    value: &'a T,
+    style: Style,
+}
+
+impl<T: Display> Display for Painted<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if color_enabled() {
+            owo_colors::OwoColorize::style(self.value, self.style).fmt(f)
+        } else {
+            self.value.fmt(f)
+        }
+    }
+}
+
+/// Times a check, leaving out the time spent waiting for cargo's build lock.
+///
+/// Cargo prints "Blocking waiting for file lock" while another process holds the lock,
+/// and the next line it prints means the lock was acquired.
+struct CheckClock {
+    start: Instant,
+    blocked_since: Option<Instant>,
+    blocked: Duration,
+}
+
+impl CheckClock {
+    fn new() -> Self {
+        CheckClock {
+            start: Instant::now(),
+            blocked_since: None,
+            blocked: Duration::ZERO,
+        }
+    }
+
+    /// Update the clock from a line of cargo's output, returns whether cargo is now blocked.
+    fn observe(&mut self, line: &str) -> bool {
+        if line.contains("waiting for file lock") {
+            self.blocked_since.get_or_insert_with(Instant::now);
+            true
+        } else {
+            self.finish();
+            false
+        }
+    }
+
+    fn finish(&mut self) {
+        if let Some(since) = self.blocked_since.take() {
+            self.blocked += since.elapsed();
+        }
+    }
+
+    /// Time spent actually checking.
+    fn elapsed(&self) -> Duration {
+        self.start.elapsed().saturating_sub(self.blocked)
+    }
+
+    fn summary(&self) -> String {
+        if self.blocked.is_zero() {
+            format!("({:.1}s)", self.elapsed().as_secs_f32())
+        } else {
+            format!(
+                "({:.1}s, {:.1}s waiting for build lock)",
+                self.elapsed().as_secs_f32(),
+                self.blocked.as_secs_f32()
+            )
+        }
+    }
+}
+
+/// Replace `{dep}` and `{version}` in a custom command.
+///
+/// `{{` and `}}` are literal braces, any other braces are left untouched.
+fn expand_placeholders(command: &str, dep: &str, version: &semver::Version) -> String {
+    let mut result = String::with_capacity(command.len());
+    let mut rest = command;
+    while let Some(index) = rest.find(['{', '}']) {
+        result.push_str(&rest[..index]);
+        rest = &rest[index..];
+        let (replacement, len) = if rest.starts_with("{{") {
+            ("{".to_owned(), 2)
+        } else if rest.starts_with("}}") {
+            ("}".to_owned(), 2)
+        } else if rest.starts_with("{dep}") {
+            (dep.to_owned(), 5)
+        } else if rest.starts_with("{version}") {
+            (version.to_string(), 9)
+        } else {
+            (rest[..1].to_owned(), 1)
+        };
+        result.push_str(&replacement);
+        rest = &rest[len..];
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Build the command for a custom `--command`.
+///
+/// Without a shell the string is split with shell quoting rules and run directly,
+/// so it works where no `bash` is installed.
+fn custom_check_command(custom_command: &str, shell: Option<&str>) -> Result<Command> {
+    if let Some(shell) = shell {
+        let mut command = Command::new(shell);
+        let is_cmd = Path::new(shell)
+            .file_stem()
+            .is_some_and(|name| name.eq_ignore_ascii_case("cmd"));
+        command.arg(if is_cmd { "/C" } else { "-c" });
+        command.arg(custom_command);
+        return Ok(command);
+    }
+
+    let words = shlex::split(custom_command)
+        .ok_or(anyhow!("Couldnt parse the command {custom_command:?}"))?;
+    let (program, args) = words
+        .split_first()
+        .ok_or(anyhow!("The custom command is empty"))?;
+    let mut command = Command::new(program);
+    command.args(args);
+    Ok(command)
+}
+
+/// A spinner that falls back to plain lines when progress is disabled.
+///
+/// While a compact dependency line is shown the spinner stays quiet. Otherwise its finished
+/// line stays until the dependency is done, see [`finish_dep_bar`].
+struct Spinner {
+    bar: Option<ProgressBar>,
+    /// The lines under the spinner showing the latest output, see [`Spinner::show_output`].
+    window: Vec<ProgressBar>,
+    msg: String,
+    quiet: bool,
+}
+
+impl Spinner {
+    fn new(msg: String) -> Self {
+        let quiet = DEP_BAR
+            .lock()
+            .unwrap()
+            .as_ref()
+            .is_some_and(|dep_bar| dep_bar.bar.is_some());
+        let bar = if quiet {
+            None
+        } else if display_mode() == DisplayMode::Plain {
+            out!("{msg} ...");
+            None
+        } else {
+            let bar = add_bar(ProgressBar::new_spinner().with_style(Spinner::style(&msg, true)));
+            bar.enable_steady_tick(Duration::from_millis(100));
+            Some(bar)
+        };
+        Spinner {
+            bar,
+            window: Vec::new(),
+            msg,
+            quiet,
+        }
+    }
+
+    /// The line of a check, with a timer while it is `running`.
+    fn style(msg: &str, running: bool) -> ProgressStyle {
+        let elapsed = if running { " {elapsed:.dim}" } else { "" };
+        ProgressStyle::with_template(&format!("{{spinner:.cyan}} {msg}{elapsed} {{msg}}"))
+            .unwrap()
+            .tick_chars("⠋⠙⠹⠸⠼⠴⠦⠧⠇⠏ ")
+    }
+
+    fn set_message(&self, msg: String) {
+        if let Some(bar) = &self.bar {
+            bar.set_message(msg);
+        }
+    }
+
+    /// Show the last [`OUTPUT_WINDOW_LINES`] of `output` under the spinner, until it finishes.
+    ///
+    /// Left out for `minimize --jobs`, where the windows of every worker would crowd the screen.
+    fn show_output(&mut self, output: &[String]) {
+        let Some(bar) = &self.bar else {
+            return;
+        };
+        if capturing() {
+            return;
+        }
+        let shown = &output[output.len().saturating_sub(OUTPUT_WINDOW_LINES)..];
+        while self.window.len() < shown.len() {
+            let above = self.window.last().unwrap_or(bar);
+            let style = ProgressStyle::with_template("  {wide_msg:.dim}").unwrap();
+            let line = MULTI.insert_after(above, ProgressBar::new_spinner().with_style(style));
+            self.window.push(line);
+        }
+        for (line, text) in self.window.iter().zip(shown) {
+            line.set_message(text.clone());
+        }
+    }
+
+    fn clear_output(&mut self) {
+        for line in self.window.drain(..) {
+            line.finish_and_clear();
+            MULTI.remove(&line);
+        }
+    }
+
+    fn finish_with_message(mut self, res: String) {
+        self.clear_output();
+        match self.bar {
+            // Shown with the rest of what the worker printed, in manifest order.
+            Some(bar) if capturing() => {
+                bar.finish_and_clear();
+                out!("{} {res}", self.msg);
+            }
+            Some(bar) => {
+                // The result has the time it took, the timer would only repeat it.
+                bar.set_style(Spinner::style(&self.msg, false));
+                bar.finish_with_message(res);
+                if let Some(dep_bar) = DEP_BAR.lock().unwrap().as_mut() {
+                    dep_bar.spinners.push(bar);
+                }
+            }
+            None if self.quiet => {}
+            None => out!("{} {res}", self.msg),
+        }
+    }
+
+    fn finish_and_clear(mut self) {
+        self.clear_output();
+        if let Some(bar) = self.bar {
+            bar.finish_and_clear();
+        }
+    }
+}
+
+/// The progress of a single dependency, while progress is drawn.
+struct DepBar {
+    /// The compact line, see [`DisplayMode::Compact`].
+    bar: Option<ProgressBar>,
+    checked: u64,
+    fails: u64,
+    /// The finished spinners of its versions, see [`DisplayMode::Spinner`].
+    spinners: Vec<ProgressBar>,
+}
+
+impl DepBar {
+    fn fails_text(&self) -> String {
+        let plural = if self.fails == 1 { "fail" } else { "fails" };
+        format!("{} {plural}", self.fails).red().to_string()
+    }
+}
+
+/// Start the progress of a dependency, with a compact line when using [`DisplayMode::Compact`].
+fn start_dep_bar(label: String, len: usize) {
+    grow_run_bar(len);
+    let bar = match display_mode() {
+        DisplayMode::Plain => return,
+        DisplayMode::Spinner => None,
+        DisplayMode::Compact => Some(add_bar(
+            ProgressBar::new(len as u64)
+                .with_style(
+                    ProgressStyle::with_template("{prefix:30!} {bar:20.cyan} {pos}/{len} {msg}")
+                        .unwrap()
+                        .progress_chars("▓▓░"),
+                )
+                .with_prefix(label),
+        )),
+    };
+    *DEP_BAR.lock().unwrap() = Some(DepBar {
+        bar,
+        checked: 0,
+        fails: 0,
+        spinners: Vec::new(),
+    });
+}
+
+fn extend_dep_bar(extra: usize) {
+    grow_run_bar(extra);
+    if let Some(bar) = DEP_BAR
+        .lock()
+        .unwrap()
+        .as_ref()
+        .and_then(|dep_bar| dep_bar.bar.as_ref())
+    {
+        bar.inc_length(extra as u64);
+    }
+}
+
+fn advance_dep_bar(result: TestResult) {
+    advance_run_bar();
+    if let Some(dep_bar) = DEP_BAR.lock().unwrap().as_mut() {
+        dep_bar.checked += 1;
+        if result == TestResult::Fail {
+            dep_bar.fails += 1;
+        }
+        if let Some(bar) = &dep_bar.bar {
+            if result == TestResult::Fail {
+                bar.set_message(dep_bar.fails_text());
+            }
+            bar.inc(1);
+        }
+    }
+}
+
+/// Leave a single line behind as the final result of the dependency.
+///
+/// That is the compact line, or instead of the spinners of every version a line with the totals.
+fn finish_dep_bar() {
+    let Some(dep_bar) = DEP_BAR.lock().unwrap().take() else {
+        return;
+    };
+    let result = if dep_bar.fails == 0 {
+        "OK".green().to_string()
+    } else {
+        dep_bar.fails_text()
+    };
+    match &dep_bar.bar {
+        Some(bar) => {
+            if dep_bar.fails == 0 {
+                bar.set_message(result);
+            }
+            bar.finish();
+        }
+        None => {
+            for spinner in &dep_bar.spinners {
+                spinner.finish_and_clear();
+            }
+            if dep_bar.checked != 0 {
+                out!("  {} checked, {result}", dep_bar.checked);
+            }
+        }
+    }
+}
+
+/// Add a bar above the run bar, so that one stays at the bottom.
+fn add_bar(bar: ProgressBar) -> ProgressBar {
+    match RUN_BAR
+        .lock()
+        .unwrap()
+        .as_ref()
+        .and_then(|run| run.bar.as_ref())
+    {
+        Some(run) => MULTI.insert_before(run, bar),
+        None => MULTI.add(bar),
+    }
+}
+
+/// Progress over every version of the run, like `deps 3/25 [12/87] serde 1.0.145` with elapsed
+/// and ETA.
+///
+/// The total grows as each dependency is reached, since its versions are only known then.
+/// Without a terminal a plain `[12/87]` line is printed after each version instead.
+struct RunBar {
+    bar: Option<ProgressBar>,
+    pos: u64,
+    len: u64,
+    start: Instant,
+    /// How many dependencies the run covers, and which one it is at.
+    deps: usize,
+    dep: usize,
+    /// The steps of the dependencies not reached yet, see [`plan_run_bar`].
+    planned: HashMap<String, u64>,
+}
+
+impl RunBar {
+    fn eta(&self) -> Duration {
+        let remaining = self.len.saturating_sub(self.pos);
+        self.start.elapsed() / self.pos.max(1) as u32 * remaining as u32
+    }
+
+    /// Like `deps 3/25 `, empty when the run covers a single dependency.
+    fn deps_prefix(&self) -> String {
+        if self.deps > 1 {
+            format!("deps {}/{} ", self.dep, self.deps)
+        } else {
+            String::new()
+        }
+    }
+}
+
+fn start_run_bar(deps: usize) {
+    let bar = (display_mode() != DisplayMode::Plain).then(|| {
+        let bar = MULTI.add(
+            ProgressBar::new(0).with_style(
+                ProgressStyle::with_template(
+                    "{prefix}[{pos}/{len}] {msg:30!} {bar:20.cyan} {elapsed_precise}, ETA {eta}",
+                )
+                .unwrap()
+                .progress_chars("▓▓░"),
+            ),
+        );
+        bar.enable_steady_tick(Duration::from_secs(1));
+        bar
+    });
+    *RUN_BAR.lock().unwrap() = Some(RunBar {
+        bar,
+        pos: 0,
+        len: 0,
+        start: Instant::now(),
+        deps,
+        dep: 0,
+        planned: HashMap::new(),
+    });
+}
+
+/// Count the planned versions of every dependency from the start, instead of as each is reached.
+fn plan_run_bar(planned: HashMap<String, usize>) {
+    if let Some(run) = RUN_BAR.lock().unwrap().as_mut() {
+        run.planned = planned
+            .into_iter()
+            .map(|(dep, steps)| (dep, steps as u64))
+            .collect();
+        run.len += run.planned.values().sum::<u64>();
+        if let Some(bar) = &run.bar {
+            bar.set_length(run.len);
+        }
+    }
+}
+
+/// Move on to the next dependency of the run.
+fn run_bar_next_dep(dep: &str) {
+    if let Some(run) = RUN_BAR.lock().unwrap().as_mut() {
+        run.dep += 1;
+        // Its own steps are added when it knows them.
+        if let Some(steps) = run.planned.remove(dep) {
+            run.len = run.len.saturating_sub(steps);
+        }
+        if let Some(bar) = &run.bar {
+            bar.set_prefix(run.deps_prefix());
+            bar.set_message(dep.to_owned());
+        }
+    }
+}
+
+/// Add `steps` versions to the total of the run.
+fn grow_run_bar(steps: usize) {
+    if let Some(run) = RUN_BAR.lock().unwrap().as_mut() {
+        run.len += steps as u64;
+        if let Some(bar) = &run.bar {
+            bar.set_length(run.len);
+        }
+    }
+}
+
+/// Drop the steps a dependency turned out not to need, once its done.
+fn settle_run_bar() {
+    if let Some(run) = RUN_BAR.lock().unwrap().as_mut() {
+        run.len = run.pos + run.planned.values().sum::<u64>();
+        if let Some(bar) = &run.bar {
+            bar.set_length(run.len);
+        }
+    }
+}
+
+/// Show which version is being checked.
+fn run_bar_checking(dep: &str, version: &semver::Version) {
+    if let Some(bar) = RUN_BAR
+        .lock()
+        .unwrap()
+        .as_ref()
+        .and_then(|run| run.bar.as_ref())
+    {
+        bar.set_message(format!("{dep} {version}"));
+    }
+}
+
+fn advance_run_bar() {
+    let mut run = RUN_BAR.lock().unwrap();
+    let Some(run) = run.as_mut() else {
+        return;
+    };
+    run.pos += 1;
+    // The estimate of `minimize` can be short.
+    run.len = run.len.max(run.pos);
+    match &run.bar {
+        Some(bar) => {
+            bar.set_length(run.len);
+            bar.set_position(run.pos);
+        }
+        // Progress of the whole run, so never held back with the output of a worker.
+        None => MULTI.suspend(|| {
+            println!(
+                "{}",
+                format!(
+                    "[{}/{}] {}{} elapsed, ETA {}",
+                    run.pos,
+                    run.len,
+                    run.deps_prefix(),
+                    HumanDuration(run.start.elapsed()),
+                    HumanDuration(run.eta())
+                )
+                .bright_black()
+            )
+        }),
+    }
+}
+
+fn finish_run_bar() {
+    if let Some(bar) = RUN_BAR.lock().unwrap().take().and_then(|run| run.bar) {
+        bar.finish_and_clear();
+    }
+}
+
+fn write_log(
+    log_file: &Path,
+    command: &Command,
+    clock: &CheckClock,
+    stderr: &[String],
+    stdout: &str,
+) -> Result<()> {
+    if let Some(dir) = log_file.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut log = format!(
+        "$ {command:?}\nchecked in {:.1}s, waited {:.1}s for the build lock\n\n--- stderr ---\n",
+        clock.elapsed().as_secs_f32(),
+        clock.blocked.as_secs_f32()
+    );
+    for line in stderr {
+        log.push_str(line);
+        log.push('\n');
+    }
+    log.push_str("\n--- stdout ---\n");
+    log.push_str(stdout);
+    fs::write(log_file, log)?;
+    let mut written = WRITTEN_LOGS.lock().unwrap();
+    if !written.iter().any(|written| written == log_file) {
+        written.push(log_file.to_owned());
+    }
+    Ok(())
+}
+
+/// How a result is shown next to the version.
+trait Colored {
+    fn colored(self) -> String;
+}
+
+impl Colored for TestResult {
+    fn colored(self) -> String {
+        match self {
+            TestResult::Fail => "FAILED".red().to_string(),
+            TestResult::Sucess => "OK".green().to_string(),
+            TestResult::Indeterminate => "INDETERMINATE".yellow().to_string(),
+        }
+    }
+}
+
+/// A shields.io endpoint badge, see <https://shields.io/badges/endpoint-badge>.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Badge {
+    schema_version: u32,
+    label: &'static str,
+    message: String,
+    color: &'static str,
+}
+
+/// Write the `--badge`, red as soon as any dep has failing versions.
+///
+/// Deps without a version requirement arent counted.
+fn write_badge(path: &Path, message: BadgeMessage, summary: &TestSummary) -> Result<()> {
+    let deps = summary
+        .results
+        .iter()
+        .filter(|dep| dep.bound.is_some())
+        .collect::<Vec<_>>();
+    let holding = deps
+        .iter()
+        .filter(|dep| dep.failed == 0 && !dep.unreached)
+        .count();
+    let passing = summary.failed_deps() == 0;
+    let message = match (message, passing) {
+        (BadgeMessage::Count, true) => format!("{holding}/{} ok", deps.len()),
+        (BadgeMessage::Count, false) => {
+            format!("{}/{} failing", summary.failed_deps(), deps.len())
+        }
+        (BadgeMessage::Word, true) => "passing".to_owned(),
+        (BadgeMessage::Word, false) => "failing".to_owned(),
+    };
+    let badge = Badge {
+        schema_version: 1,
+        label: "dep bounds",
+        message,
+        color: if passing { "brightgreen" } else { "red" },
+    };
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+    write_atomic(path, serde_json::to_string(&badge)?)?;
+    Ok(())
+}
+
+/// `results.json` of the `--artifacts` bundle, and what every `--report` is made from.
+#[derive(Serialize)]
+struct RunResults<'a> {
+    schema_version: u32,
+    /// How long the whole run took.
+    duration_ms: u128,
+    configuration: &'a RunConfiguration,
+    deps: &'a [DepResults],
+    /// The checks that took longest, see [`TestSummary::slowest`].
+    slowest: Vec<SlowCheck>,
+}
+
+/// A check in the "Slowest checks" list.
+#[derive(Serialize)]
+struct SlowCheck {
+    dep: String,
+    version: semver::Version,
+    duration_ms: u128,
+}
+
+/// How the versions of a run were checked.
+#[derive(Serialize, Default)]
+struct RunConfiguration {
+    /// The built-in or custom command, before any placeholders are filled in.
+    command: String,
+    features: String,
+    granularity: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    toolchains: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    targets: Vec<String>,
+    /// The `--env` variables and those of the metadata, as `KEY=VALUE`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    env: Vec<String>,
+    cargo_bounds_version: String,
+}
+
+impl RunConfiguration {
+    fn of(config: &TestConfig) -> Result<Self> {
+        let command = match &config.check.command {
+            Some(command) => command.clone(),
+            None => {
+                let command = check_command(config, None)?;
+                let args = command
+                    .get_args()
+                    .map(|arg| arg.to_string_lossy())
+                    .collect::<Vec<_>>();
+                format!("cargo {}", args.join(" "))
+            }
+        };
+        let mut granularity = config.granularity().to_string();
+        if let Some(per_major) = config.per_major {
+            granularity = format!("{granularity}, {per_major} per major");
+        }
+        if config.adaptive {
+            granularity = "adaptive".to_owned();
+        }
+        if config.extremes_only {
+            granularity = "extremes only".to_owned();
+        }
+        Ok(RunConfiguration {
+            command,
+            features: config.describe_features(),
+            granularity,
+            toolchains: config.toolchain.clone(),
+            targets: config.target.clone(),
+            env: config
+                .check
+                .env
+                .iter()
+                .map(|(key, value)| format!("{key}={value}"))
+                .collect(),
+            cargo_bounds_version: env!("CARGO_PKG_VERSION").to_owned(),
+        })
+    }
+}
+
+impl TestSummary {
+    fn run_results(&self) -> RunResults<'_> {
+        RunResults {
+            schema_version: ARTIFACTS_SCHEMA,
+            duration_ms: self.duration.as_millis(),
+            configuration: &self.configuration,
+            deps: &self.results,
+            slowest: self.slowest(),
+        }
+    }
+}
+
+/// The tested versions of one dependency.
+#[derive(Serialize)]
+struct DepResults {
+    dep: String,
+    /// The workspace member it belongs to, with `--package`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    package: Option<String>,
+    /// The declared version requirement.
+    bound: Option<String>,
+    /// The requirement `--bound` swept instead of the declared one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hypothetical_bound: Option<String>,
+    /// How many checks failed, which also counts failures that arent a tested version,
+    /// like a bound that no published version matches.
+    failed: usize,
+    /// The versions `--fail-fast` skipped after the first failure.
+    untested: Vec<semver::Version>,
+    /// Set when `--fail-fast=all` stopped the run before this dep.
+    unreached: bool,
+    /// Set when no published version matches the bound, with what is published around it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    no_match: Option<NoMatch>,
+    /// How long testing it took, from fetching its versions to the last check.
+    duration_ms: u128,
+    versions: Vec<VersionResult>,
+}
+
+impl DepResults {
+    /// The dep, after its workspace member with `--package`.
+    fn name(&self) -> String {
+        match &self.package {
+            Some(package) => format!("{package}/{}", self.dep),
+            None => self.dep.clone(),
+        }
+    }
+
+    /// The versions that couldnt be confirmed either way for `reason`.
+    fn unconfirmed(&self, reason: Unconfirmed) -> Vec<String> {
+        self.versions
+            .iter()
+            .filter(|tested| tested.unconfirmed() == Some(reason))
+            .map(|tested| tested.version.to_string())
+            .collect()
+    }
+
+    /// The newest failing and the oldest passing version.
+    fn transition(&self) -> (Option<&semver::Version>, Option<&semver::Version>) {
+        let newest_fail = self
+            .versions
+            .iter()
+            .filter(|tested| tested.result == TestResult::Fail)
+            .map(|tested| &tested.version)
+            .max();
+        let oldest_pass = self
+            .versions
+            .iter()
+            .filter(|tested| tested.result == TestResult::Sucess)
+            .map(|tested| &tested.version)
+            .min();
+        (newest_fail, oldest_pass)
+    }
+}
+
+/// Why a version couldnt be confirmed to pass or fail.
+#[derive(Clone, Copy, PartialEq)]
+enum Unconfirmed {
+    /// Cargo used a different version than the pin, see [`resolved_version`].
+    Indeterminate,
+    /// Cargo couldnt resolve the dependency graph with the pin, see `--conflicts untestable`.
+    Untestable,
+    /// The check was killed by a signal.
+    Killed,
+}
+
+#[derive(Serialize)]
+struct VersionResult {
+    version: semver::Version,
+    result: TestResult,
+    duration_ms: u128,
+    /// The `--each-feature` configurations that failed.
+    failed_variants: Vec<String>,
+    /// The toolchains it failed on, with several `--toolchain`s.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    failed_toolchains: Vec<String>,
+    /// The targets it failed for, with several `--target`s.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    failed_targets: Vec<String>,
+    /// Why it failed, see [`FailureKind`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    failure: Option<FailureKind>,
+    /// How often it was tried again after a network error, see `--retries`.
+    #[serde(skip_serializing_if = "is_zero")]
+    retries: u32,
+    /// The logs of its checks, kept for failures and with `--keep-all-logs`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    logs: Vec<PathBuf>,
+}
+
+impl VersionResult {
+    /// Why it couldnt be confirmed either way, `None` when it passed or failed.
+    fn unconfirmed(&self) -> Option<Unconfirmed> {
+        if self.result != TestResult::Indeterminate {
+            return None;
+        }
+        Some(match self.failure {
+            Some(FailureKind::ResolutionConflict) => Unconfirmed::Untestable,
+            Some(FailureKind::Killed) => Unconfirmed::Killed,
+            _ => Unconfirmed::Indeterminate,
+        })
+    }
+}
+
+fn is_zero(count: &u32) -> bool {
+    *count == 0
+}
+
+/// `index.json` of the `--artifacts` bundle, listing its files.
+#[derive(Serialize)]
+struct ArtifactsIndex {
+    schema_version: u32,
+    cargo_bounds_version: &'static str,
+    files: Vec<ArtifactFile>,
+}
+
+#[derive(Serialize)]
+struct ArtifactFile {
+    /// Relative to the bundle directory.
+    path: PathBuf,
+    kind: &'static str,
+}
+
+/// Write the `--artifacts` bundle of a `test` run to `dir`.
+///
+/// The layout is `index.json`, `results.json`, `summary.txt` and the failure logs in `logs/`,
+/// where they keep their path within the `log_dir` of the run.
+/// Files the index doesnt list are left alone, so the directory can be reused between runs.
+fn write_artifacts(dir: &Path, summary: &TestSummary, log_dir: &Path) -> Result<()> {
+    fs::create_dir_all(dir.join("logs"))?;
+    let mut files = Vec::new();
+
+    write_atomic(
+        dir.join("results.json"),
+        serde_json::to_string_pretty(&summary.run_results())?,
+    )?;
+    files.push(ArtifactFile {
+        path: "results.json".into(),
+        kind: "results",
+    });
+
+    write_atomic(dir.join("summary.txt"), summary.plain_text())?;
+    files.push(ArtifactFile {
+        path: "summary.txt".into(),
+        kind: "summary",
+    });
+
+    for log in WRITTEN_LOGS.lock().unwrap().iter() {
+        let log = std::path::absolute(log)?;
+        let Some(name) = log.file_name() else {
+            continue;
+        };
+        let path = Path::new("logs").join(log.strip_prefix(log_dir).unwrap_or(Path::new(name)));
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(dir.join(parent))?;
+        }
+        fs::copy(&log, dir.join(&path))?;
+        files.push(ArtifactFile { path, kind: "log" });
+    }
+
+    let index = ArtifactsIndex {
+        schema_version: ARTIFACTS_SCHEMA,
+        cargo_bounds_version: env!("CARGO_PKG_VERSION"),
+        files,
+    };
+    write_atomic(
+        dir.join("index.json"),
+        serde_json::to_string_pretty(&index)?,
+    )?;
+    out!("Wrote artifacts to {}", dir.display().blue());
+    Ok(())
+}
+
+/// The results of a run as `--save-baseline` wrote them, only what `diff` needs.
+///
+/// `results.json` of the `--artifacts` bundle has the same layout.
+#[derive(Deserialize)]
+struct SavedResults {
+    deps: Vec<SavedDep>,
+}
+
+#[derive(Deserialize)]
+struct SavedDep {
+    dep: String,
+    #[serde(default)]
+    package: Option<String>,
+    versions: Vec<SavedVersion>,
+}
+
+#[derive(Deserialize)]
+struct SavedVersion {
+    version: semver::Version,
+    result: TestResult,
+}
+
+impl SavedResults {
+    fn load(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .map_err(|err| anyhow!("Couldnt read the results {}: {err}", path.display()))?;
+        serde_json::from_str(&contents)
+            .map_err(|err| anyhow!("{} isnt a saved run: {err}", path.display()))
+    }
+
+    /// The results of this run, through the same format they are saved in.
+    fn of(summary: &TestSummary) -> Result<Self> {
+        Ok(serde_json::from_value(serde_json::to_value(
+            summary.run_results(),
+        )?)?)
+    }
+
+    fn result(
+        &self,
+        dep: &str,
+        package: Option<&str>,
+        version: &semver::Version,
+    ) -> Option<TestResult> {
+        self.deps
+            .iter()
+            .find(|saved| saved.dep == dep && saved.package.as_deref() == package)?
+            .versions
+            .iter()
+            .find(|saved| saved.version == *version)
+            .map(|saved| saved.result)
+    }
+}
+
+/// What changed between two runs, see [`SavedResults`].
+struct ResultsDiff {
+    /// Passed before and fail now.
+    regressions: Vec<(String, semver::Version)>,
+    /// Failed before and pass now.
+    fixes: Vec<(String, semver::Version)>,
+    /// Only tested by the later run.
+    new: Vec<(String, semver::Version, TestResult)>,
+}
+
+impl ResultsDiff {
+    fn between(old: &SavedResults, new: &SavedResults) -> Self {
+        let mut diff = ResultsDiff {
+            regressions: Vec::new(),
+            fixes: Vec::new(),
+            new: Vec::new(),
+        };
+        for dep in &new.deps {
+            let name = match &dep.package {
+                Some(package) => format!("{package}/{}", dep.dep),
+                None => dep.dep.clone(),
+            };
+            for tested in &dep.versions {
+                let entry = (name.clone(), tested.version.clone());
+                let before = old.result(&dep.dep, dep.package.as_deref(), &tested.version);
+                match (before, tested.result) {
+                    (None, result) => diff.new.push((entry.0, entry.1, result)),
+                    (Some(TestResult::Sucess), TestResult::Fail) => diff.regressions.push(entry),
+                    (Some(TestResult::Fail), TestResult::Sucess) => diff.fixes.push(entry),
+                    _ => {}
+                }
+            }
+        }
+        diff
+    }
+
+    fn print(&self) {
+        if self.regressions.is_empty() && self.fixes.is_empty() && self.new.is_empty() {
+            out!("{}", "No changes since the baseline".green());
+            return;
+        }
+        if !self.regressions.is_empty() {
+            out!("Regressions, these passed in the baseline:");
+            for (dep, version) in &self.regressions {
+                out!("  {} {}", dep.blue(), version.red());
+            }
+        }
+        if !self.fixes.is_empty() {
+            out!("Fixed, these failed in the baseline:");
+            for (dep, version) in &self.fixes {
+                out!("  {} {}", dep.blue(), version.green());
+            }
+        }
+        if !self.new.is_empty() {
+            out!("Not in the baseline:");
+            for (dep, version, result) in &self.new {
+                out!("  {} {} {}", dep.blue(), version, result.colored());
+            }
+        }
+    }
+
+    /// Fail when a version regressed, failures the baseline already had are fine.
+    fn check(&self) -> Result<()> {
+        match self.regressions.len() {
+            0 => Ok(()),
+            regressed => Err(anyhow!("{regressed} versions regressed since the baseline")),
+        }
+    }
+}
+
+/// Write a `--report` of the results.
+fn write_report(report: &ReportTarget, summary: &TestSummary) -> Result<()> {
+    if let Some(dir) = report
+        .path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+    {
+        fs::create_dir_all(dir)?;
+    }
+    let results = summary.run_results();
+    let contents = match report.format {
+        ReportFormat::Html => render_html(&results),
+        ReportFormat::Json => serde_json::to_string_pretty(&results)?,
+    };
+    write_atomic(&report.path, contents)?;
+    out!("Wrote the report to {}", report.path.display().blue());
+    Ok(())
+}
+
+/// A page with the configuration, a table of the deps and a section per dep listing its
+/// versions, the failing ones with the stderr of their checks.
+///
+/// Everything is inline, so the file can be sent around on its own.
+fn render_html(results: &RunResults) -> String {
+    let mut html = String::from(HTML_HEAD);
+    let configuration = results.configuration;
+    html.push_str("<h1>cargo bounds</h1>\n<table class=\"config\">\n");
+    let mut rows = vec![
+        ("command", configuration.command.clone()),
+        ("features", configuration.features.clone()),
+        ("versions", configuration.granularity.clone()),
+    ];
+    if !configuration.toolchains.is_empty() {
+        rows.push(("toolchains", configuration.toolchains.join(", ")));
+    }
+    if !configuration.targets.is_empty() {
+        rows.push(("targets", configuration.targets.join(", ")));
+    }
+    if !configuration.env.is_empty() {
+        rows.push(("env", configuration.env.join(" ")));
+    }
+    rows.push((
+        "took",
+        format!("{:.1}s", results.duration_ms as f64 / 1000.0),
+    ));
+    rows.push(("cargo-bounds", configuration.cargo_bounds_version.clone()));
+    for (name, value) in rows {
+        html.push_str(&format!(
+            "<tr><th>{name}</th><td><code>{}</code></td></tr>\n",
+            escape_html(&value)
+        ));
+    }
+    html.push_str("</table>\n");
+
+    html.push_str(
+        "<table class=\"deps\">\n<tr><th>dep</th><th>bound</th><th>tested</th><th>failed</th><th>time</th></tr>\n",
+    );
+    for dep in results.deps {
+        let class = if dep.failed == 0 { "pass" } else { "fail" };
+        html.push_str(&format!(
+            "<tr class=\"{class}\"><td><a href=\"#{id}\">{name}</a></td><td><code>{bound}</code></td><td>{tested}</td><td>{failed}</td><td>{time:.1}s</td></tr>\n",
+            id = escape_html(&dep.name()),
+            name = escape_html(&dep.name()),
+            bound = escape_html(dep.bound.as_deref().unwrap_or("-")),
+            tested = dep.versions.len(),
+            failed = dep.failed,
+            time = dep.duration_ms as f64 / 1000.0,
+        ));
+    }
+    html.push_str("</table>\n");
+
+    for dep in results.deps {
+        html.push_str(&format!(
+            "<h2 id=\"{id}\">{id} <code>{bound}</code></h2>\n",
+            id = escape_html(&dep.name()),
+            bound = escape_html(dep.bound.as_deref().unwrap_or("-")),
+        ));
+        if dep.unreached {
+            html.push_str("<p>Not tested, the run stopped before it.</p>\n");
+        }
+        if let Some(no_match) = &dep.no_match {
+            html.push_str(&format!(
+                "<p class=\"fail\">Matches no published version, {}.</p>\n",
+                escape_html(&no_match.closest())
+            ));
+            for excluded in no_match.excluded() {
+                html.push_str(&format!("<p>{}.</p>\n", escape_html(&excluded)));
+            }
+        }
+        for tested in &dep.versions {
+            let (class, result) = match tested.result {
+                TestResult::Sucess => ("pass", "OK"),
+                TestResult::Fail => ("fail", "FAILED"),
+                TestResult::Indeterminate => ("indeterminate", "INDETERMINATE"),
+            };
+            let mut notes = Vec::new();
+            if let Some(failure) = tested.failure {
+                notes.push(failure.to_string());
+            }
+            if !tested.failed_variants.is_empty() {
+                notes.push(tested.failed_variants.join(", "));
+            }
+            if !tested.failed_toolchains.is_empty() {
+                notes.push(format!("+{}", tested.failed_toolchains.join(", +")));
+            }
+            if !tested.failed_targets.is_empty() {
+                notes.push(tested.failed_targets.join(", "));
+            }
+            if tested.retries != 0 {
+                notes.push(format!("{} retries", tested.retries));
+            }
+            let open = if tested.result == TestResult::Sucess {
+                ""
+            } else {
+                " open"
+            };
+            html.push_str(&format!(
+                "<details class=\"{class}\"{open}><summary><code>{version}</code> <b>{result}</b> {time:.1}s {notes}</summary>\n",
+                version = tested.version,
+                time = tested.duration_ms as f64 / 1000.0,
+                notes = escape_html(&notes.join(" - ")),
+            ));
+            for log in &tested.logs {
+                let stderr = fs::read_to_string(log)
+                    .map(|log| log_stderr(&log))
+                    .unwrap_or_else(|err| format!("couldnt read {}: {err}", log.display()));
+                html.push_str(&format!(
+                    "<p class=\"log\">{}</p>\n<pre>{}</pre>\n",
+                    escape_html(&log.display().to_string()),
+                    escape_html(&stderr)
+                ));
+            }
+            if tested.logs.is_empty() {
+                html.push_str("<p class=\"log\">No log was kept.</p>\n");
+            }
+            html.push_str("</details>\n");
+        }
+        if !dep.untested.is_empty() {
+            let untested = dep
+                .untested
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>();
+            html.push_str(&format!(
+                "<p>Not tested after the first failure: {}</p>\n",
+                untested.join(", ")
+            ));
+        }
+    }
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+/// The stderr part of a log written by [`write_log`], without the ANSI colors.
+fn log_stderr(log: &str) -> String {
+    let stderr = log
+        .split_once("--- stderr ---\n")
+        .map_or(log, |(_, rest)| rest);
+    let stderr = stderr
+        .split_once("\n--- stdout ---")
+        .map_or(stderr, |(stderr, _)| stderr);
+    strip_ansi(stderr)
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+const HTML_HEAD: &str = r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<title>cargo bounds report</title>
+<style>
+body { font-family: system-ui, sans-serif; margin: 2em auto; max-width: 60em; padding: 0 1em; color: #222; }
+table { border-collapse: collapse; margin-bottom: 2em; }
+th, td { text-align: left; padding: 0.3em 0.8em; border-bottom: 1px solid #ddd; }
+tr.fail td:first-child, details.fail > summary b { color: #c62828; }
+tr.pass td:first-child, details.pass > summary b { color: #2e7d32; }
+details.indeterminate > summary b { color: #b26a00; }
+details { margin: 0.3em 0; }
+summary { cursor: pointer; }
+pre { background: #f6f8fa; padding: 0.8em; overflow-x: auto; font-size: 0.85em; }
+p.log { color: #666; font-size: 0.85em; margin-bottom: 0; }
+</style>
+</head>
+<body>
+"#;
+
+/// Manifest edits proposed by `minimize`, in a form tools can apply without understanding TOML.
+#[derive(Serialize, Deserialize)]
+struct Fixes {
+    fixes: Vec<Fix>,
+}
+
+/// Replace the string at `toml_path` in `manifest` with `new`, if it's still `old`.
+#[derive(Serialize, Deserialize)]
+struct Fix {
+    dep: String,
+    manifest: PathBuf,
+    toml_path: Vec<String>,
+    old: String,
+    new: String,
+    evidence: Evidence,
+}
+
+/// Why a [`Fix`] was proposed.
+#[derive(Serialize, Deserialize)]
+struct Evidence {
+    verified_min: semver::Version,
+    verified_max: semver::Version,
+    /// Versions that were tested and failed.
+    failing: Vec<semver::Version>,
+    /// The minimum that also builds with `-Zdirect-minimal-versions`, from `--verify-minimal`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    minimal_versions_min: Option<semver::Version>,
+}
+
+fn apply_fixes(file: &Path) -> Result<()> {
+    let fixes: Fixes = serde_json::from_str(&fs::read_to_string(file)?)?;
+    apply_all(fixes.fixes)
+}
+
+fn apply_all(fixes: Vec<Fix>) -> Result<()> {
+    for fix in fixes {
+        let mut manifest = fs::read_to_string(&fix.manifest)?.parse::<DocumentMut>()?;
+        match apply_fix(&mut manifest, &fix) {
+            Ok(()) => {
+                write_atomic(&fix.manifest, manifest.to_string())?;
+                out!(
+                    "{} {} -> {}",
+                    fix.dep.blue(),
+                    fix.old.yellow(),
+                    fix.new.green()
+                );
+            }
+            Err(err) => out!("{} {} {err}", fix.dep.blue(), "skipped:".red()),
+        }
+    }
+    Ok(())
+}
+
+/// Apply a single fix, only touching the requirement string itself.
+///
+/// The fix is refused if the manifest no longer contains the requirement it was made for.
+fn apply_fix(manifest: &mut DocumentMut, fix: &Fix) -> Result<()> {
+    let mut item = manifest.as_item_mut();
+    for key in &fix.toml_path {
+        item = item
+            .get_mut(key.as_str())
+            .ok_or(anyhow!("{} not found", fix.toml_path.join(".")))?;
+    }
+    let value = item
+        .as_value_mut()
+        .ok_or(anyhow!("{} isnt a value", fix.toml_path.join(".")))?;
+    if value.as_str() != Some(fix.old.as_str()) {
+        return Err(anyhow!(
+            "expected {:?} but the manifest has {}",
+            fix.old,
+            value.to_string().trim()
+        ));
+    }
+
+    let decor = value.decor().clone();
+    // A literal string stays one, so only the requirement itself changes.
+    let literal = value.to_string().trim_start().starts_with('\'');
+    *value = match literal && !fix.new.contains('\'') {
+        true => format!("'{}'", fix.new).parse()?,
+        false => fix.new.as_str().into(),
+    };
+    *value.decor_mut() = decor;
+    Ok(())
+}
+
+/// Cargo flags we pass down that older cargo versions dont have.
+///
+/// Every use of a version dependent flag goes through [`cargo_supports`],
+/// so what is needed from cargo is listed in one place.
+#[derive(Clone, Copy, Debug)]
+enum CargoFeature {
+    /// `cargo check --all-features`
+    AllFeatures,
+}
+
+impl CargoFeature {
+    /// The flag this feature adds and the first cargo version that has it.
+    fn requirement(self) -> (&'static str, semver::Version) {
+        match self {
+            CargoFeature::AllFeatures => ("--all-features", semver::Version::new(1, 15, 0)),
+        }
+    }
+}
+
+/// Get the version of the cargo on the `PATH`, or of the cargo in a rustup `toolchain`.
+fn probe_cargo(toolchain: Option<&str>) -> Result<semver::Version> {
+    let mut command = Command::new("cargo");
+    if let Some(toolchain) = toolchain {
+        command.arg(format!("+{toolchain}"));
+    }
+    let output = command
+        .arg("--version")
+        .output()
+        .map_err(|err| anyhow!("Couldnt run cargo, is it on your PATH? ({err})"))?;
+    if let Some(toolchain) = toolchain
+        && !output.status.success()
+    {
+        return Err(anyhow!(
+            "The toolchain `{toolchain}` isnt installed, install it with `rustup toolchain install {toolchain}`"
+        ));
+    }
+    let output = String::from_utf8_lossy(&output.stdout);
+    parse_cargo_version(&output).ok_or(anyhow!("Unexpected `cargo --version` output: {output}"))
+}
+
+/// Make sure rustup has the standard library of every target, for each toolchain.
+///
+/// Without rustup there is no way to tell, so the checks find out instead.
+fn check_targets_installed(targets: &[String], toolchains: &[String]) -> Result<()> {
+    if targets.is_empty() {
+        return Ok(());
+    }
+    let toolchains = match toolchains {
+        [] => vec![None],
+        toolchains => toolchains.iter().map(Some).collect(),
+    };
+    for toolchain in toolchains {
+        let mut command = Command::new("rustup");
+        command.args(["target", "list", "--installed"]);
+        if let Some(toolchain) = toolchain {
+            command.args(["--toolchain", toolchain]);
+        }
+        let Ok(output) = command.output() else {
+            return Ok(());
+        };
+        if !output.status.success() {
+            continue;
+        }
+        let installed = String::from_utf8_lossy(&output.stdout);
+        for target in targets {
+            if installed.lines().any(|line| line.trim() == target) {
+                continue;
+            }
+            let toolchain = toolchain
+                .map(|toolchain| format!(" --toolchain {toolchain}"))
+                .unwrap_or_default();
+            return Err(anyhow!(
+                "The target `{target}` isnt installed, install it with `rustup target add {target}{toolchain}`"
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Parse output like `cargo 1.85.0-nightly (abc 2024-01-01)`, ignoring any pre-release.
+fn parse_cargo_version(output: &str) -> Option<semver::Version> {
+    let version = output.strip_prefix("cargo ")?.split_whitespace().next()?;
+    let version = semver::Version::parse(version).ok()?;
+    Some(semver::Version::new(
+        version.major,
+        version.minor,
+        version.patch,
+    ))
+}
+
+fn cargo_supports(feature: CargoFeature) -> bool {
+    supported_by(feature, CARGO_VERSION.get())
+}
+
+/// Whether the cargo `found` has `feature`, warning once when it doesnt. Without knowing the
+/// version of cargo it is assumed to.
+fn supported_by(feature: CargoFeature, found: Option<&semver::Version>) -> bool {
+    let (flag, required) = feature.requirement();
+    let Some(found) = found else {
+        return true;
+    };
+    let supported = *found >= required;
+    if !supported {
+        static WARNED: Mutex<Vec<&str>> = Mutex::new(Vec::new());
+        let mut warned = WARNED.lock().unwrap();
+        if !warned.contains(&flag) {
+            warned.push(flag);
+            out!(
+                "{} `{flag}` requires cargo {}.{}+, found {found}, leaving it out",
+                "warning:".yellow(),
+                required.major,
+                required.minor
+            );
+        }
+    }
+    supported
+}
+
+/// Versions known to pass or fail from earlier `test` and `minimize` runs.
+///
+/// Stored in `knowledge.json` of the state directory and thrown away as soon as the manifest,
+/// the lockfile or any source file changes, results for a dep are only used with the same check
+/// command. Failures are only reused with `--cache-failures`, since they are often flaky.
+#[derive(Serialize, Deserialize, Default)]
+struct Knowledge {
+    /// Hash of the manifest and sources the results were found with.
+    context: String,
+    deps: HashMap<String, DepKnowledge>,
+    /// How many results were reused per dep in this run.
+    #[serde(skip)]
+    reused: HashMap<String, usize>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct DepKnowledge {
+    /// See [`TestConfig::check_context`].
+    command: String,
+    pass: BTreeSet<semver::Version>,
+    fail: BTreeSet<semver::Version>,
+}
+
+impl Knowledge {
+    fn load(state: &State) -> Result<Self> {
+        let context = hash_sources(&state.cargo_toml, state.lockfile.contents.as_deref())?;
+        let knowledge = fs::read_to_string(paths().knowledge())
+            .ok()
+            .and_then(|knowledge| serde_json::from_str::<Knowledge>(&knowledge).ok())
+            .filter(|knowledge| knowledge.context == context);
+        Ok(knowledge.unwrap_or(Knowledge {
+            context,
+            ..Knowledge::default()
+        }))
+    }
+
+    fn lookup(
+        &mut self,
+        dep: &str,
+        command: &str,
+        version: &semver::Version,
+    ) -> Option<TestResult> {
+        if NO_CACHE.load(Ordering::Relaxed) {
+            return None;
+        }
+        let known = self
+            .deps
+            .get(dep)
+            .filter(|known| known.command == command)?;
+        let result = if known.pass.contains(version) {
+            TestResult::Sucess
+        } else if known.fail.contains(version) && CACHE_FAILURES.load(Ordering::Relaxed) {
+            TestResult::Fail
+        } else {
+            return None;
+        };
+        *self.reused.entry(dep.to_owned()).or_default() += 1;
+        Some(result)
+    }
+
+    fn record(
+        &mut self,
+        dep: &str,
+        command: &str,
+        version: semver::Version,
+        result: TestResult,
+    ) -> Result<()> {
+        self.remember(dep, command, version, result);
+        fs::create_dir_all(&paths().root)?;
+        write_atomic(paths().knowledge(), serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// Replace what is known about `version` with `result`, an indeterminate one forgets it.
+    fn remember(&mut self, dep: &str, command: &str, version: semver::Version, result: TestResult) {
+        let known = self.deps.entry(dep.to_owned()).or_default();
+        if known.command != command {
+            *known = DepKnowledge {
+                command: command.to_owned(),
+                ..DepKnowledge::default()
+            };
+        }
+        known.pass.remove(&version);
+        known.fail.remove(&version);
+        match result {
+            TestResult::Sucess => known.pass.insert(version),
+            TestResult::Fail => known.fail.insert(version),
+            TestResult::Indeterminate => false,
+        };
+    }
+}
+
+/// The outcome of one `test` run, kept for `trend`.
+#[derive(Serialize, Deserialize)]
+struct RunRecord {
+    /// Seconds since the unix epoch.
+    timestamp: u64,
+    /// The hash of the sources, see [`hash_sources`].
+    sources: String,
+    /// The check context and granularity, only runs with the same one are compared.
+    configuration: String,
+    deps: Vec<DepRecord>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct DepRecord {
+    dep: String,
+    /// The workspace member it belongs to, with `--package`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    package: Option<String>,
+    passing: Vec<semver::Version>,
+    failing: Vec<semver::Version>,
+}
+
+impl RunRecord {
+    fn append(configuration: String, summary: &TestSummary) -> Result<()> {
+        let sources = KNOWLEDGE
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|knowledge| knowledge.context.clone())
+            .unwrap_or_default();
+        let versions_with = |dep: &DepResults, result| {
+            dep.versions
+                .iter()
+                .filter(|tested| tested.result == result)
+                .map(|tested| tested.version.clone())
+                .collect()
+        };
+        let record = RunRecord {
+            timestamp: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)?
+                .as_secs(),
+            sources,
+            configuration,
+            deps: summary
+                .results
+                .iter()
+                .map(|dep| DepRecord {
+                    dep: dep.dep.clone(),
+                    package: dep.package.clone(),
+                    passing: versions_with(dep, TestResult::Sucess),
+                    failing: versions_with(dep, TestResult::Fail),
+                })
+                .collect(),
+        };
+
+        fs::create_dir_all(&paths().root)?;
+        let mut history = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(paths().history())?;
+        writeln!(history, "{}", serde_json::to_string(&record)?)?;
+        Ok(())
+    }
+}
+
+/// How one dependency did over the compared runs.
+#[derive(Serialize)]
+struct DepTrend {
+    dep: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    package: Option<String>,
+    /// The number of passing versions in each run, oldest first.
+    window: Vec<usize>,
+    /// The number of failing versions in each run, oldest first.
+    failures: Vec<usize>,
+    shrinking: bool,
+    notes: Vec<String>,
+}
+
+fn trend(config: &TrendConfig) -> Result<()> {
+    let history = fs::read_to_string(paths().history()).unwrap_or_default();
+    let records = history
+        .lines()
+        .filter_map(|line| serde_json::from_str::<RunRecord>(line).ok())
+        .collect::<Vec<_>>();
+    let Some(latest) = records.last() else {
+        out!("No runs recorded yet, run `cargo bounds test` first");
+        return Ok(());
+    };
+
+    // Windows only compare between runs that checked the same way.
+    let comparable = records
+        .iter()
+        .filter(|record| record.configuration == latest.configuration)
+        .collect::<Vec<_>>();
+    let left_out = records.len() - comparable.len();
+    let runs = &comparable[comparable.len().saturating_sub(config.runs)..];
+
+    let mut trends = Vec::new();
+    for dep in &latest.deps {
+        let history = runs
+            .iter()
+            .filter_map(|run| {
+                let record = run
+                    .deps
+                    .iter()
+                    .find(|record| record.dep == dep.dep && record.package == dep.package)?;
+                Some((*run, record))
+            })
+            .collect::<Vec<_>>();
+        let window = history
+            .iter()
+            .map(|(_, record)| record.passing.len())
+            .collect::<Vec<_>>();
+        let failures = history
+            .iter()
+            .map(|(_, record)| record.failing.len())
+            .collect::<Vec<_>>();
+
+        let mut notes = Vec::new();
+        for pair in history.windows(2) {
+            let ((before_run, before), (after_run, after)) = (pair[0], pair[1]);
+            let new_failures = after
+                .failing
+                .iter()
+                .filter(|version| !before.failing.contains(version))
+                .collect::<Vec<_>>();
+            if new_failures.is_empty() {
+                continue;
+            }
+            let place = match (before.passing.iter().min(), before.passing.iter().max()) {
+                (Some(oldest), _) if new_failures.iter().all(|version| *version <= oldest) => {
+                    "at the old end"
+                }
+                (_, Some(newest)) if new_failures.iter().all(|version| *version >= newest) => {
+                    "at the new end"
+                }
+                _ => "inside the window",
+            };
+            let cause = if before_run.sources != after_run.sources {
+                "after the sources changed"
+            } else {
+                "with unchanged sources"
+            };
+            notes.push(format!(
+                "{}: new failures {place} {cause}",
+                format_date(after_run.timestamp)
+            ));
+        }
+
+        trends.push(DepTrend {
+            dep: dep.dep.clone(),
+            package: dep.package.clone(),
+            shrinking: window.first() > window.last(),
+            window,
+            failures,
+            notes,
+        });
+    }
+
+    if config.json {
+        let json = serde_json::to_string_pretty(&trends)?;
+        out!("{json}");
+        return Ok(());
+    }
+
+    out!(
+        "Last {} runs since {}{}",
+        runs.len(),
+        format_date(runs[0].timestamp),
+        if left_out == 0 {
+            String::new()
+        } else {
+            format!(" ({left_out} runs with a different configuration left out)")
+                .bright_black()
+                .to_string()
+        }
+    );
+    for trend in &trends {
+        let first = trend.window.first().copied().unwrap_or(0);
+        let last = trend.window.last().copied().unwrap_or(0);
+        let change = format!("{:+}", last as i64 - first as i64);
+        let change = if trend.shrinking {
+            change.red().to_string()
+        } else {
+            change.green().to_string()
+        };
+        let failures = format!(
+            "{} -> {} failing",
+            trend.failures.first().copied().unwrap_or(0),
+            trend.failures.last().copied().unwrap_or(0)
+        );
+        out!(
+            "  {:<20} {} window {first} -> {last} ({change})  {}  {}",
+            match &trend.package {
+                Some(package) => format!("{package}/{}", trend.dep),
+                None => trend.dep.clone(),
+            }
+            .blue(),
+            sparkline(&trend.window),
+            failures.yellow(),
+            if trend.shrinking {
+                "shrinking".red().to_string()
+            } else {
+                String::new()
+            }
+        );
+        for note in &trend.notes {
+            out!("      {}", note.bright_black());
+        }
+    }
+    Ok(())
+}
+
+/// Draw the values as a line of bars scaled between their minimum and maximum.
+fn sparkline(values: &[usize]) -> String {
+    const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let min = values.iter().copied().min().unwrap_or(0);
+    let max = values.iter().copied().max().unwrap_or(0);
+    values
+        .iter()
+        .map(|value| {
+            if max == min {
+                BARS[3]
+            } else {
+                BARS[(value - min) * (BARS.len() - 1) / (max - min)]
+            }
+        })
+        .collect()
 }
 
-#[derive(Parser, Debug)]
-#[command(bin_name("cargo bounds"))]
-enum Cli {
-    /// Test if your current depedency bounds are valid.
-    Test(TestConfig),
-    /// Find the most flexible range you could support
-    Minimize {
-        /// Minimize a specific dependency
-        dep: Option<String>,
-        /// Skip the sanity check
-        #[arg(short, long)]
-        skip_sanity: bool,
-    },
+/// Format seconds since the unix epoch as a `YYYY-MM-DD` date in UTC.
+fn format_date(timestamp: u64) -> String {
+    // Howard Hinnant's days to civil date.
+    let days = (timestamp / 86400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{year:04}-{month:02}-{day:02}")
 }
 
-#[derive(Clone)]
-struct State {
-    cargo_toml: Box<str>,
+fn print_reused(dep: &str) {
+    let reused = KNOWLEDGE
+        .lock()
+        .unwrap()
+        .as_mut()
+        .and_then(|knowledge| knowledge.reused.remove(dep))
+        .unwrap_or(0);
+    if reused != 0 {
+        out!(
+            "  {}",
+            format!("{reused} results reused from previous runs").bright_black()
+        );
+    }
 }
 
-impl State {
-    fn store() -> Result<Self> {
-        Ok(State {
-            cargo_toml: fs::read_to_string("Cargo.toml")?.into(),
-        })
+/// Hash the manifest, the lockfile and every source file that could change a check result.
+///
+/// Uses FNV-1a so the hash is stable between builds of cargo-bounds.
+fn hash_sources(manifest: &str, lockfile: Option<&str>) -> Result<String> {
+    fn collect(path: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+        if path.is_dir() {
+            for entry in fs::read_dir(path)? {
+                collect(&entry?.path(), files)?;
+            }
+        } else if path.is_file() {
+            files.push(path.to_owned());
+        }
+        Ok(())
     }
 
-    fn restore(&self) -> Result<()> {
-        fs::write("Cargo.toml", self.cargo_toml.as_bytes())?;
+    fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+        for byte in bytes {
+            hash ^= u64::from(*byte);
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+        hash
+    }
 
-        Ok(())
+    let mut files = Vec::new();
+    for path in ["build.rs", "src", "tests", "benches", "examples"] {
+        collect(Path::new(path), &mut files)?;
+    }
+    files.sort();
+
+    let mut hash = fnv1a(0xcbf29ce484222325, manifest.as_bytes());
+    hash = fnv1a(hash, lockfile.unwrap_or_default().as_bytes());
+    for file in files {
+        hash = fnv1a(hash, file.to_string_lossy().as_bytes());
+        hash = fnv1a(hash, &fs::read(file)?);
     }
+    Ok(format!("{hash:016x}"))
 }
 
-impl Drop for State {
-    fn drop(&mut self) {
-        let _ = self.restore();
+/// Configuration from the `[package.metadata.bounds]` table.
+#[derive(Default)]
+struct Metadata {
+    policy: HashMap<String, DepPolicy>,
+    /// Check commands to use for specific deps.
+    commands: HashMap<String, String>,
+    /// Environment variables for every check, see `--env`.
+    env: Vec<(String, String)>,
+    /// The level of each `lint` rule, over its default.
+    lints: HashMap<LintRule, LintLevel>,
+    /// Where to keep the state, see [`Paths`].
+    state_dir: Option<PathBuf>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+enum DepPolicy {
+    /// The requirement is a range whose versions should all work.
+    #[default]
+    Range,
+    /// The requirement is an exact pin that should be validated.
+    Exact,
+}
+
+impl Metadata {
+    fn load(cargo_toml: &DocumentMut) -> Result<Self> {
+        let Some(bounds) = cargo_toml
+            .get("package")
+            .and_then(|package| package.get("metadata"))
+            .and_then(|metadata| metadata.get("bounds"))
+        else {
+            return Ok(Metadata::default());
+        };
+        let bounds = bounds
+            .as_table_like()
+            .ok_or(anyhow!("[package.metadata.bounds] wasnt a table"))?;
+
+        let mut metadata = Metadata::default();
+        if let Some(policy) = bounds.get("policy") {
+            let policy = policy
+                .as_table_like()
+                .ok_or(anyhow!("bounds policy wasnt a table"))?;
+            for (dep, kind) in policy.iter() {
+                let kind = match kind.as_str() {
+                    Some("range") => DepPolicy::Range,
+                    Some("exact") => DepPolicy::Exact,
+                    _ => return Err(anyhow!("Unknown policy for {dep}, expected range or exact")),
+                };
+                metadata.policy.insert(dep.to_owned(), kind);
+            }
+        }
+        if let Some(commands) = bounds.get("commands") {
+            let commands = commands
+                .as_table_like()
+                .ok_or(anyhow!("bounds commands wasnt a table"))?;
+            for (dep, command) in commands.iter() {
+                let command = command
+                    .as_str()
+                    .ok_or(anyhow!("The command for {dep} wasnt a string"))?;
+                metadata.commands.insert(dep.to_owned(), command.to_owned());
+            }
+        }
+        if let Some(env) = bounds.get("env") {
+            let env = env
+                .as_table_like()
+                .ok_or(anyhow!("bounds env wasnt a table"))?;
+            for (key, value) in env.iter() {
+                let value = value
+                    .as_str()
+                    .ok_or(anyhow!("The env value of {key} wasnt a string"))?;
+                metadata.env.push((key.to_owned(), value.to_owned()));
+            }
+        }
+        if let Some(lints) = bounds.get("lints") {
+            let lints = lints
+                .as_table_like()
+                .ok_or(anyhow!("bounds lints wasnt a table"))?;
+            for (rule, level) in lints.iter() {
+                let rule = LintRule::from_str(rule, false)
+                    .map_err(|_| anyhow!("Unknown lint rule {rule}"))?;
+                let level = level
+                    .as_str()
+                    .and_then(|level| LintLevel::from_str(level, false).ok())
+                    .ok_or(anyhow!(
+                        "Unknown level for lint rule {}, expected allow, warn or deny",
+                        rule.name()
+                    ))?;
+                metadata.lints.insert(rule, level);
+            }
+        }
+        if let Some(state_dir) = bounds.get("state-dir") {
+            let state_dir = state_dir
+                .as_str()
+                .ok_or(anyhow!("bounds state-dir wasnt a string"))?;
+            metadata.state_dir = Some(state_dir.into());
+        }
+        Ok(metadata)
+    }
+
+    fn policy(&self, dep: &str) -> DepPolicy {
+        self.policy.get(dep).copied().unwrap_or_default()
     }
 }
 
-fn main() -> Result<()> {
-    let mut arguments = args_os().collect::<Vec<_>>();
-    if arguments[1].to_string_lossy() == "bounds" {
-        arguments.remove(1);
+/// A release as published on the registry.
+#[derive(Serialize, Deserialize, Clone)]
+struct PublishedVersion {
+    version: semver::Version,
+    yanked: bool,
+    /// The `rust-version` it declares.
+    #[serde(default)]
+    rust_version: Option<String>,
+}
+
+impl PublishedVersion {
+    /// The `rust-version` of this release, when the toolchain is too old for it.
+    fn needs_newer_rust(&self, toolchain: &semver::Version) -> Option<&str> {
+        let needed = self.rust_version.as_deref()?;
+        // `1.75` means `1.75.0`.
+        let mut parts = needed.split('.').map(|part| part.parse::<u64>().ok());
+        let minimum = semver::Version::new(
+            parts.next()??,
+            parts.next().flatten().unwrap_or(0),
+            parts.next().flatten().unwrap_or(0),
+        );
+        (minimum > *toolchain).then_some(needed)
     }
+}
 
-    let cli = Cli::parse_from(arguments);
+/// The user agent of requests to registries, `--user-agent` or [`DEFAULT_USER_AGENT`].
+static USER_AGENT: OnceLock<String> = OnceLock::new();
 
-    let prev_state = State::store()?;
-    let cloned_state = prev_state.clone();
-    ctrlc::set_handler(move || {
-        let _ = cloned_state.restore();
-        std::process::exit(1);
-    })?;
-    let res = main_impl(&prev_state, cli);
-    prev_state.restore()?;
+const DEFAULT_USER_AGENT: &str = "cargo-bounds (vivax3794@pm.me)";
 
-    res
+fn user_agent() -> &'static str {
+    USER_AGENT.get().map_or(DEFAULT_USER_AGENT, String::as_str)
 }
 
-#[inline]
-fn main_impl(state: &State, cli: Cli) -> Result<()> {
-    match cli {
-        Cli::Test(mut test) => {
-            if test.patch {
-                test.minor = true;
-            }
-            let res = sanity_test(state, test)?;
-            if res.failed_deps != 0 {
-                Err(anyhow!("{}", res.print()))
-            } else {
-                Ok(())
-            }
+fn registry_client() -> Result<crates_io_api::SyncClient> {
+    // The spacing is up to the [`RegistryBudget`].
+    Ok(crates_io_api::SyncClient::new(
+        user_agent(),
+        Duration::ZERO,
+    )?)
+}
+
+/// The published versions of `dep`, from its own registry if it has one.
+///
+/// `None` when that registry cant be reached, which skips the dep instead of failing the run.
+fn published_versions(
+    cargo_toml: &DocumentMut,
+    dep: &str,
+) -> Result<Option<Vec<PublishedVersion>>> {
+    match fetch_published(cargo_toml, dep) {
+        Ok(published) => Ok(Some(published)),
+        Err(err) => {
+            let Some(registry) = dep_registry(cargo_toml, dep) else {
+                return Err(err);
+            };
+            out!(
+                "  {} skipping {}, registry {} cant be reached: {err:#}",
+                "warning:".yellow(),
+                dep.blue(),
+                registry.yellow()
+            );
+            Ok(None)
         }
-        Cli::Minimize { dep, skip_sanity } => minimize(state, dep, skip_sanity),
     }
 }
 
-fn minimize(state: &State, dep: Option<String>, skip_sanity: bool) -> Result<()> {
-    let cargo_toml = state.cargo_toml.parse::<DocumentMut>()?;
-    let Some(deps) = cargo_toml.get("dependencies") else {
-        println!("{}", "No dependencies".bright_red());
-        return Ok(());
+/// The published versions of `dep` from its registry, fetched once per run.
+///
+/// They are fetched under the package name, so a renamed dep gets the versions of its package.
+fn fetch_published(cargo_toml: &DocumentMut, dep: &str) -> Result<Vec<PublishedVersion>> {
+    let registry = dep_registry(cargo_toml, dep);
+    let package = package_name(dep);
+    if let Some(published) = remembered_versions(registry.as_deref(), &package) {
+        return Ok(published);
+    }
+    let spinner = Spinner::new(match &registry {
+        Some(registry) => format!(
+            "Fetching versions for {} from {}",
+            package.blue(),
+            registry.yellow()
+        ),
+        None => format!("Fetching versions for {}", package.blue()),
+    });
+    let published = download_versions(registry.as_deref(), &package);
+    spinner.finish_and_clear();
+    published
+}
+
+/// The versions of `dep` fetched earlier in the run, `registry` being `None` for crates.io.
+fn remembered_versions(registry: Option<&str>, dep: &str) -> Option<Vec<PublishedVersion>> {
+    let key = format!("{}/{dep}", registry.unwrap_or("crates-io"));
+    PUBLISHED.lock().unwrap().get(&key).cloned()
+}
+
+/// Fetch the versions of `dep` without any progress, and remember them for the rest of the run.
+fn download_versions(registry: Option<&str>, dep: &str) -> Result<Vec<PublishedVersion>> {
+    let published = match registry {
+        Some(registry) => get_registry_versions(dep, registry)?,
+        None => get_all_versions(dep)?,
     };
-    let deps = deps
-        .as_table()
-        .ok_or(anyhow!("[depdencies] wasnt a table"))?;
+    let key = format!("{}/{dep}", registry.unwrap_or("crates-io"));
+    PUBLISHED.lock().unwrap().insert(key, published.clone());
+    Ok(published)
+}
 
-    if let Some(dep) = dep {
-        if !deps.contains_key(&dep) {
-            return Err(anyhow!("dep {dep} not found."));
-        }
-        minimize_dep(state, &dep, skip_sanity)?;
+/// How many version lists [`prefetch_versions`] fetches at once.
+const PREFETCH_JOBS: usize = 8;
+
+/// Fetch the version lists of `deps` before the first check, several at a time, so the run
+/// doesnt pause for each dependency and its plan is known before anything is built.
+///
+/// Crates from a git index are left for when they are reached, as they share one checkout. So
+/// are failures, which [`published_versions`] reports then.
+fn prefetch_versions(cargo_toml: &DocumentMut, deps: &[DeclaredDep]) {
+    let mut wanted = deps
+        .iter()
+        .filter(|declared| declared.requirement(cargo_toml).is_some())
+        .filter(|declared| patched_section(&declared.name).is_none())
+        .map(|declared| {
+            (
+                dep_registry(cargo_toml, &declared.name),
+                declared.package.as_str(),
+            )
+        })
+        .filter(|(registry, dep)| remembered_versions(registry.as_deref(), dep).is_none())
+        .filter(|(registry, _)| {
+            registry.as_deref().is_none_or(|registry| {
+                registry_index(registry).is_ok_and(|index| index.starts_with("sparse+"))
+            })
+        })
+        .collect::<Vec<_>>();
+    // Two majors of a package under different names share one list.
+    wanted.sort();
+    wanted.dedup();
+    if wanted.is_empty() {
+        return;
+    }
+
+    let bar = if display_mode() == DisplayMode::Plain {
+        let deps = if wanted.len() == 1 { "dep" } else { "deps" };
+        out!("Fetching version info for {} {deps} ...", wanted.len());
+        None
     } else {
-        let deps = deps.iter().map(|(key, _)| key).collect::<Vec<_>>();
-        for dep in deps {
-            minimize_dep(state, dep, skip_sanity)?;
+        Some(add_bar(ProgressBar::new(wanted.len() as u64).with_style(
+            ProgressStyle::with_template("Fetching version info ({pos}/{len})").unwrap(),
+        )))
+    };
+    let next = AtomicUsize::new(0);
+    thread::scope(|scope| {
+        for _ in 0..PREFETCH_JOBS.min(wanted.len()) {
+            scope.spawn(|| {
+                while let Some((registry, dep)) = wanted.get(next.fetch_add(1, Ordering::SeqCst)) {
+                    let _ = download_versions(registry.as_deref(), dep);
+                    if let Some(bar) = &bar {
+                        bar.inc(1);
+                    }
+                }
+            });
         }
+    });
+    if let Some(bar) = bar {
+        bar.finish_and_clear();
     }
-    Ok(())
 }
 
-fn sanity_test(state: &State, config: TestConfig) -> Result<TestSummary> {
-    let cargo_toml = state.cargo_toml.parse::<DocumentMut>()?;
-    let Some(deps) = cargo_toml.get("dependencies") else {
-        println!("{}", "No dependencies".bright_red());
-        return Ok(TestSummary::default());
-    };
-    let deps = deps
-        .as_table()
-        .ok_or(anyhow!("[depdencies] wasnt a table"))?;
-
-    let mut summary = TestSummary::default();
-    if let Some(dep) = &config.dep {
-        if !deps.contains_key(dep) {
-            return Err(anyhow!("dep {dep} not found."));
+/// How many versions `test` is going to check of each of `deps`, as far as the version lists
+/// [`prefetch_versions`] got tell.
+///
+/// The preflight check can still skip some of them, and `--adaptive` can add more.
+fn plan_versions(
+    cargo_toml: &DocumentMut,
+    deps: &[DeclaredDep],
+    config: &TestConfig,
+    metadata: &Metadata,
+) -> HashMap<String, usize> {
+    let mut planned = HashMap::new();
+    for declared in deps {
+        let dep = declared.name.as_str();
+        if (declared.inherited && declared.workspace_entry.is_none())
+            || patched_section(dep).is_some()
+        {
+            continue;
         }
-        summary.failed_versions = sanity_test_dep(state, dep, &config)?;
-        if summary.failed_versions != 0 {
-            summary.failed_deps = 1;
+        let Some(bound) = declared
+            .requirement(cargo_toml)
+            .and_then(|requirement| parse_bound(dep, &requirement).ok())
+        else {
+            continue;
+        };
+        if metadata.policy(dep) == DepPolicy::Exact {
+            planned.insert(dep.to_owned(), 1);
+            continue;
         }
-    } else {
-        let deps = deps.iter().map(|(key, _)| key).collect::<Vec<_>>();
-        for dep in deps {
-            let fails = sanity_test_dep(state, dep, &config)?;
-            summary.failed_versions += fails;
-            if fails != 0 {
-                summary.failed_deps += 1;
-            }
+        let registry = dep_registry(cargo_toml, dep);
+        let Some(published) = remembered_versions(registry.as_deref(), &declared.package) else {
+            continue;
+        };
+        // The preflight check runs cargo, that has to wait for the dep.
+        let config = TestConfig {
+            preflight: false,
+            ..config.for_dep(dep, metadata)
+        };
+        if let Ok(candidates) = Candidates::pick(cargo_toml, dep, &bound, &published, &config) {
+            planned.insert(dep.to_owned(), candidates.selected.len());
         }
     }
-    Ok(summary)
+    planned
 }
 
-#[derive(Default)]
-struct TestSummary {
-    failed_deps: u8,
-    failed_versions: u16,
-}
-
-impl TestSummary {
-    fn print(self) -> String {
-        format!(
-            "{} deps have failing versions in their bounds. ({} versions failed in total)",
-            self.failed_deps.red(),
-            self.failed_versions.yellow()
-        )
+/// Say how many versions the run is going to check, see [`plan_versions`].
+fn print_plan(planned: &HashMap<String, usize>, config: &TestConfig) {
+    let total = planned.values().sum::<usize>();
+    let mut caveats = Vec::new();
+    if config.preflight {
+        caveats.push("the preflight check can skip some");
     }
-}
-
-fn sanity_test_dep(state: &State, dep: &str, config: &TestConfig) -> Result<u16> {
-    let mut cargo_toml = state.cargo_toml.parse::<DocumentMut>()?;
-    let deps = cargo_toml
-        .get_mut("dependencies")
-        .unwrap()
-        .as_table_mut()
-        .unwrap();
-    let dep_item = deps.get_mut(dep).unwrap();
-
-    let bound;
-    if let Some(ver) = dep_item.as_str() {
-        let mut new_table = toml_edit::InlineTable::new();
-        new_table.insert("version", ver.into());
-        bound = semver::VersionReq::parse(ver)?;
-        *dep_item = new_table.into();
-    } else {
-        let ver = dep_item
-            .as_table_like()
-            .ok_or(anyhow!("Unexpected dep type"))?;
-        let Some(ver) = ver.get("version") else {
-            println!("{} {}", "No version info found for".yellow(), dep.green());
-            return Ok(0);
-        };
-        let ver = ver.as_str().ok_or(anyhow!("Expected str"))?;
-        bound = semver::VersionReq::parse(ver)?;
+    if config.adaptive {
+        caveats.push("--adaptive can add more");
     }
+    let caveats = match caveats.is_empty() {
+        true => String::new(),
+        false => format!(" ({})", caveats.join(", ")),
+    };
+    let deps = if planned.len() == 1 { "dep" } else { "deps" };
+    out!(
+        "Testing {} versions of {} {deps}{}",
+        total.yellow(),
+        planned.len().yellow(),
+        caveats.bright_black()
+    );
+}
 
-    println!("{} - {}", dep.blue(), bound.yellow());
+/// The alternative registry `dep` comes from, `None` for crates.io.
+fn dep_registry(cargo_toml: &DocumentMut, dep: &str) -> Option<String> {
+    let registry = item_at(cargo_toml, &declaration_tables(dep)[0])?.get("registry")?;
+    registry.as_str().map(str::to_owned)
+}
 
-    let mut versions = get_versions(dep)?;
-    versions.retain(|version| bound.matches(version));
-    versions.sort();
+/// An entry of a registry index, one per published version.
+#[derive(Deserialize)]
+struct IndexEntry {
+    vers: semver::Version,
+    #[serde(default)]
+    yanked: bool,
+    #[serde(default)]
+    rust_version: Option<String>,
+}
 
-    let mut last_major = u64::MAX;
-    let mut last_minor = u64::MAX;
+/// Read the versions of `dep` from the index of an alternative registry, sparse or git.
+fn get_registry_versions(dep: &str, registry: &str) -> Result<Vec<PublishedVersion>> {
+    let index = registry_index(registry)?;
+    let path = index_path(dep);
 
-    let last_version = versions
-        .last()
-        .ok_or(anyhow!("No versions in bound"))?
-        .clone();
-    let mut fails = 0;
-    for version in versions {
-        if version.major == last_major
-            && ((!config.minor && version.major != 0)
-                || (last_minor == version.minor && !config.patch))
-            && version != last_version
-        {
-            if config.print_skiped {
-                println!("  {}", version.bright_black());
+    let entries = if let Some(url) = index.strip_prefix("sparse+") {
+        let url = format!("{}/{path}", url.trim_end_matches('/'));
+        fetch_sparse(&url, registry_token(registry))?
+    } else {
+        let checkout = paths().registry().join(format!("{registry}-index"));
+        let git = |args: &[&str]| -> Result<()> {
+            let status = Command::new("git")
+                .args(args)
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()?;
+            if !status.success() {
+                return Err(anyhow!("`git {}` failed", args.join(" ")));
             }
-            continue;
+            Ok(())
+        };
+        let checkout_str = &checkout.to_string_lossy();
+        if checkout.exists() {
+            git(&["-C", checkout_str, "fetch", "--depth", "1", "origin"])?;
+            git(&["-C", checkout_str, "reset", "--hard", "FETCH_HEAD"])?;
+        } else {
+            fs::create_dir_all(paths().registry())?;
+            git(&["clone", "--depth", "1", &index, checkout_str])?;
         }
+        fs::read_to_string(checkout.join(&path))
+            .map_err(|err| anyhow!("{dep} isnt in the index of {registry} ({err})"))?
+    };
 
-        last_minor = version.minor;
-        last_major = version.major;
+    parse_index(&entries)
+}
 
-        if let TestResult::Fail = test_version(&mut cargo_toml, dep, version, config)? {
-            fails += 1;
+/// Get a file from a sparse index.
+///
+/// A rate limit or server error is returned as [`Transient`], with the `Retry-After` it came with.
+fn fetch_sparse(url: &str, token: Option<String>) -> Result<String> {
+    let client = reqwest::blocking::Client::builder()
+        .user_agent(user_agent())
+        .build()?;
+    let mut request = client.get(url);
+    if let Some(token) = token {
+        request = request.header("Authorization", token);
+    }
+    let response = request.send()?;
+    let status = response.status();
+    if status.as_u16() == 429 || status.is_server_error() {
+        let retry_after = response
+            .headers()
+            .get("Retry-After")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse::<u64>().ok())
+            .map(Duration::from_secs);
+        return Err(Transient {
+            status: status.as_u16(),
+            retry_after,
         }
+        .into());
     }
+    Ok(response.error_for_status()?.text()?)
+}
 
-    Ok(fails)
+/// A registry answer that is worth asking again for, see [`registry_fetch`].
+#[derive(Debug)]
+struct Transient {
+    status: u16,
+    /// How long the registry asked to wait first.
+    retry_after: Option<Duration>,
 }
 
-fn minimize_dep(state: &State, dep: &str, skip_sanity: bool) -> Result<()> {
-    let mut cargo_toml = state.cargo_toml.parse::<DocumentMut>()?;
-    let deps = cargo_toml
-        .get_mut("dependencies")
-        .unwrap()
-        .as_table_mut()
-        .unwrap();
-    let dep_item = deps.get_mut(dep).unwrap();
-
-    let bound;
-    if let Some(ver) = dep_item.as_str() {
-        let mut new_table = toml_edit::InlineTable::new();
-        new_table.insert("version", ver.into());
-        bound = semver::VersionReq::parse(ver)?;
-        *dep_item = new_table.into();
-    } else {
-        let ver = dep_item
-            .as_table_like()
-            .ok_or(anyhow!("Unexpected dep type"))?;
-        let ver = ver.get("version").ok_or(anyhow!("Expected version key"))?;
-        let ver = ver.as_str().ok_or(anyhow!("Expected str"))?;
-        bound = semver::VersionReq::parse(ver)?;
+impl Display for Transient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the registry answered {}", self.status)
     }
+}
 
-    println!("{} - {}", dep.blue(), bound.yellow());
+impl std::error::Error for Transient {}
 
-    let mut versions = get_versions(dep)?;
-    versions.sort();
+/// Whether a failed request is worth trying again, with how long to wait first if the
+/// registry said so, and whether it was a rate limit.
+fn transient_error(err: &anyhow::Error) -> Option<(Option<Duration>, bool)> {
+    if let Some(transient) = err.downcast_ref::<Transient>() {
+        return Some((transient.retry_after, transient.status == 429));
+    }
+    let http = match err.downcast_ref::<crates_io_api::Error>() {
+        Some(crates_io_api::Error::Http(http)) => http,
+        _ => err.downcast_ref::<reqwest::Error>()?,
+    };
+    let status = http.status().map(|status| status.as_u16());
+    let transient = http.is_timeout()
+        || http.is_connect()
+        || status.is_some_and(|status| status == 429 || status >= 500);
+    transient.then_some((None, status == Some(429)))
+}
 
-    let mut current_supported = versions.clone();
-    current_supported.retain(|version| bound.matches(version));
+/// The releases in the index file of a crate, one JSON object per line.
+fn parse_index(entries: &str) -> Result<Vec<PublishedVersion>> {
+    let mut result = Vec::new();
+    for line in entries.lines().filter(|line| !line.trim().is_empty()) {
+        let entry = serde_json::from_str::<IndexEntry>(line)?;
+        result.push(PublishedVersion {
+            version: entry.vers,
+            yanked: entry.yanked,
+            rust_version: entry.rust_version,
+        });
+    }
+    Ok(result)
+}
 
-    let min_version = current_supported[0].clone();
-    let max_version = current_supported.last().unwrap().clone();
+/// Where the index of a crate lives, e.g. `se/rd/serde`.
+fn index_path(name: &str) -> String {
+    let name = name.to_lowercase();
+    match name.len() {
+        1 => format!("1/{name}"),
+        2 => format!("2/{name}"),
+        3 => format!("3/{}/{name}", &name[..1]),
+        _ => format!("{}/{}/{name}", &name[..2], &name[2..4]),
+    }
+}
 
-    let (min_index, _) = versions
-        .iter()
-        .enumerate()
-        .find(|(_, ver)| **ver == min_version)
-        .unwrap();
-    let (max_index, _) = versions
-        .iter()
-        .enumerate()
-        .find(|(_, ver)| **ver == max_version)
-        .unwrap();
+/// The directory cargo keeps its global config and credentials in.
+fn cargo_home() -> Option<PathBuf> {
+    std::env::var_os("CARGO_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".cargo")))
+}
 
-    println!("  Minimizing {}", versions[min_index].yellow());
-    let min_version = binary_search(
-        &versions[..=min_index],
-        &mut cargo_toml,
-        dep,
-        TestResult::Sucess,
-    )?;
-    println!("  Found min {}", min_version.green());
-    println!("  Maximizing {}", versions[max_index].yellow());
-    let max_version = binary_search(
-        &versions[max_index..],
-        &mut cargo_toml,
-        dep,
-        TestResult::Fail,
-    )?;
-    println!("  Found max {}", max_version.green());
+/// The environment variable cargo reads `key` of a registry from, e.g. `CARGO_REGISTRIES_MY_REG_INDEX`.
+fn registry_env(registry: &str, key: &str) -> Option<String> {
+    let registry = registry.to_uppercase().replace('-', "_");
+    std::env::var(format!(
+        "CARGO_REGISTRIES_{registry}_{}",
+        key.to_uppercase()
+    ))
+    .ok()
+}
 
-    let bound = semver::VersionReq::parse(&format!(">={min_version}, <={max_version}"))?;
-    if skip_sanity {
-        println!("  {}", bound.green());
-        return Ok(());
+/// The index URL of a registry, from the environment or the cargo config files.
+///
+/// Config files closer to the current directory win, like they do for cargo.
+fn registry_index(registry: &str) -> Result<String> {
+    if let Some(index) = registry_env(registry, "index") {
+        return Ok(index);
     }
-    println!("  {} - doing sanity check", bound.green());
-    let mut started = false;
-    let mut last_combo = (u64::MAX, u64::MAX);
-    for version in versions {
-        if version == min_version {
-            started = true;
-        }
-
-        if started {
-            if last_combo == (version.major, version.minor) {
-                continue;
-            }
-
-            test_version(
-                &mut cargo_toml,
-                dep,
-                version.clone(),
-                &TestConfig::default(),
-            )?;
-            last_combo = (version.major, version.minor);
-            if version == max_version {
-                break;
-            }
+    for (_, config) in cargo_configs()? {
+        let index = config
+            .get("registries")
+            .and_then(|registries| registries.get(registry))
+            .and_then(|registry| registry.get("index"))
+            .and_then(|index| index.as_str());
+        if let Some(index) = index {
+            return Ok(index.to_owned());
         }
     }
-    Ok(())
+    Err(anyhow!(
+        "no index for it in .cargo/config.toml, add it under [registries.{registry}]"
+    ))
 }
 
-fn binary_search(
-    versions: &[semver::Version],
-    cargo_toml: &mut DocumentMut,
-    dep: &str,
-    upper_kind: TestResult,
-) -> Result<semver::Version> {
-    let mut low = 0;
-    let mut top = versions.len() - 1;
-
-    while top - low > 1 {
-        let center = (low + top) / 2;
-        let res = test_version(
-            cargo_toml,
-            dep,
-            versions[center].clone(),
-            &TestConfig::default(),
-        )?;
+/// The cargo config files that apply here, the ones closer to the current directory first.
+fn cargo_configs() -> Result<Vec<(PathBuf, DocumentMut)>> {
+    let cwd = std::env::current_dir()?;
+    let configs = cwd
+        .ancestors()
+        .flat_map(|dir| {
+            [
+                dir.join(".cargo").join("config.toml"),
+                dir.join(".cargo").join("config"),
+            ]
+        })
+        .chain(cargo_home().map(|home| home.join("config.toml")))
+        .filter_map(|path| {
+            let config = fs::read_to_string(&path)
+                .ok()?
+                .parse::<DocumentMut>()
+                .ok()?;
+            Some((path, config))
+        })
+        .collect();
+    Ok(configs)
+}
 
-        if res == upper_kind {
-            top = center;
-        } else {
-            low = center;
-        }
+/// The token for a registry, from the environment or cargo's credentials.
+fn registry_token(registry: &str) -> Option<String> {
+    if let Some(token) = registry_env(registry, "token") {
+        return Some(token);
     }
+    let credentials = fs::read_to_string(cargo_home()?.join("credentials.toml")).ok()?;
+    let credentials = credentials.parse::<DocumentMut>().ok()?;
+    let token = credentials.get("registries")?.get(registry)?.get("token")?;
+    token.as_str().map(str::to_owned)
+}
 
-    let low_res = test_version(
-        cargo_toml,
-        dep,
-        versions[low].clone(),
-        &TestConfig::default(),
-    )?;
-    let top_res = test_version(
-        cargo_toml,
+/// Where crates.io serves its sparse index, which lists every release of a crate.
+const CRATES_IO_INDEX: &str = "https://index.crates.io";
+
+/// The releases of `dep` on crates.io.
+///
+/// They come from the sparse index, since the API doesnt promise to list every release of a
+/// crate with hundreds of them. The API is only asked when the index cant be reached, and its
+/// answer is refused when it has fewer releases than it counts or misses the newest.
+fn get_all_versions(dep: &str) -> Result<Vec<PublishedVersion>> {
+    let url = format!("{CRATES_IO_INDEX}/{}", index_path(dep));
+    index_or_api(
         dep,
-        versions[top].clone(),
-        &TestConfig::default(),
-    )?;
+        || {
+            registry_fetch(&format!("{dep}.index"), Endpoint::Index, |_| {
+                parse_index(&fetch_sparse(&url, None)?)
+            })
+        },
+        || {
+            registry_fetch(&format!("{dep}.api"), Endpoint::Api, |client| {
+                Listing::of(client.get_crate(dep)?)
+            })
+        },
+    )
+    .map_err(|err| anyhow!("couldnt get the versions of {dep} from crates.io: {err}"))
+}
 
-    if low_res == top_res {
-        if upper_kind == TestResult::Fail {
-            return Ok(versions[top].clone());
-        } else {
-            return Ok(versions[low].clone());
-        }
+/// [`get_all_versions`] with the requests to the `index` and the `api` passed in.
+fn index_or_api(
+    dep: &str,
+    index: impl FnOnce() -> Result<Vec<PublishedVersion>>,
+    api: impl FnOnce() -> Result<Listing>,
+) -> Result<Vec<PublishedVersion>> {
+    let index_err = match index() {
+        Ok(result) => return Ok(result),
+        Err(err) => err,
+    };
+    let Listing {
+        versions,
+        listed,
+        newest,
+    } = api()?;
+    if versions.len() < listed || !versions.iter().any(|release| release.version == newest) {
+        return Err(anyhow!(
+            "crates.io returned {} of the {listed} releases of {dep} (newest {newest}), and the index couldnt be reached ({index_err})",
+            versions.len()
+        ));
     }
+    Ok(versions)
+}
 
-    if upper_kind == TestResult::Fail {
-        Ok(versions[low].clone())
-    } else {
-        Ok(versions[top].clone())
-    }
+/// The releases of a crate as the crates.io API lists them.
+#[derive(Serialize, Deserialize)]
+struct Listing {
+    versions: Vec<PublishedVersion>,
+    /// How many releases the crate has, which can be more than `versions` holds.
+    listed: usize,
+    newest: semver::Version,
 }
 
-fn test_version(
-    cargo_toml: &mut DocumentMut,
-    dep: &str,
-    version: semver::Version,
-    config: &TestConfig,
-) -> Result<TestResult> {
-    cargo_toml["dependencies"][dep]["version"] = format!("={version}").into();
-    fs::write("Cargo.toml", cargo_toml.to_string())?;
-    run_test(version.blue().to_string(), config)
+impl Listing {
+    fn of(response: crates_io_api::CrateResponse) -> Result<Self> {
+        let mut versions = Vec::new();
+        for version in response.versions {
+            versions.push(PublishedVersion {
+                version: semver::Version::parse(&version.num)?,
+                yanked: version.yanked,
+                rust_version: version.rust_version,
+            });
+        }
+        Ok(Listing {
+            versions,
+            listed: response.crate_data.versions.map_or(0, |ids| ids.len()),
+            newest: semver::Version::parse(&response.crate_data.max_version)?,
+        })
+    }
 }
 
-fn run_test(msg: String, config: &TestConfig) -> Result<TestResult> {
-    let spinner = indicatif::ProgressBar::new_spinner().with_style(
-        ProgressStyle::with_template(&format!("{{spinner:.cyan}} {msg} {{msg}}",))
-            .unwrap()
-            .tick_chars("⠋⠙⠹⠸⠼⠴⠦⠧⠇⠏ "),
-    );
-    spinner.enable_steady_tick(Duration::from_millis(100));
+/// How many times a request that crates.io rate limited is tried again.
+const REGISTRY_RETRIES: u32 = 5;
 
-    let mut command;
-    if let Some(custom_command) = &config.command {
-        command = Command::new("bash");
-        command.arg("-c").arg(custom_command);
-    } else {
-        command = Command::new("cargo");
-        command.arg("check");
-        command.arg("--all-features");
-        command.arg("--color");
-        command.arg("always");
-    }
+/// How old cached registry data may be for `--courtesy` to use it without asking crates.io.
+const COURTESY_CACHE_AGE: Duration = Duration::from_secs(24 * 60 * 60);
 
-    let mut child = command
-        .stdout(Stdio::null())
-        .stderr(Stdio::piped())
-        .spawn()?;
-    let stderr = child.stderr.take().unwrap();
+/// The requests per minute `--courtesy` allows at most.
+const COURTESY_REQUESTS_PER_MINUTE: u32 = 20;
 
-    let mut output = String::new();
-    for line in BufReader::new(stderr).lines().map_while(Result::ok) {
-        output.push_str(&line);
-        output.push('\n');
-        spinner.set_message(line);
+/// The requests made to crates.io by this process, kept within `--requests-per-minute`.
+static REGISTRY_BUDGET: Mutex<RegistryBudget> = Mutex::new(RegistryBudget {
+    base: Duration::from_secs(1),
+    spacing: Duration::from_secs(1),
+    per_minute: 60,
+    courtesy: false,
+    next: None,
+    first: None,
+    requests: 0,
+    throttled: 0,
+    cached: 0,
+    index: 0,
+});
+
+/// What a [`registry_fetch`] asks, which decides whether it counts against the budget.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Endpoint {
+    /// The API, held to `--requests-per-minute`.
+    Api,
+    /// The sparse index, served by a CDN meant for cargo fetching in parallel, so it isnt spaced.
+    Index,
+}
+
+struct RegistryBudget {
+    /// The spacing between requests the budget allows.
+    base: Duration,
+    /// The spacing in use, wider than `base` after slow responses and rate limits.
+    spacing: Duration,
+    per_minute: u32,
+    courtesy: bool,
+    /// When the next request may start.
+    next: Option<Instant>,
+    first: Option<Instant>,
+    requests: u32,
+    /// Requests crates.io answered with 429.
+    throttled: u32,
+    /// Requests answered from the cache instead.
+    cached: u32,
+    /// Reads of the sparse index, which dont count as requests.
+    index: u32,
+}
+
+impl RegistryBudget {
+    /// Allow a request every `spacing`, or less often with `--courtesy`.
+    fn configure(&mut self, spacing: Duration, courtesy: bool) {
+        self.base = if courtesy {
+            spacing.max(Duration::from_secs(60) / COURTESY_REQUESTS_PER_MINUTE)
+        } else {
+            spacing
+        };
+        self.courtesy = courtesy;
+        self.per_minute = (Duration::from_secs(60).as_secs_f64() / self.base.as_secs_f64()) as u32;
+        self.spacing = self.base;
     }
 
-    let res = match child.wait()?.success() {
-        true => TestResult::Sucess,
-        false => TestResult::Fail,
-    };
+    /// Reserve the next slot for a request, returning how long to wait for it.
+    fn reserve(&mut self) -> Duration {
+        let now = Instant::now();
+        let slot = self.next.map_or(now, |next| next.max(now));
+        self.next = Some(slot + self.spacing);
+        self.first.get_or_insert(slot);
+        self.requests += 1;
+        slot - now
+    }
 
-    let res_text = match res {
-        TestResult::Fail => "FAILED".red().to_string(),
-        TestResult::Sucess => "OK".green().to_string(),
-    };
-    spinner.finish_with_message(res_text);
-    if res == TestResult::Fail {
-        println!("{output}");
+    /// Widen the spacing after a transient error or a slow response, and narrow it again
+    /// otherwise. See [`transient_error`] for what `transient` holds.
+    fn observe(&mut self, latency: Duration, transient: Option<(Option<Duration>, bool)>) {
+        match transient {
+            Some((retry_after, throttled)) => {
+                if throttled {
+                    self.throttled += 1;
+                }
+                self.spacing = (self.spacing * 2).min(Duration::from_secs(60));
+                let wait =
+                    retry_after.map_or(self.spacing, |retry_after| retry_after.max(self.spacing));
+                self.next = Some(Instant::now() + wait);
+            }
+            None => self.spacing = (self.spacing * 3 / 4).max(latency).max(self.base),
+        }
     }
-    Ok(res)
 }
 
-#[derive(Clone, Copy, PartialEq, Eq)]
-enum TestResult {
-    Fail,
-    Sucess,
+/// A registry response as stored in the cache.
+#[derive(Serialize, Deserialize)]
+struct Cached<T> {
+    /// Seconds since the unix epoch.
+    fetched_at: u64,
+    data: T,
 }
 
-fn get_versions(dep: &str) -> Result<Vec<semver::Version>> {
-    let spinner = indicatif::ProgressBar::new_spinner()
-        .with_message(format!("Fetching versions for {}", dep.blue()));
-    spinner.enable_steady_tick(Duration::from_millis(100));
-
-    let client = crates_io_api::SyncClient::new(
-        "cargo-bounds (vivax3794@pm.me)",
-        Duration::from_millis(1000),
-    )?;
-    let dep = client.get_crate(dep)?;
+/// Ask crates.io for something within the request budget, caching the answer as `name`.
+///
+/// With `--courtesy` an answer cached less than [`COURTESY_CACHE_AGE`] ago is used instead.
+/// Rate limits, server errors and timeouts are tried again after backing off, or after the
+/// `Retry-After` the registry sent.
+fn registry_fetch<T: Serialize + serde::de::DeserializeOwned>(
+    name: &str,
+    endpoint: Endpoint,
+    request: impl Fn(&crates_io_api::SyncClient) -> Result<T>,
+) -> Result<T> {
+    let path = paths().registry().join(format!("{name}.json"));
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)?
+        .as_secs();
+    if REGISTRY_BUDGET.lock().unwrap().courtesy {
+        let cached = fs::read_to_string(&path)
+            .ok()
+            .and_then(|cached| serde_json::from_str::<Cached<T>>(&cached).ok())
+            .filter(|cached| now.saturating_sub(cached.fetched_at) < COURTESY_CACHE_AGE.as_secs());
+        if let Some(cached) = cached {
+            REGISTRY_BUDGET.lock().unwrap().cached += 1;
+            return Ok(cached.data);
+        }
+    }
 
-    let mut result = Vec::new();
-    for version in dep.versions {
-        if !version.yanked {
-            let ver = semver::Version::parse(&version.num)?;
-            if ver.pre.is_empty() {
-                result.push(ver);
+    let client = registry_client()?;
+    let mut tries = 0;
+    let mut backoff = Duration::ZERO;
+    let data = loop {
+        let wait = match endpoint {
+            Endpoint::Api => REGISTRY_BUDGET.lock().unwrap().reserve(),
+            Endpoint::Index => {
+                REGISTRY_BUDGET.lock().unwrap().index += 1;
+                backoff
             }
+        };
+        thread::sleep(wait);
+        let start = Instant::now();
+        let res = request(&client);
+        let transient = res.as_ref().err().and_then(transient_error);
+        match (endpoint, transient) {
+            (Endpoint::Api, _) => REGISTRY_BUDGET
+                .lock()
+                .unwrap()
+                .observe(start.elapsed(), transient),
+            (Endpoint::Index, Some((retry_after, _))) => {
+                backoff = retry_after.unwrap_or(Duration::from_secs(1 << tries));
+            }
+            (Endpoint::Index, None) => {}
         }
-    }
+        tries += 1;
+        match res {
+            Err(err) if transient.is_some() && tries > REGISTRY_RETRIES => {
+                return Err(anyhow!("{err}, even after {tries} tries"));
+            }
+            Err(_) if transient.is_some() => {}
+            res => break res?,
+        }
+    };
 
-    spinner.finish_and_clear();
-    Ok(result)
+    fs::create_dir_all(paths().registry())?;
+    let cached = Cached {
+        fetched_at: now,
+        data,
+    };
+    write_atomic(&path, serde_json::to_string(&cached)?)?;
+    Ok(cached.data)
+}
+
+/// Print how many requests went to crates.io, to check the run stayed within its budget.
+fn print_registry_stats() {
+    let budget = REGISTRY_BUDGET.lock().unwrap();
+    if budget.requests == 0 && budget.cached == 0 && budget.index == 0 {
+        return;
+    }
+    let minutes = budget
+        .first
+        .map_or(0.0, |first| first.elapsed().as_secs_f64() / 60.0);
+    out!(
+        "{}",
+        format!(
+            "crates.io: {} requests ({} rate limited, {} answered from the cache), {:.1} per minute with a budget of {}, {} reads of the index",
+            budget.requests,
+            budget.throttled,
+            budget.cached,
+            f64::from(budget.requests) / minutes.max(1.0),
+            budget.per_minute,
+            budget.index
+        )
+        .bright_black()
+    );
 }
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,558 @@
+use super::*;
+
+/// A fix of `toml_path` from `old` to `new`.
+fn fix(toml_path: &[&str], old: &str, new: &str) -> Fix {
+    Fix {
+        dep: toml_path[1].to_owned(),
+        manifest: PathBuf::from("Cargo.toml"),
+        toml_path: toml_path.iter().map(|key| key.to_string()).collect(),
+        old: old.to_owned(),
+        new: new.to_owned(),
+        evidence: Evidence {
+            verified_min: semver::Version::new(1, 0, 0),
+            verified_max: semver::Version::new(1, 2, 0),
+            failing: Vec::new(),
+            minimal_versions_min: None,
+        },
+    }
+}
+
+/// Apply `fix` to `manifest` and return the new manifest.
+fn applied(manifest: &str, fix: &Fix) -> Result<String> {
+    let mut document = manifest.parse::<DocumentMut>()?;
+    apply_fix(&mut document, fix)?;
+    Ok(document.to_string())
+}
+
+const MANIFEST: &str = r#"[package]
+name = "demo"   # the name
+version = "0.1.0"
+
+[dependencies]
+# Parsing
+serde = "1.0"  # keep this comment
+itoa = { version = "1", default-features = false } # inline
+ryu.version = "1.0.5"
+ryu.features = ["small"]
+literal = '0.3'
+
+[target.'cfg(unix)'.dependencies]
+libc = { version = "0.2" , optional = true }
+"#;
+
+#[test]
+fn apply_fix_only_changes_the_requirement() {
+    let cases = [
+        (
+            &["dependencies", "serde"][..],
+            "1.0",
+            "1.0.100",
+            r#"serde = "1.0.100"  # keep this comment"#,
+        ),
+        (
+            &["dependencies", "itoa", "version"][..],
+            "1",
+            ">=0.4, <2",
+            r#"itoa = { version = ">=0.4, <2", default-features = false } # inline"#,
+        ),
+        (
+            &["dependencies", "ryu", "version"][..],
+            "1.0.5",
+            "1.0.10",
+            r#"ryu.version = "1.0.10""#,
+        ),
+        (
+            &["dependencies", "literal"][..],
+            "0.3",
+            "0.3.2",
+            "literal = '0.3.2'",
+        ),
+        (
+            &["target", "cfg(unix)", "dependencies", "libc", "version"][..],
+            "0.2",
+            "0.2.100",
+            r#"libc = { version = "0.2.100" , optional = true }"#,
+        ),
+    ];
+    for (path, old, new, line) in cases {
+        let original_line = MANIFEST
+            .lines()
+            .find(|original| original.starts_with(line.split([' ', '.']).next().unwrap()))
+            .unwrap();
+        let expected = MANIFEST.replacen(original_line, line, 1);
+        assert_eq!(applied(MANIFEST, &fix(path, old, new)).unwrap(), expected);
+    }
+}
+
+#[test]
+fn apply_fix_refuses_a_changed_requirement() {
+    let err = applied(MANIFEST, &fix(&["dependencies", "serde"], "0.9", "1.0.100")).unwrap_err();
+    assert!(err.to_string().contains("expected \"0.9\""), "{err}");
+    assert!(applied(MANIFEST, &fix(&["dependencies", "missing"], "1", "2")).is_err());
+    assert!(applied(MANIFEST, &fix(&["dependencies", "itoa"], "1", "2")).is_err());
+}
+
+fn interval(req: &str) -> Option<Interval> {
+    Interval::of(&semver::VersionReq::parse(req).unwrap())
+}
+
+/// The interval `low..high`, with an empty `high` for no upper bound.
+fn between(low: &str, high: &str) -> Interval {
+    Interval {
+        low: semver::Version::parse(low).unwrap(),
+        high: (!high.is_empty()).then(|| semver::Version::parse(high).unwrap()),
+    }
+}
+
+#[test]
+fn interval_endpoints() {
+    let cases = [
+        ("^1.2.3", "1.2.3", "2.0.0"),
+        ("1.2", "1.2.0", "2.0.0"),
+        ("^0.2.3", "0.2.3", "0.3.0"),
+        ("^0.0.3", "0.0.3", "0.0.4"),
+        ("^0.0", "0.0.0", "0.1.0"),
+        ("^0", "0.0.0", "1.0.0"),
+        ("~1.2.3", "1.2.3", "1.3.0"),
+        ("~1.2", "1.2.0", "1.3.0"),
+        ("~1", "1.0.0", "2.0.0"),
+        ("~0.0.3", "0.0.3", "0.1.0"),
+        ("=1.2.3", "1.2.3", "1.2.4"),
+        ("=1.2", "1.2.0", "1.3.0"),
+        ("1.*", "1.0.0", "2.0.0"),
+        ("1.2.*", "1.2.0", "1.3.0"),
+        ("*", "0.0.0", ""),
+        (">1.2.3", "1.2.4", ""),
+        (">1.2", "1.3.0", ""),
+        (">=1.2, <1.5", "1.2.0", "1.5.0"),
+        ("<=1.4", "0.0.0", "1.5.0"),
+        (">=1.0.0, <=1.4.2", "1.0.0", "1.4.3"),
+    ];
+    for (req, low, high) in cases {
+        assert_eq!(interval(req), Some(between(low, high)), "{req}");
+    }
+    assert_eq!(interval(">=2, <1.5"), None);
+    assert_eq!(interval(">1.2.3, <1.2.4"), None);
+}
+
+#[test]
+fn interval_series() {
+    let series = |req: &str| interval(req).unwrap().series().map(|v| v.to_string());
+    assert_eq!(series("^1.2.3").as_deref(), Some("1.0.0"));
+    assert_eq!(series("~1.2").as_deref(), Some("1.0.0"));
+    assert_eq!(series("^0.2.3").as_deref(), Some("0.2.0"));
+    assert_eq!(series("=0.0.3").as_deref(), Some("0.0.3"));
+    assert_eq!(series("^0.0.3").as_deref(), Some("0.0.3"));
+    assert_eq!(series(">=1.2, <=1.9").as_deref(), Some("1.0.0"));
+    assert_eq!(series("^0"), None);
+    assert_eq!(series("^0.0"), None);
+    assert_eq!(series("~0.0.3"), None);
+    assert_eq!(series(">=1.2, <3"), None);
+    assert_eq!(series(">=1.2"), None);
+    assert_eq!(series("*"), None);
+}
+
+#[test]
+fn conflicting_requirements() {
+    let conflict = |a: &str, b: &str| {
+        let (a, b) = (
+            semver::VersionReq::parse(a).unwrap(),
+            semver::VersionReq::parse(b).unwrap(),
+        );
+        let conflict = requirements_conflict(&a, &b);
+        assert_eq!(conflict, requirements_conflict(&b, &a), "not symmetric");
+        conflict
+    };
+    assert!(conflict("=1.2.3", "^1.3"));
+    assert!(conflict("~1.2", "^1.3"));
+    assert!(conflict("^0.2.5", ">=0.2.0, <0.2.5"));
+    assert!(conflict(">=1.2, <1.4", "=1.5.0"));
+
+    assert!(!conflict("^1.2", "^1.3"));
+    assert!(!conflict("^1", "^2"));
+    assert!(!conflict("^0.2", "^0.3"));
+    assert!(!conflict("^0.0.3", "^0.0.4"));
+    assert!(!conflict("^0.2.5", "<0.2.5"));
+    // Spans several series, so cargo can pick one version of each.
+    assert!(!conflict(">=1, <3", "=2.5.0"));
+    assert!(!conflict("*", "=1.0.0"));
+    assert!(!conflict("^0", "=0.3.0"));
+}
+
+/// A directory of its own in the temp dir, for tests that write files.
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("cargo-bounds-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn write_atomic_replaces_the_file() {
+    let dir = scratch("write-atomic");
+    let path = dir.join("Cargo.toml");
+    fs::write(&path, "old").unwrap();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+    }
+
+    write_atomic(&path, "new").unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o640);
+    }
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn failed_write_atomic_leaves_the_file() {
+    let dir = scratch("write-atomic-fails");
+    let path = dir.join("Cargo.toml");
+    fs::write(&path, "old").unwrap();
+
+    // Fails halfway through writing, and after writing everything but before the rename.
+    let halfway = write_atomic_with(&path, |file| {
+        file.write_all(b"ne")?;
+        Err(std::io::Error::other("disk full"))
+    });
+    assert!(halfway.is_err());
+    let before_rename = write_atomic_with(&path, |file| {
+        file.write_all(b"new")?;
+        file.flush()?;
+        Err(std::io::Error::other("killed"))
+    });
+    assert!(before_rename.is_err());
+
+    assert_eq!(fs::read_to_string(&path).unwrap(), "old");
+    // The temporary file is cleaned up.
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+fn releases(versions: &[&str]) -> Vec<PublishedVersion> {
+    versions
+        .iter()
+        .map(|version| PublishedVersion {
+            version: semver::Version::parse(version).unwrap(),
+            yanked: false,
+            rust_version: None,
+        })
+        .collect()
+}
+
+fn listing(versions: &[&str], listed: usize, newest: &str) -> Listing {
+    Listing {
+        versions: releases(versions),
+        listed,
+        newest: semver::Version::parse(newest).unwrap(),
+    }
+}
+
+fn listed(result: Result<Vec<PublishedVersion>>) -> Result<Vec<String>> {
+    Ok(result?
+        .iter()
+        .map(|release| release.version.to_string())
+        .collect())
+}
+
+#[test]
+fn versions_prefer_the_index() {
+    let found = index_or_api(
+        "demo",
+        || Ok(releases(&["1.0.0", "1.1.0"])),
+        || panic!("the api is only asked when the index fails"),
+    );
+    assert_eq!(listed(found).unwrap(), ["1.0.0", "1.1.0"]);
+}
+
+#[test]
+fn versions_fall_back_to_the_api() {
+    let found = index_or_api(
+        "demo",
+        || Err(anyhow!("index down")),
+        || Ok(listing(&["1.0.0", "1.1.0"], 2, "1.1.0")),
+    );
+    assert_eq!(listed(found).unwrap(), ["1.0.0", "1.1.0"]);
+
+    let failed = index_or_api(
+        "demo",
+        || Err(anyhow!("index down")),
+        || Err(anyhow!("api down")),
+    );
+    assert_eq!(failed.err().unwrap().to_string(), "api down");
+}
+
+#[test]
+fn versions_refuse_an_incomplete_api() {
+    let missing_some = index_or_api(
+        "demo",
+        || Err(anyhow!("index down")),
+        || Ok(listing(&["1.0.0", "1.1.0"], 3, "1.1.0")),
+    );
+    assert_eq!(
+        missing_some.err().unwrap().to_string(),
+        "crates.io returned 2 of the 3 releases of demo (newest 1.1.0), and the index couldnt be reached (index down)"
+    );
+
+    let missing_newest = index_or_api(
+        "demo",
+        || Err(anyhow!("index down")),
+        || Ok(listing(&["1.0.0", "1.1.0"], 2, "1.2.0")),
+    );
+    assert!(missing_newest.is_err());
+}
+
+/// The program and arguments `command` runs.
+fn words(command: &Command) -> Vec<String> {
+    std::iter::once(command.get_program())
+        .chain(command.get_args())
+        .map(|word| word.to_string_lossy().into_owned())
+        .collect()
+}
+
+#[test]
+fn custom_check_command_splits_like_a_shell() {
+    let split = |custom: &str| words(&custom_check_command(custom, None).unwrap());
+    assert_eq!(split("cargo check"), ["cargo", "check"]);
+    assert_eq!(split("  cargo   check  "), ["cargo", "check"]);
+    assert_eq!(
+        split(r#"cargo test --features "a b" -- --skip 'slow one'"#),
+        [
+            "cargo",
+            "test",
+            "--features",
+            "a b",
+            "--",
+            "--skip",
+            "slow one"
+        ]
+    );
+    assert_eq!(
+        split(r#"./my\ script.sh "it's" 'say "hi"'"#),
+        ["./my script.sh", "it's", r#"say "hi""#]
+    );
+    assert_eq!(split("echo ''"), ["echo", ""]);
+
+    assert!(custom_check_command("", None).is_err());
+    assert!(custom_check_command("   ", None).is_err());
+    assert!(custom_check_command(r#"echo "unclosed"#, None).is_err());
+}
+
+#[test]
+fn custom_check_command_in_a_shell() {
+    let in_shell =
+        |custom: &str, shell: &str| words(&custom_check_command(custom, Some(shell)).unwrap());
+    let custom = r#"cargo check && echo "done" | tee log"#;
+    assert_eq!(in_shell(custom, "bash"), ["bash", "-c", custom]);
+    assert_eq!(in_shell(custom, "/bin/sh"), ["/bin/sh", "-c", custom]);
+    assert_eq!(in_shell(custom, "cmd"), ["cmd", "/C", custom]);
+    assert_eq!(in_shell(custom, "CMD.EXE"), ["CMD.EXE", "/C", custom]);
+    assert_eq!(in_shell(custom, "pwsh"), ["pwsh", "-c", custom]);
+}
+
+#[test]
+fn expand_placeholders_and_braces() {
+    let version = semver::Version::parse("1.2.3-rc.1").unwrap();
+    let expand = |command: &str| expand_placeholders(command, "serde", &version);
+    assert_eq!(
+        expand("cargo test -p {dep}@{version}"),
+        "cargo test -p serde@1.2.3-rc.1"
+    );
+    assert_eq!(expand("{dep}{dep} {version}"), "serdeserde 1.2.3-rc.1");
+    assert_eq!(
+        expand("echo {{dep}} {{{version}}}"),
+        "echo {dep} {1.2.3-rc.1}"
+    );
+    assert_eq!(expand("awk '{ print $1 }'"), "awk '{ print $1 }'");
+    assert_eq!(expand("echo }} {{ } {"), "echo } { } {");
+    assert_eq!(expand("{Dep} {ver} {"), "{Dep} {ver} {");
+    assert_eq!(expand("no placeholders"), "no placeholders");
+}
+
+#[test]
+fn compiled_units_counts_cargo_lines() {
+    let count = |lines: &[&str]| {
+        compiled_units(
+            &lines
+                .iter()
+                .map(|line| line.to_string())
+                .collect::<Vec<_>>(),
+        )
+    };
+    assert_eq!(
+        count(&[
+            "   Compiling itoa v1.0.1",
+            "\u{1b}[1m\u{1b}[32m    Checking\u{1b}[0m demo v0.1.0",
+            "    Finished `dev` profile [unoptimized] target(s) in 1.00s",
+        ]),
+        Some(2)
+    );
+    assert_eq!(
+        count(&["    Finished `dev` profile target(s) in 0.01s"]),
+        Some(0)
+    );
+    // Not cargo, so nothing can be said about what was compiled.
+    assert_eq!(count(&["ok"]), None);
+    assert_eq!(count(&["   Compiling itoa v1.0.1", "error: oops"]), None);
+}
+
+#[test]
+fn parse_cargo_version_output() {
+    let parse = |output: &str| parse_cargo_version(output).map(|version| version.to_string());
+    assert_eq!(
+        parse("cargo 1.85.0 (d73d2caf9 2024-12-31)\n").as_deref(),
+        Some("1.85.0")
+    );
+    assert_eq!(
+        parse("cargo 1.86.0-nightly (cecde95c1 2025-01-24)").as_deref(),
+        Some("1.86.0")
+    );
+    assert_eq!(parse("cargo 1.15.0").as_deref(), Some("1.15.0"));
+    assert_eq!(parse("cargo-bounds 1.85.0"), None);
+    assert_eq!(parse("cargo 1.85"), None);
+    assert_eq!(parse("error: no such toolchain"), None);
+    assert_eq!(parse(""), None);
+}
+
+#[test]
+fn cargo_features_are_gated_on_the_version() {
+    let supports = |found: Option<&str>| {
+        let found = found.map(|found| parse_cargo_version(found).unwrap());
+        supported_by(CargoFeature::AllFeatures, found.as_ref())
+    };
+    assert_eq!(CargoFeature::AllFeatures.requirement().0, "--all-features");
+    assert!(!supports(Some("cargo 1.14.0")));
+    assert!(supports(Some("cargo 1.15.0")));
+    // A nightly of the first version with the flag has it too.
+    assert!(supports(Some("cargo 1.15.0-nightly (abc 2017-01-01)")));
+    assert!(supports(Some("cargo 1.85.0")));
+    // Without a version the flag is passed, and cargo says if it's unknown.
+    assert!(supports(None));
+}
+
+#[test]
+fn write_artifacts_layout() {
+    let dir = scratch("artifacts");
+    let log_dir = dir.join("state/logs");
+    let logs = [
+        log_dir.join("itoa-1.0.0.log"),
+        log_dir.join("first/baseline.log"),
+        log_dir.join("second/baseline.log"),
+    ];
+    for log in &logs {
+        fs::create_dir_all(log.parent().unwrap()).unwrap();
+        fs::write(log, log.to_string_lossy().as_bytes()).unwrap();
+    }
+    WRITTEN_LOGS.lock().unwrap().extend(logs.iter().cloned());
+
+    let bundle = dir.join("bundle");
+    write_artifacts(&bundle, &TestSummary::default(), &log_dir).unwrap();
+    WRITTEN_LOGS.lock().unwrap().clear();
+
+    // Logs of the same name from different members dont overwrite each other.
+    for (log, copy) in logs.iter().zip([
+        "logs/itoa-1.0.0.log",
+        "logs/first/baseline.log",
+        "logs/second/baseline.log",
+    ]) {
+        assert_eq!(
+            fs::read_to_string(bundle.join(copy)).unwrap(),
+            log.to_string_lossy()
+        );
+    }
+    let results = fs::read_to_string(bundle.join("results.json")).unwrap();
+    assert!(serde_json::from_str::<serde_json::Value>(&results).is_ok());
+    assert!(bundle.join("summary.txt").exists());
+
+    let index = fs::read_to_string(bundle.join("index.json")).unwrap();
+    let index = serde_json::from_str::<serde_json::Value>(&index).unwrap();
+    assert_eq!(index["schema_version"], ARTIFACTS_SCHEMA);
+    let files = index["files"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|file| {
+            format!(
+                "{} {}",
+                file["kind"].as_str().unwrap(),
+                file["path"].as_str().unwrap()
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        files,
+        [
+            "results results.json",
+            "summary summary.txt",
+            "log logs/itoa-1.0.0.log",
+            "log logs/first/baseline.log",
+            "log logs/second/baseline.log",
+        ]
+    );
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn log_stderr_without_colors() {
+    let log = "$ \"cargo\" \"check\"\nchecked in 1.0s, waited 0.0s for the build lock\n\n--- stderr ---\n\u{1b}[1m\u{1b}[31merror\u{1b}[0m: oops\n\n--- stdout ---\nout\n";
+    assert_eq!(log_stderr(log), "error: oops\n");
+    assert_eq!(log_stderr("no sections"), "no sections");
+}
+
+#[test]
+fn baseline_is_refused_with_explore() {
+    let parse = |args: &[&str]| Args::try_parse_from([&["cargo-bounds"][..], args].concat());
+    assert!(parse(&["test", "--baseline", "old.json"]).is_ok());
+    for args in [
+        &["test", "--explore", "--baseline", "old.json"][..],
+        &["check-crate", "demo", "--explore", "--baseline", "old.json"],
+    ] {
+        let err = parse(args).unwrap_err();
+        assert_eq!(err.kind(), clap::error::ErrorKind::ArgumentConflict);
+    }
+}
+
+#[test]
+fn knowledge_replaces_an_earlier_result() {
+    let version = semver::Version::new(1, 0, 0);
+    let mut knowledge = Knowledge::default();
+    knowledge.remember("itoa", "check", version.clone(), TestResult::Sucess);
+    knowledge.remember("itoa", "check", version.clone(), TestResult::Fail);
+    assert_ne!(
+        knowledge.lookup("itoa", "check", &version),
+        Some(TestResult::Sucess)
+    );
+
+    knowledge.remember("itoa", "check", version.clone(), TestResult::Sucess);
+    knowledge.remember("itoa", "check", version.clone(), TestResult::Indeterminate);
+    assert_eq!(knowledge.lookup("itoa", "check", &version), None);
+}
+
+#[test]
+fn fresh_failure_replaces_a_cached_pass() {
+    let version = semver::Version::new(1, 0, 0);
+    let mut knowledge = Knowledge::default();
+    knowledge.remember("ryu", "check", version.clone(), TestResult::Sucess);
+    assert_eq!(
+        knowledge.lookup("ryu", "check", &version),
+        Some(TestResult::Sucess)
+    );
+
+    // A `--no-cache` run checks again and records what it found.
+    NO_CACHE.store(true, Ordering::Relaxed);
+    assert_eq!(knowledge.lookup("ryu", "check", &version), None);
+    knowledge.remember("ryu", "check", version.clone(), TestResult::Fail);
+    NO_CACHE.store(false, Ordering::Relaxed);
+
+    // The next run checks it again, or reports the failure with `--cache-failures`.
+    assert_ne!(
+        knowledge.lookup("ryu", "check", &version),
+        Some(TestResult::Sucess)
+    );
+}
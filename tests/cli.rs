@@ -0,0 +1,464 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::{Child, Command, Output, Stdio},
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// A project of its own in the temp dir, removed again at the end of the test.
+struct Project {
+    dir: PathBuf,
+}
+
+impl Project {
+    fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("cargo-bounds-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(
+            dir.join("Cargo.toml"),
+            "[package]\nname = \"demo\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n[dependencies]\n",
+        )
+        .unwrap();
+        fs::write(dir.join("src/lib.rs"), "").unwrap();
+        Project { dir }
+    }
+
+    /// A project depending on `itoa`, with its releases already in the registry cache so
+    /// `--courtesy` runs dont ask crates.io.
+    fn with_dep(name: &str) -> Self {
+        let project = Project::new(name);
+        let manifest = project.dir.join("Cargo.toml");
+        let mut contents = fs::read_to_string(&manifest).unwrap();
+        contents.push_str("itoa = \"1.0.1\"   # the one dep\n");
+        fs::write(&manifest, contents).unwrap();
+        fs::write(
+            project.dir.join("Cargo.lock"),
+            "# the lockfile\nversion = 3\n",
+        )
+        .unwrap();
+
+        project.seed_registry(&project.dir.join("target/cargo-bounds"));
+        project
+    }
+
+    /// Put the releases of `itoa` in the registry cache of the state directory `state`.
+    fn seed_registry(&self, state: &Path) {
+        let registry = state.join("registry");
+        fs::create_dir_all(&registry).unwrap();
+        let releases = (1..=8)
+            .map(|patch| format!(r#"{{"version":"1.0.{patch}","yanked":false}}"#))
+            .collect::<Vec<_>>()
+            .join(",");
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        fs::write(
+            registry.join("itoa.index.json"),
+            format!(r#"{{"fetched_at":{now},"data":[{releases}]}}"#),
+        )
+        .unwrap();
+    }
+
+    fn read(&self, file: &str) -> Vec<u8> {
+        fs::read(self.dir.join(file)).unwrap()
+    }
+
+    fn lock(&self) -> PathBuf {
+        self.dir.join("target/cargo-bounds/.lock")
+    }
+
+    /// Make the lock look like it is held by `pid`.
+    fn hold_lock(&self, pid: u32) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        fs::create_dir_all(self.lock().parent().unwrap()).unwrap();
+        fs::write(self.lock(), format!("{pid} {now}\n")).unwrap();
+    }
+
+    fn bounds(&self, args: &[&str]) -> Command {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_cargo-bounds"));
+        command
+            .current_dir(&self.dir)
+            .args(["--display", "plain", "--color", "never"])
+            .args(args)
+            .env_remove("CARGO_BOUNDS_STATE_DIR")
+            .env("CARGO_NET_OFFLINE", "true");
+        command
+    }
+
+    fn run(&self, args: &[&str]) -> Output {
+        self.bounds(args).output().unwrap()
+    }
+}
+
+impl Drop for Project {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Every file under `dir` with its contents, relative to `dir`.
+fn files(dir: &Path) -> Vec<(PathBuf, Vec<u8>)> {
+    let mut files = Vec::new();
+    let mut dirs = vec![dir.to_owned()];
+    while let Some(next) = dirs.pop() {
+        for entry in fs::read_dir(next).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                dirs.push(path);
+            } else {
+                let contents = fs::read(&path).unwrap();
+                files.push((path.strip_prefix(dir).unwrap().to_owned(), contents));
+            }
+        }
+    }
+    files.sort();
+    files
+}
+
+fn text(output: &Output) -> String {
+    format!(
+        "{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    )
+}
+
+/// A process that stays alive until killed, standing in for another run.
+fn other_run() -> Child {
+    Command::new("sleep").arg("600").spawn().unwrap()
+}
+
+#[cfg(unix)]
+#[test]
+fn second_run_is_refused() {
+    let project = Project::new("lock-refused");
+    let mut other = other_run();
+    project.hold_lock(other.id());
+
+    let output = project.run(&["restore"]);
+    assert!(!output.status.success());
+    let message = format!("another cargo-bounds run (pid {}, started", other.id());
+    assert!(text(&output).contains(&message), "{}", text(&output));
+    // The lock still belongs to the other run.
+    assert!(
+        fs::read_to_string(project.lock())
+            .unwrap()
+            .starts_with(&other.id().to_string())
+    );
+
+    other.kill().unwrap();
+    other.wait().unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn waiting_run_starts_when_the_other_is_gone() {
+    let project = Project::new("lock-wait");
+    let mut other = other_run();
+    project.hold_lock(other.id());
+
+    let waiting = project
+        .bounds(&["--wait", "restore"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    thread::sleep(Duration::from_secs(2));
+    // A run without `--wait` is still refused while both are around.
+    assert!(!project.run(&["restore"]).status.success());
+
+    other.kill().unwrap();
+    other.wait().unwrap();
+    let output = waiting.wait_with_output().unwrap();
+    assert!(output.status.success(), "{}", text(&output));
+    assert!(text(&output).contains("Waiting for another cargo-bounds run"));
+    assert!(!project.lock().exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn stale_lock_is_taken_over() {
+    let project = Project::new("lock-stale");
+    let mut gone = Command::new("true").spawn().unwrap();
+    gone.wait().unwrap();
+    project.hold_lock(gone.id());
+
+    let output = project.run(&["restore"]);
+    assert!(output.status.success(), "{}", text(&output));
+    let message = format!("removing the lock of pid {}, which is gone", gone.id());
+    assert!(text(&output).contains(&message), "{}", text(&output));
+    assert!(!project.lock().exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn forced_run_takes_over_the_lock() {
+    let project = Project::new("lock-force");
+    let mut other = other_run();
+    project.hold_lock(other.id());
+
+    let output = project.run(&["--force", "restore"]);
+    assert!(output.status.success(), "{}", text(&output));
+    let message = format!("taking over the lock of pid {}", other.id());
+    assert!(text(&output).contains(&message), "{}", text(&output));
+    assert!(!project.lock().exists());
+
+    other.kill().unwrap();
+    other.wait().unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn interrupted_runs_restore_the_manifest() {
+    let project = Project::with_dep("interrupted");
+    let manifest = project.read("Cargo.toml");
+    let lockfile = project.read("Cargo.lock");
+    // The check rewrites the lockfile like cargo does, so both need restoring.
+    let check = "sh -c 'echo \\# {version} >> Cargo.lock; sleep 0.1'";
+
+    let mut seed = 0x2545_f491_4f6c_dd1d_u64;
+    for _ in 0..12 {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        let mut run = project
+            .bounds(&[
+                "--courtesy",
+                "--no-cache",
+                "test",
+                "--patch",
+                "--command",
+                check,
+            ])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        // Anywhere from starting up to the last check.
+        thread::sleep(Duration::from_millis(200 + seed % 1800));
+        Command::new("kill")
+            .args(["-INT", &run.id().to_string()])
+            .status()
+            .unwrap();
+        run.wait().unwrap();
+
+        assert_eq!(
+            String::from_utf8_lossy(&project.read("Cargo.toml")),
+            String::from_utf8_lossy(&manifest)
+        );
+        assert_eq!(
+            String::from_utf8_lossy(&project.read("Cargo.lock")),
+            String::from_utf8_lossy(&lockfile)
+        );
+        assert!(
+            !project
+                .dir
+                .join("target/cargo-bounds/snapshot.json")
+                .exists()
+        );
+        assert!(!project.lock().exists());
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn check_that_compiled_nothing_is_run_again() {
+    let project = Project::with_dep("zero-compile");
+    // Stands in for cargo, with `1.0.1` already in its cache the first time it is checked.
+    let script = r#"
+echo "$1" >> checks.txt
+if [ "$1" = 1.0.1 ] && [ "$(grep -c '^1.0.1$' checks.txt)" = 1 ]; then
+    echo "    Finished dev [unoptimized] target(s) in 0.01s" >&2
+else
+    echo "   Checking demo v0.1.0" >&2
+    echo "    Finished dev [unoptimized] target(s) in 0.01s" >&2
+fi
+"#;
+    fs::write(project.dir.join("check.sh"), script).unwrap();
+
+    let output = project.run(&[
+        "--courtesy",
+        "--no-cache",
+        "test",
+        "--command",
+        "sh check.sh {version}",
+    ]);
+    assert!(output.status.success(), "{}", text(&output));
+    assert!(
+        text(&output).contains("nothing was compiled for 1.0.1, checking again without the cache"),
+        "{}",
+        text(&output)
+    );
+    let checks = fs::read_to_string(project.dir.join("checks.txt")).unwrap();
+    assert_eq!(
+        checks.lines().filter(|line| *line == "1.0.1").count(),
+        2,
+        "{checks}"
+    );
+    assert_eq!(
+        checks.lines().filter(|line| *line == "1.0.8").count(),
+        1,
+        "{checks}"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn nothing_is_written_outside_the_state_dir() {
+    let project = Project::new("sandbox");
+    let manifest = project.dir.join("Cargo.toml");
+    let mut contents = fs::read_to_string(&manifest).unwrap();
+    contents.push_str("itoa = \"1.0.1\"\n");
+    fs::write(&manifest, contents).unwrap();
+    let sandbox = Project::new("sandbox-state");
+    let state = sandbox.dir.join("state");
+    let home = sandbox.dir.join("home");
+    fs::create_dir_all(&home).unwrap();
+    project.seed_registry(&state);
+    let before = files(&project.dir);
+
+    let cargo_home = std::env::var_os("CARGO_HOME").map_or_else(
+        || std::env::home_dir().unwrap().join(".cargo"),
+        PathBuf::from,
+    );
+    let rustup_home = std::env::var_os("RUSTUP_HOME").map_or_else(
+        || std::env::home_dir().unwrap().join(".rustup"),
+        PathBuf::from,
+    );
+    let output = project
+        .bounds(&[
+            "--state-dir",
+            state.to_str().unwrap(),
+            "--courtesy",
+            "--no-cache",
+            "test",
+            "--skip-baseline",
+            "--keep-all-logs",
+            "--command",
+            "sh -c 'test {version} != 1.0.8'",
+        ])
+        .env("HOME", &home)
+        .env("CARGO_HOME", cargo_home)
+        .env("RUSTUP_HOME", rustup_home)
+        .output()
+        .unwrap();
+    assert!(text(&output).contains("1.0.8 FAILED"), "{}", text(&output));
+
+    assert_eq!(files(&project.dir), before);
+    assert!(files(&home).is_empty());
+    let written = files(&state)
+        .into_iter()
+        .map(|(path, _)| path)
+        .collect::<Vec<_>>();
+    for expected in [
+        "logs/itoa-1.0.1.log",
+        "logs/itoa-1.0.8.log",
+        "knowledge.json",
+    ] {
+        assert!(written.contains(&PathBuf::from(expected)), "{written:?}");
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn patched_version_stays_out_of_the_other_checks() {
+    let project = Project::with_dep("patch-version");
+    let manifest = project.read("Cargo.toml");
+    let lockfile = project.read("Cargo.lock");
+    fs::create_dir_all(project.dir.join("seen")).unwrap();
+
+    let output = project.run(&[
+        "--courtesy",
+        "--no-cache",
+        "test",
+        "--patch",
+        "--skip-baseline",
+        "--patch-version",
+        "itoa=1.0.3=../fix",
+        "--command",
+        "cp Cargo.toml seen/{version}.toml",
+    ]);
+    assert!(output.status.success(), "{}", text(&output));
+    assert!(
+        text(&output).contains("(patched from ../fix)"),
+        "{}",
+        text(&output)
+    );
+
+    let seen = |version: &str| {
+        fs::read_to_string(project.dir.join(format!("seen/{version}.toml"))).unwrap()
+    };
+    let patched = seen("1.0.3");
+    assert!(patched.contains("version = \"=1.0.3\""), "{patched}");
+    assert!(patched.contains("[patch.crates-io]"), "{patched}");
+    assert!(
+        patched.contains("itoa = { path = \"../fix\" }"),
+        "{patched}"
+    );
+    for version in ["1.0.1", "1.0.2", "1.0.4", "1.0.8"] {
+        let pinned = seen(version);
+        assert!(
+            pinned.contains(&format!("version = \"={version}\"")),
+            "{pinned}"
+        );
+        assert!(!pinned.contains("patch"), "{pinned}");
+    }
+    assert_eq!(project.read("Cargo.toml"), manifest);
+    assert_eq!(project.read("Cargo.lock"), lockfile);
+}
+
+#[cfg(unix)]
+#[test]
+fn ignored_patches_are_put_back() {
+    let project = Project::with_dep("ignore-patches");
+    let manifest_path = project.dir.join("Cargo.toml");
+    let mut manifest = fs::read_to_string(&manifest_path).unwrap();
+    manifest.push_str("\n# Our fork\n[patch.crates-io]\nitoa = { path = \"../itoa\" } # local\n");
+    fs::write(&manifest_path, &manifest).unwrap();
+    fs::create_dir_all(project.dir.join("seen")).unwrap();
+
+    let output = project.run(&[
+        "--courtesy",
+        "--no-cache",
+        "--ignore-patches",
+        "test",
+        "--skip-baseline",
+        "--command",
+        "cp Cargo.toml seen/{version}.toml",
+    ]);
+    assert!(output.status.success(), "{}", text(&output));
+    for version in ["1.0.1", "1.0.8"] {
+        let pinned = fs::read_to_string(project.dir.join(format!("seen/{version}.toml"))).unwrap();
+        assert!(!pinned.contains("patch"), "{pinned}");
+    }
+    assert_eq!(fs::read_to_string(&manifest_path).unwrap(), manifest);
+
+    // Also when interrupted in the middle of a check.
+    let mut run = project
+        .bounds(&[
+            "--courtesy",
+            "--no-cache",
+            "--ignore-patches",
+            "test",
+            "--skip-baseline",
+            "--command",
+            "sleep 30",
+        ])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    thread::sleep(Duration::from_secs(1));
+    Command::new("kill")
+        .args(["-INT", &run.id().to_string()])
+        .status()
+        .unwrap();
+    run.wait().unwrap();
+    assert_eq!(fs::read_to_string(&manifest_path).unwrap(), manifest);
+}